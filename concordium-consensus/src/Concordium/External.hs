@@ -33,7 +33,6 @@ import Concordium.Types
 import Concordium.Types.Block (AbsoluteBlockHeight)
 import qualified Data.FixedByteString as FBS
 
-import Concordium.Afgjort.Finalize.Types (FinalizationInstance (FinalizationInstance))
 import Concordium.Birk.Bake
 import Concordium.Constants.Time (defaultEarlyBlockThreshold, defaultMaxBakingDelay)
 import Concordium.GlobalState
@@ -54,6 +53,7 @@ import qualified Concordium.Queries as Q
 import Concordium.Scheduler.Types
 import Concordium.Skov (
     BufferedFinalization (..),
+    FinalizationConfig,
     MessageType (..),
     NoFinalization (..),
     UpdateResult (..),
@@ -241,7 +241,7 @@ makeRegenesisRef = newForeignPtr
 --
 -- The use of the existential type is convenient, since it avoids or defers case analysis, while
 -- allowing for multiple possible configurations.
-data ConsensusRunner = forall gsconf finconf. ConsensusRunner (MultiVersionRunner gsconf finconf)
+data ConsensusRunner = forall gsconf finconf. (FinalizationConfig finconf) => ConsensusRunner (MultiVersionRunner gsconf finconf)
 
 -- |Result of starting consensus
 data StartResult
@@ -426,13 +426,7 @@ startConsensus
             -- Do globalstate migration if necessary
             migrateGlobalState appDataPath logM
             let mvcStateConfig = DiskStateConfig appDataPath
-            let mvcFinalizationConfig =
-                    BufferedFinalization
-                        ( FinalizationInstance
-                            (bakerSignKey bakerIdentity)
-                            (bakerElectionKey bakerIdentity)
-                            (bakerAggregationKey bakerIdentity)
-                        )
+            let mvcFinalizationConfig = BufferedFinalization (MV.bakerFinalizationInstance bakerIdentity)
             regenesisRef <- makeRegenesisRef regenesisFree regenesisPtr
             -- Callbacks
             let notifyCallback = callNotifyCallback notifyCbk
@@ -1324,6 +1318,47 @@ checkIfRunning cptr = do
     res <- runMVR Q.checkIsShutDown mvr
     return $! if res then 0 else 1
 
+-- |Look up the signature verification key of a baker in the baking committee for the current
+-- epoch of the best block. If the baker is in the committee, its 32-byte key is written to the
+-- supplied buffer and 1 is returned. Otherwise 0 is returned.
+currentBakerSignatureKey :: StablePtr ConsensusRunner -> Word64 -> Ptr Word8 -> IO Word8
+currentBakerSignatureKey cptr bid outKey = do
+    (ConsensusRunner mvr) <- deRefStablePtr cptr
+    mKey <- runMVR (Q.getCurrentBakerSignatureKey (BakerId (AccountIndex bid))) mvr
+    case mKey of
+        Nothing -> return 0
+        Just key -> do
+            BS.unsafeUseAsCStringLen (S.encode key) $ \(keyPtr, keyLen) ->
+                copyBytes outKey (castPtr keyPtr) (min 32 keyLen)
+            return 1
+
+-- |Replace the baker credentials used by consensus. The credentials are supplied as JSON, in the
+-- same format as on startup. The new credentials are used for the next block that is baked, for
+-- finalization rounds that start afterwards, and for the baker status reported by
+-- 'bakerStatusBestBlock'.
+-- Returns 0 on success, 1 if consensus was started without baker credentials, and 2 if the
+-- credentials could not be decoded.
+setBakerIdentity :: StablePtr ConsensusRunner -> CString -> Int64 -> IO Int64
+setBakerIdentity cptr bidC bidLenC = do
+    (ConsensusRunner mvr) <- deRefStablePtr cptr
+    bakerInfoBS <- BS.packCStringLen (bidC, fromIntegral bidLenC)
+    case AE.eitherDecodeStrict bakerInfoBS of
+        Left err -> do
+            mvLog mvr External LLError $ "Failed to decode baker identity data: " ++ err
+            return 2
+        Right bakerIdentity -> do
+            replaced <- MV.setBakerIdentity mvr bakerIdentity
+            return $! if replaced then 0 else 1
+
+-- |Get the epoch of the best block, and write its genesis index to the supplied pointer. Epochs
+-- restart at every protocol update.
+getBestBlockEpoch :: StablePtr ConsensusRunner -> Ptr Word32 -> IO Word64
+getBestBlockEpoch cptr genesisIndexPtr = do
+    (ConsensusRunner mvr) <- deRefStablePtr cptr
+    (genIndex, epoch) <- runMVR Q.getBestBlockEpoch mvr
+    poke genesisIndexPtr (fromIntegral genIndex)
+    return $! fromIntegral epoch
+
 -- |Check whether we are a baker from the perspective of the best block.
 -- bakerIdPtr expects to receive the baker ID (optional).
 -- hasBakerIdPtr expects to receive either 0 (representing false) or 1 (representing true) if a baker ID is not found or found respectively.
@@ -1494,8 +1529,11 @@ foreign export ccall getCryptographicParameters :: StablePtr ConsensusRunner ->
 
 -- baker status checking
 foreign export ccall bakerStatusBestBlock :: StablePtr ConsensusRunner -> Ptr Word64 -> Ptr Word8 -> IO Word8
+foreign export ccall currentBakerSignatureKey :: StablePtr ConsensusRunner -> Word64 -> Ptr Word8 -> IO Word8
 foreign export ccall checkIfWeAreFinalizer :: StablePtr ConsensusRunner -> IO Word8
 foreign export ccall checkIfRunning :: StablePtr ConsensusRunner -> IO Word8
+foreign export ccall setBakerIdentity :: StablePtr ConsensusRunner -> CString -> Int64 -> IO Int64
+foreign export ccall getBestBlockEpoch :: StablePtr ConsensusRunner -> Ptr Word32 -> IO Word64
 
 -- maintenance
 foreign export ccall freeCStr :: CString -> IO ()
@@ -23,6 +23,8 @@ import Data.Int
 import qualified Data.ProtoLens as Proto
 import qualified Data.ProtoLens.Combinators as Proto
 import qualified Data.Serialize as S
+import qualified Data.Text as Text
+import Data.Time (getCurrentTime)
 import qualified Data.Vector as Vec
 import Data.Word
 import Foreign
@@ -45,7 +47,9 @@ import Concordium.MultiVersion (
 import Concordium.Crypto.SHA256 (DigestSize, Hash (Hash))
 import Concordium.GlobalState.Parameters (CryptographicParameters)
 import Concordium.ID.Parameters (withGlobalContext)
+import Concordium.Types.Execution (TxResult (..))
 import qualified Concordium.Types.InvokeContract as InvokeContract
+import qualified Concordium.Types.Transactions as Transactions
 import qualified Concordium.Wasm as Wasm
 import Data.Text (Text)
 import qualified Data.Text.Encoding as Text
@@ -503,6 +507,56 @@ invokeInstanceV2 cptr blockIdType blockIdPtr contractIndex contractSubindex invo
             BS.unsafeUseAsCStringLen encoded (\(ptr, len) -> copier outVec (castPtr ptr) (fromIntegral len))
             return $ queryResultCode QRSuccess
 
+dryRunTransactionV2 ::
+    StablePtr Ext.ConsensusRunner ->
+    -- |Block type.
+    Word8 ->
+    -- |Block hash.
+    Ptr Word8 ->
+    -- |Pointer to the serialized account transaction.
+    Ptr Word8 ->
+    -- |Length of the serialized account transaction.
+    Word32 ->
+    -- |Out pointer for writing the block hash that was used.
+    Ptr Word8 ->
+    -- |Out pointer for writing whether the transaction is valid. If it is (1),
+    -- the output is the summary of the transaction, otherwise (0) it is the
+    -- UTF-8 encoded reason why it would not be included in a block.
+    Ptr Word8 ->
+    Ptr ReceiverVec ->
+    -- |Callback to output data.
+    FunPtr CopyToVecCallback ->
+    IO Int64
+dryRunTransactionV2 cptr blockType blockHashPtr transactionPtr transactionLen outHash outValid outVec copierCbk = do
+    Ext.ConsensusRunner mvr <- deRefStablePtr cptr
+    let copier = callCopyToVecCallback copierCbk
+    bhi <- decodeBlockHashInput blockType blockHashPtr
+    transactionBytes <- BS.packCStringLen (castPtr transactionPtr, fromIntegral transactionLen)
+    case S.decode transactionBytes of
+        Left _ -> return $ queryResultCode QRInvalidArgument
+        Right transaction -> do
+            now <- utcTimeToTransactionTime <$> getCurrentTime
+            (bh, result) <- runMVR (Q.dryRunTransaction bhi (Transactions.fromAccountTransaction now transaction)) mvr
+            copyHashTo outHash bh
+            case dryRunOutcome <$> result of
+                Nothing -> return $ queryResultCode QRNotFound
+                Just (Left e) -> do
+                    mvLog mvr Logger.External Logger.LLError $ "Internal conversion error occured for a transaction dry-run in block '" ++ show bh ++ "': " ++ show e
+                    return $ queryResultCode QRInternalError
+                Just (Right (valid, encoded)) -> do
+                    poke outValid (if valid then 1 else 0)
+                    BS.unsafeUseAsCStringLen encoded (\(ptr, len) -> copier outVec (castPtr ptr) (fromIntegral len))
+                    return $ queryResultCode QRSuccess
+  where
+    invalid :: String -> (Bool, BS.ByteString)
+    invalid reason = (False, Text.encodeUtf8 (Text.pack reason))
+    -- 'Nothing' means that the transaction would exceed the maximum block energy.
+    dryRunOutcome Nothing = Right $ invalid "The transaction exceeds the maximum block energy."
+    dryRunOutcome (Just (TxInvalid failure)) = Right $ invalid (show failure)
+    dryRunOutcome (Just (TxValid summary)) = do
+        protoSummary :: Proto.BlockItemSummary <- toProto summary
+        return (True, Proto.encodeMessage protoSummary)
+
 getBlockInfoV2 ::
     StablePtr Ext.ConsensusRunner ->
     -- |Block type.
@@ -1251,6 +1305,25 @@ foreign export ccall
         FunPtr CopyToVecCallback ->
         IO Int64
 
+foreign export ccall
+    dryRunTransactionV2 ::
+        StablePtr Ext.ConsensusRunner ->
+        -- |Block type.
+        Word8 ->
+        -- |Block hash.
+        Ptr Word8 ->
+        -- |Pointer to the serialized account transaction.
+        Ptr Word8 ->
+        -- |Length of the serialized account transaction.
+        Word32 ->
+        -- |Out pointer for writing the block hash that was used.
+        Ptr Word8 ->
+        -- |Out pointer for writing whether the transaction is valid.
+        Ptr Word8 ->
+        Ptr ReceiverVec ->
+        FunPtr CopyToVecCallback ->
+        IO Int64
+
 foreign export ccall
     getCryptographicParametersV2 ::
         StablePtr Ext.ConsensusRunner ->
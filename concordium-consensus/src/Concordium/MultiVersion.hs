@@ -196,8 +196,9 @@ data Callbacks = Callbacks
 
 -- |Baker identity and baking thread 'MVar'.
 data Baker = Baker
-    { -- |The baker ID and keys to use for baking.
-      bakerIdentity :: !BakerIdentity,
+    { -- |The baker ID and keys to use for baking and finalization.
+      -- This is only updated by a thread that holds the global lock, see 'setBakerIdentity'.
+      bakerIdentity :: !(IORef BakerIdentity),
       -- |An 'MVar' holding the 'ThreadId' of the baker thread.
       -- If present, the 'ThreadId' should be that of the singular baker thread.
       -- Stopping the baker thread is accomplished by taking the 'MVar' and
@@ -416,6 +417,7 @@ newGenesis (PVGenesisData (gd :: GenesisData pv)) vcGenesisHeight =
                         ++ show vcGenesisHeight
                 oldVersions <- readIORef mvVersions
                 let vcIndex = fromIntegral (length oldVersions)
+                finconf <- currentFinalizationConfig mvcFinalizationConfig mvBaker
                 (vcContext, st) <-
                     runLoggerT
                         ( Skov.initialiseNewSkov
@@ -427,7 +429,7 @@ newGenesis (PVGenesisData (gd :: GenesisData pv)) vcGenesisHeight =
                                     vcIndex
                                     vcGenesisHeight
                                 )
-                                mvcFinalizationConfig
+                                finconf
                                 UpdateHandler
                             )
                         )
@@ -476,6 +478,7 @@ checkForProtocolUpdate = liftSkov body
                 let vcIndex = fromIntegral (length existingVersions)
                 -- construct the the new skov instance
                 let vcGenesisHeight = 1 + localToAbsoluteBlockHeight latestEraGenesisHeight pvInitFinalHeight
+                finconf <- liftIO $ currentFinalizationConfig (mvcFinalizationConfig mvConfiguration) mvBaker
                 let newGSConfig =
                         ( Skov.SkovConfig @newpv @gc @fc
                             ( globalStateConfig
@@ -484,7 +487,7 @@ checkForProtocolUpdate = liftSkov body
                                 vcIndex
                                 vcGenesisHeight
                             )
-                            (mvcFinalizationConfig mvConfiguration)
+                            finconf
                             UpdateHandler
                         )
                 -- clear data we no longer need after the protocol update
@@ -595,7 +598,8 @@ makeMultiVersionRunner
     mbakerIdentity
     mvLog
     genesis = do
-        mvBaker <- forM mbakerIdentity $ \bakerIdentity -> do
+        mvBaker <- forM mbakerIdentity $ \ident -> do
+            bakerIdentity <- newIORef ident
             bakerThread <- newEmptyMVar
             return Baker{..}
         mvVersions <- newIORef Vec.empty
@@ -666,6 +670,7 @@ startupSkov genesis = do
                               mvConfiguration = MultiVersionConfiguration{..},
                               ..
                             } -> do
+                                finconf <- currentFinalizationConfig mvcFinalizationConfig mvBaker
                                 r <-
                                     runLoggerT
                                         ( Skov.initialiseExistingSkov
@@ -676,7 +681,7 @@ startupSkov genesis = do
                                                     vcIndex
                                                     vcGenesisHeight
                                                 )
-                                                mvcFinalizationConfig
+                                                finconf
                                                 UpdateHandler
                                             )
                                         )
@@ -789,7 +794,8 @@ startBaker mvr@MultiVersionRunner{mvBaker = Just Baker{..}, ..} = do
                 -- If the genesis index has changed, we reset the slot counter to 0, since this
                 -- is a different chain.
                 let nextSlot = if vcIndex vc == lastGenIndex then slot else 0
-                (vcIndex vc,) <$> runMVR (liftSkovUpdate vc (tryBake bakerIdentity nextSlot)) mvr
+                ident <- readIORef bakerIdentity
+                (vcIndex vc,) <$> runMVR (liftSkovUpdate vc (tryBake ident nextSlot)) mvr
         case res of
             BakeSuccess slot' block -> do
                 broadcastBlock mvCallbacks genIndex block
@@ -823,6 +829,50 @@ stopBaker MultiVersionRunner{mvBaker = Just Baker{..}, ..} = do
             Nothing -> mvLog Runner LLWarning "Attempted to stop baker thread, but it was not running."
             Just thrd -> killThread thrd
 
+-- |The keys used for finalization by a baker.
+bakerFinalizationInstance :: BakerIdentity -> FinalizationInstance
+bakerFinalizationInstance BakerIdentity{..} =
+    FinalizationInstance bakerSignKey bakerElectionKey bakerAggregationKey
+
+-- |The finalization configuration to use when starting a consensus instance. If there is a
+-- baker, its current keys are used, since they may have been replaced by 'setBakerIdentity'
+-- since the runner was created.
+currentFinalizationConfig :: (Skov.FinalizationConfig finconf) => finconf -> Maybe Baker -> IO finconf
+currentFinalizationConfig finconf Nothing = return finconf
+currentFinalizationConfig finconf (Just Baker{..}) = do
+    ident <- readIORef bakerIdentity
+    return $! Skov.setFinalizationInstance (bakerFinalizationInstance ident) finconf
+
+-- |Replace the baker identity associated with a 'MultiVersionRunner'. The new identity is used
+-- for the next block that is baked, for finalization rounds that start afterwards, and for
+-- determining the baker status. A finalization round that is in progress is completed with the
+-- previous keys. Returns 'False' if the runner was initialised without baker credentials.
+setBakerIdentity ::
+    (Skov.FinalizationConfig finconf) =>
+    MultiVersionRunner gsconf finconf ->
+    BakerIdentity ->
+    IO Bool
+setBakerIdentity MultiVersionRunner{mvBaker = Nothing, ..} _ = do
+    mvLog
+        Runner
+        LLError
+        "Attempted to replace the baker credentials, but consensus was started without baker credentials."
+    return False
+setBakerIdentity mvr@MultiVersionRunner{mvBaker = Just Baker{..}, ..} ident = do
+    withWriteLockIO mvr $ do
+        writeIORef bakerIdentity ident
+        -- Only the current consensus instance takes part in finalization.
+        let updateLast versions
+                | Vec.null versions = versions
+                | otherwise = case Vec.last versions of
+                    EVersionedConfiguration vc ->
+                        Vec.init versions
+                            `Vec.snoc` EVersionedConfiguration
+                                vc{vcContext = Skov.setSkovFinalizationInstance (bakerFinalizationInstance ident) (vcContext vc)}
+        modifyIORef' mvVersions updateLast
+    mvLog Runner LLInfo $ "Replaced the baker credentials. Now using the keys of baker " ++ show (bakerId ident) ++ "."
+    return True
+
 -- |Set the flag to stop importing the blocks to `True`.
 stopImportingBlocks :: MultiVersionRunner gsconf finconf -> IO ()
 stopImportingBlocks MultiVersionRunner{..} = mask_ $ do
@@ -29,7 +29,7 @@ import Concordium.Types
 import Concordium.Types.Accounts
 import Concordium.Types.AnonymityRevokers
 import Concordium.Types.Block (absoluteToLocalBlockHeight, localToAbsoluteBlockHeight)
-import Concordium.Types.Execution (TransactionSummary)
+import Concordium.Types.Execution (TransactionSummary, TxResult)
 import Concordium.Types.HashableTo
 import Concordium.Types.IdentityProviders
 import Concordium.Types.Parameters
@@ -39,7 +39,10 @@ import Concordium.Types.Transactions
 import qualified Concordium.Types.UpdateQueues as UQ
 import qualified Concordium.Wasm as Wasm
 
+import qualified Concordium.Scheduler as Scheduler
+import qualified Concordium.Scheduler.EnvironmentImplementation as EnvImpl
 import qualified Concordium.Scheduler.InvokeContract as InvokeContract
+import qualified Concordium.TransactionVerification as TVer
 import qualified Concordium.Types.InvokeContract as InvokeContract
 
 import Concordium.Afgjort.Finalize.Types (FinalizationCommittee (..), PartyInfo (..))
@@ -984,6 +987,47 @@ invokeContract bhi cctx =
         )
         bhi
 
+-- * Transaction dry-runs
+
+-- |Execute an account transaction in the state at the end of the given block, without
+-- committing it to any state, adding it to the transaction table or sending it to peers.
+--
+-- The signatures of the transaction are not checked, so that the cost of a transaction can be
+-- estimated before it is signed. The cost does depend on the number of signatures, so the
+-- transaction should carry one (possibly dummy) signature for each key that will sign it.
+--
+-- The result is 'Nothing' if the transaction would exceed the maximum block energy, and
+-- otherwise either the reason the transaction would not be included in a block, or its
+-- summary if it would.
+dryRunTransaction :: BlockHashInput -> Transaction -> MVR gsconf finconf (BlockHash, Maybe (Maybe TxResult))
+dryRunTransaction bhi tx =
+    liftSkovQueryBHI
+        ( \bp -> do
+            bs <- blockState bp
+            cm <- ChainMetadata <$> getSlotTimestamp (blockSlot bp)
+            maxBlockEnergy <- gdMaxBlockEnergy <$> getGenesisData
+            -- The scheduler skips the signature check when given a successful verification
+            -- result for the current keys of the sender.
+            verRes <-
+                BS.getAccount bs (transactionSender tx) >>= \case
+                    Nothing -> return Nothing
+                    Just (_, acc) -> do
+                        keys <- BS.getAccountVerificationKeys acc
+                        return $ Just $ TVer.Ok $ TVer.NormalTransactionSuccess (getHash keys) (transactionNonce tx)
+            ubs <- BS.thawBlockState bs
+            let context =
+                    EnvImpl.ContextState
+                        { _chainMetadata = cm,
+                          _maxBlockEnergy = maxBlockEnergy,
+                          -- account transactions do not create accounts
+                          _accountCreationLimit = 0
+                        }
+            (res, finState) <- EnvImpl.runSchedulerT (Scheduler.dispatch (tx, verRes)) context (EnvImpl.makeInitialSchedulerState ubs)
+            BS.dropUpdatableBlockState (finState ^. EnvImpl.ssBlockState)
+            return res
+        )
+        bhi
+
 -- * Miscellaneous
 
 -- |Check whether the node is currently a member of the finalization committee.
@@ -1006,35 +1050,59 @@ data BakerStatus
       AddedButWrongKeys
     deriving (Eq, Ord, Show)
 
+-- |Get the signature verification key of a baker, if it is in the baking committee for the
+-- current epoch of the best block.
+getCurrentBakerSignatureKey :: BakerId -> MVR gsconf finconf (Maybe BakerSignVerifyKey)
+getCurrentBakerSignatureKey bid = liftSkovQueryLatest $ do
+    bb <- bestBlock
+    bs <- queryBlockState bb
+    bakers <- BS.getCurrentEpochBakers bs
+    return $ (^. bakerInfo . bakerSignatureVerifyKey) <$> fullBaker bakers bid
+
+-- |Get the genesis index and the epoch of the best block. Epochs are counted from the genesis
+-- block of each genesis index, so they restart at every protocol update.
+getBestBlockEpoch :: MVR gsconf finconf (GenesisIndex, Epoch)
+getBestBlockEpoch = MVR $ \mvr -> do
+    versions <- readIORef (mvVersions mvr)
+    case Vec.last versions of
+        evc@(EVersionedConfiguration vc) -> do
+            epoch <- liftSkovQuery mvr evc $ do
+                bb <- bestBlock
+                epochLength <- gdEpochLength <$> getGenesisData
+                return $! fromIntegral (blockSlot bb) `div` fromIntegral epochLength
+            return (vcIndex vc, epoch)
+
 -- |Determine the status of the baker with respect to the current best block.
 getBakerStatusBestBlock :: MVR gsconf finconf (BakerStatus, Maybe BakerId)
 getBakerStatusBestBlock =
     asks mvBaker >>= \case
         Nothing -> return (NotInCommittee, Nothing)
-        Just Baker{bakerIdentity = bakerIdent} -> liftSkovQueryLatest $ do
-            bb <- bestBlock
-            bs <- queryBlockState bb
-            bakers <- BS.getCurrentEpochBakers bs
-            bakerStatus <- case fullBaker bakers (bakerId bakerIdent) of
-                Just fbinfo
-                    -- Current baker with valid keys
-                    | validateBakerKeys (fbinfo ^. bakerInfo) bakerIdent ->
-                        return ActiveInComittee
-                    -- Current baker, but invalid keys
-                    | otherwise -> return AddedButWrongKeys
-                Nothing ->
-                    -- Not a current baker
-                    BS.getBakerAccount bs (bakerId bakerIdent) >>= \case
-                        Just acc ->
-                            -- Account is valid
-                            BS.getAccountBaker acc >>= \case
-                                -- Account has no registered baker
-                                Nothing -> return NotInCommittee
-                                Just ab
-                                    -- Registered baker with valid keys
-                                    | validateBakerKeys (ab ^. accountBakerInfo . bakerInfo) bakerIdent ->
-                                        return AddedButNotActiveInCommittee
-                                    -- Registered baker with invalid keys
-                                    | otherwise -> return AddedButWrongKeys
-                        Nothing -> return NotInCommittee
-            return (bakerStatus, Just $ bakerId bakerIdent)
+        Just Baker{bakerIdentity = bakerIdentRef} -> do
+            bakerIdent <- liftIO $ readIORef bakerIdentRef
+            liftSkovQueryLatest $ do
+                bb <- bestBlock
+                bs <- queryBlockState bb
+                bakers <- BS.getCurrentEpochBakers bs
+                bakerStatus <- case fullBaker bakers (bakerId bakerIdent) of
+                    Just fbinfo
+                        -- Current baker with valid keys
+                        | validateBakerKeys (fbinfo ^. bakerInfo) bakerIdent ->
+                            return ActiveInComittee
+                        -- Current baker, but invalid keys
+                        | otherwise -> return AddedButWrongKeys
+                    Nothing ->
+                        -- Not a current baker
+                        BS.getBakerAccount bs (bakerId bakerIdent) >>= \case
+                            Just acc ->
+                                -- Account is valid
+                                BS.getAccountBaker acc >>= \case
+                                    -- Account has no registered baker
+                                    Nothing -> return NotInCommittee
+                                    Just ab
+                                        -- Registered baker with valid keys
+                                        | validateBakerKeys (ab ^. accountBakerInfo . bakerInfo) bakerIdent ->
+                                            return AddedButNotActiveInCommittee
+                                        -- Registered baker with invalid keys
+                                        | otherwise -> return AddedButWrongKeys
+                            Nothing -> return NotInCommittee
+                return (bakerStatus, Just $ bakerId bakerIdent)
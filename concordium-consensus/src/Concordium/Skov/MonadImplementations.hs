@@ -124,6 +124,14 @@ class FinalizationConfig finconfig where
     -- |Generate an initial context and state from a finalization configuration.
     initialiseFinalization :: (MonadIO m, SkovQueryMonad m) => finconfig -> m (FCContext finconfig, FCState finconfig)
 
+    -- |Replace the keys used for finalization in a finalization configuration.
+    -- Configurations that do not participate in finalization are unchanged.
+    setFinalizationInstance :: FinalizationInstance -> finconfig -> finconfig
+
+    -- |Replace the keys used for finalization in a finalization context.
+    -- Contexts that do not participate in finalization are unchanged.
+    setContextFinalizationInstance :: Proxy finconfig -> FinalizationInstance -> FCContext finconfig -> FCContext finconfig
+
 -- |Type of finalization configuration for no active participation in finalization.
 -- The type parameter is the type of timers supported by the 'TimerMonad' in which finalization
 -- operations occur.
@@ -134,6 +142,8 @@ instance FinalizationConfig (NoFinalization t) where
     type FCState (NoFinalization t) = FinalizationState t
     initialiseFinalization NoFinalization =
         ((),) <$> recoverFinalizationState Nothing
+    setFinalizationInstance _ = id
+    setContextFinalizationInstance _ _ = id
 
 -- This provides an implementation of FinalizationOutputMonad that does nothing.
 -- This should be fine, because NoFinalization indicates that no participation in
@@ -155,6 +165,8 @@ instance FinalizationConfig (ActiveFinalization t) where
     type FCState (ActiveFinalization t) = FinalizationState t
     initialiseFinalization (ActiveFinalization finInst) =
         (finInst,) <$> recoverFinalizationState (Just finInst)
+    setFinalizationInstance finInst _ = ActiveFinalization finInst
+    setContextFinalizationInstance _ finInst _ = finInst
 
 instance
     (SkovFinalizationHandlers h m, Monad m) =>
@@ -174,6 +186,8 @@ instance FinalizationConfig (BufferedFinalization t) where
     initialiseFinalization (BufferedFinalization finInst) = do
         finalizationState <- recoverFinalizationState (Just finInst)
         return (finInst, BufferedFinalizationState finalizationState emptyFinalizationBuffer)
+    setFinalizationInstance finInst _ = BufferedFinalization finInst
+    setContextFinalizationInstance _ finInst _ = finInst
 
 instance
     (SkovFinalizationHandlers h m, Monad m, TimeMonad m, MonadLogger m, SkovTimerHandlers pv h (SkovConfig pv gc (BufferedFinalization t) hc) m) =>
@@ -205,6 +219,17 @@ data instance SkovContext (SkovConfig pv gsconf finconf hconf) = SkovContext
       scHandlerContext :: !(HCContext hconf)
     }
 
+-- |Replace the keys used for finalization in a 'SkovContext'. Finalization rounds that start
+-- after this use the new keys.
+setSkovFinalizationInstance ::
+    forall pv gsconf finconf hconf.
+    (FinalizationConfig finconf) =>
+    FinalizationInstance ->
+    SkovContext (SkovConfig pv gsconf finconf hconf) ->
+    SkovContext (SkovConfig pv gsconf finconf hconf)
+setSkovFinalizationInstance finInst ctx =
+    ctx{scFinContext = setContextFinalizationInstance (Proxy @finconf) finInst (scFinContext ctx)}
+
 -- |The type of states (i.e. mutable data) for the skov configuration type.
 data family SkovState c
 
@@ -222,7 +247,7 @@ type instance SkovGSContext (SkovConfig pv gsconf finconf hconf) = GSContext gsc
 -- |A pair of 'SkovContext' and 'SkovState' for a given 'SkovConfig' determined by the type parameters.
 type InitialisedSkov pv gsconfig finconfig handlerconfig = (SkovContext (SkovConfig pv gsconfig finconfig handlerconfig), SkovState (SkovConfig pv gsconfig finconfig handlerconfig))
 
-class SkovConfiguration gsconfig finconfig handlerconfig where
+class (FinalizationConfig finconfig) => SkovConfiguration gsconfig finconfig handlerconfig where
     -- |Create an initial context and state from a given configuration. The
     -- return value is 'Maybe' if an existing state was found for the given
     -- configuration and successfully loaded. In case the state is found, but
@@ -130,17 +130,31 @@ fn main() -> std::io::Result<()> {
         .compile(&[&proto], &[&proto_root_input])
         .expect("Failed to compile gRPC definitions!");
 
-    build_grpc2(&proto_root_input)?;
+    // The messages specific to this node, in the same package as the types.
+    let node_proto_root = format!("{}/proto", cargo_dir);
+    build_grpc2(&proto_root_input, &node_proto_root)?;
     Ok(())
 }
 
 // Compile the types for GRPC2 API and generate a service description for the
 // GRPC2 interface.
-fn build_grpc2(proto_root_input: &str) -> std::io::Result<()> {
+fn build_grpc2(proto_root_input: &str, node_proto_root: &str) -> std::io::Result<()> {
     {
         let types = format!("{}/v2/concordium/types.proto", proto_root_input);
         println!("cargo:rerun-if-changed={}", types);
-        prost_build::compile_protos(&[types], &[proto_root_input])?;
+        let node_types = format!("{}/v2/concordium/node_types.proto", node_proto_root);
+        println!("cargo:rerun-if-changed={}", node_types);
+        let descriptor_path =
+            std::path::PathBuf::from(env::var("OUT_DIR").unwrap()).join("types_descriptor.bin");
+        prost_build::Config::new()
+            .file_descriptor_set_path(&descriptor_path)
+            .compile_protos(&[types, node_types], &[proto_root_input, node_proto_root])?;
+        // The JSON mapping of the types is used by the REST gateway. It follows
+        // the canonical JSON encoding of protobuf messages.
+        let descriptor_set = std::fs::read(descriptor_path)?;
+        pbjson_build::Builder::new()
+            .register_descriptors(&descriptor_set)?
+            .build(&[".concordium.v2"])?;
     }
 
     // Because we serialize messages in Haskell we need to construct the service
@@ -588,7 +602,7 @@ fn build_grpc2(proto_root_input: &str) -> std::io::Result<()> {
             tonic_build::manual::Method::builder()
                 .name("dump_start")
                 .route_name("DumpStart")
-                .input_type("crate::grpc2::types::DumpRequest")
+                .input_type("crate::grpc2::types::DumpStartRequest")
                 .output_type("crate::grpc2::types::Empty")
                 .codec_path("tonic::codec::ProstCodec")
                 .build(),
@@ -602,6 +616,60 @@ fn build_grpc2(proto_root_input: &str) -> std::io::Result<()> {
                 .codec_path("tonic::codec::ProstCodec")
                 .build(),
         )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("get_network_limits")
+                .route_name("GetNetworkLimits")
+                .input_type("crate::grpc2::types::Empty")
+                .output_type("crate::grpc2::types::NetworkLimits")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("set_network_limits")
+                .route_name("SetNetworkLimits")
+                .input_type("crate::grpc2::types::SetNetworkLimitsRequest")
+                .output_type("crate::grpc2::types::NetworkLimits")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("set_baker_credentials")
+                .route_name("SetBakerCredentials")
+                .input_type("crate::grpc2::types::SetBakerCredentialsRequest")
+                .output_type("crate::grpc2::types::SetBakerCredentialsResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("start_baker")
+                .route_name("StartBaker")
+                .input_type("crate::grpc2::types::Empty")
+                .output_type("crate::grpc2::types::Empty")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("stop_baker")
+                .route_name("StopBaker")
+                .input_type("crate::grpc2::types::Empty")
+                .output_type("crate::grpc2::types::Empty")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("get_baker_status")
+                .route_name("GetBakerStatus")
+                .input_type("crate::grpc2::types::Empty")
+                .output_type("crate::grpc2::types::BakerStatus")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
         .method(
             tonic_build::manual::Method::builder()
                 .name("get_node_info")
@@ -630,6 +698,56 @@ fn build_grpc2(proto_root_input: &str) -> std::io::Result<()> {
                 .server_streaming()
                 .build(),
         )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("get_finalized_blocks_from")
+                .route_name("GetFinalizedBlocksFrom")
+                .input_type("crate::grpc2::types::AbsoluteBlockHeight")
+                .output_type("Vec<u8>")
+                .codec_path("crate::grpc2::RawCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("get_finalized_transaction_events")
+                .route_name("GetFinalizedTransactionEvents")
+                .input_type("crate::grpc2::types::FinalizedTransactionEventsRequest")
+                .output_type("Vec<u8>")
+                .codec_path("crate::grpc2::RawCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("dry_run_transaction")
+                .route_name("DryRunTransaction")
+                .input_type("crate::grpc2::types::DryRunTransactionRequest")
+                .output_type("Vec<u8>")
+                .codec_path("crate::grpc2::RawCodec")
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("wait_for_block_item_finalization")
+                .route_name("WaitForBlockItemFinalization")
+                .input_type("crate::grpc2::types::TransactionHash")
+                .output_type("Vec<u8>")
+                .codec_path("crate::grpc2::RawCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("send_block_items")
+                .route_name("SendBlockItems")
+                .input_type("crate::grpc2::types::SendBlockItemRequest")
+                .output_type("crate::grpc2::types::SendBlockItemsResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        )
         .build();
     // Due to the slightly hacky nature of the RawCodec (i.e., it does not support
     // deserialization) we cannot build the client. But we also don't need it in the
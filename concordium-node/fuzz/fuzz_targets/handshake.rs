@@ -0,0 +1,60 @@
+#![no_main]
+use concordium_base::hashes::BlockHash;
+use concordium_node::{
+    common::P2PNodeId,
+    network::{Capabilities, Handshake, NetworkId, NetworkMessage, NetworkPayload, NetworkRequest},
+};
+use libfuzzer_sys::{arbitrary::Arbitrary, fuzz_target};
+use semver::Version;
+
+/// A handshake built from the fuzzer input, along with byte-level corruptions
+/// applied to its serialized form.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    node_id:        u64,
+    port:           u16,
+    networks:       Vec<u16>,
+    node_version:   (u64, u64, u64),
+    wire_versions:  Vec<u8>,
+    genesis_blocks: Vec<[u8; 32]>,
+    capabilities:   u64,
+    corruptions:    Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let handshake = Handshake {
+        remote_id:      P2PNodeId(input.node_id),
+        remote_port:    input.port,
+        networks:       input.networks.iter().copied().map(NetworkId::from).collect(),
+        node_version:   Version::new(
+            input.node_version.0,
+            input.node_version.1,
+            input.node_version.2,
+        ),
+        wire_versions:  input.wire_versions,
+        genesis_blocks: input.genesis_blocks.into_iter().map(BlockHash::new).collect(),
+        proof:          Vec::new(),
+        capabilities:   Capabilities::from_bits(input.capabilities),
+    };
+    let payload = NetworkPayload::NetworkRequest(NetworkRequest::Handshake(handshake));
+    let message = NetworkMessage {
+        created: 0,
+        received: None,
+        payload,
+    };
+    let mut buffer = Vec::new();
+    message.serialize(&mut buffer).expect("Serialization into memory is infallible.");
+
+    if input.corruptions.is_empty() {
+        let deserialized =
+            NetworkMessage::deserialize(&buffer).expect("A valid handshake was rejected.");
+        assert_eq!(deserialized.payload, message.payload);
+    } else {
+        for (pos, mask) in input.corruptions {
+            let pos = usize::from(pos) % buffer.len();
+            buffer[pos] ^= mask;
+        }
+        // a corrupted handshake may or may not parse, but it must not panic
+        let _ = NetworkMessage::deserialize(&buffer);
+    }
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use concordium_node::network::NetworkMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = NetworkMessage::deserialize(data);
+});
@@ -0,0 +1,31 @@
+#![no_main]
+use concordium_node::{
+    common::PeerType,
+    connection::read_untrusted_input,
+    p2p::P2PNode,
+    test_utils::{dummy_regenesis_blocks, make_node_and_sync, next_available_port},
+};
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use std::sync::Arc;
+
+lazy_static! {
+    static ref NODE: Arc<P2PNode> = {
+        let (node, _) = make_node_and_sync(
+            next_available_port(),
+            vec![100],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .expect("Cannot start the node.");
+        node
+    };
+}
+
+// The first byte selects the role of the local end, the rest is what the
+// remote end sends over the socket.
+fuzz_target!(|data: &[u8]| {
+    if let Some((&role, input)) = data.split_first() {
+        let _ = read_untrusted_input(&NODE, role & 1 == 1, input);
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use concordium_base::common::{Deserial, Serial};
+use concordium_node::p2p::bans::PersistedBanId;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cursor = Cursor::new(data);
+    if let Ok(ban_id) = PersistedBanId::deserial(&mut cursor) {
+        // whatever was accepted must serialize back to the bytes it was read from
+        let mut serialized = Vec::new();
+        ban_id.serial(&mut serialized);
+        assert_eq!(&serialized[..], &data[..cursor.position() as usize]);
+    }
+});
@@ -19,6 +19,7 @@ use std::{env, process::Command, thread, time::Duration};
 
 fn main() -> anyhow::Result<()> {
     let (mut conf, app_prefs) = get_config_and_logging_setup()?;
+    ensure!(!conf.common.check_config, "The check-config mode is only supported by the node.");
     let data_dir_path = app_prefs.get_data_dir();
 
     conf.connection.max_allowed_nodes = Some(0);
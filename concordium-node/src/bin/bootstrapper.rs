@@ -19,6 +19,7 @@ use concordium_node::stats_export_service::start_push_gateway;
 
 fn main() -> anyhow::Result<()> {
     let (mut conf, app_prefs) = get_config_and_logging_setup()?;
+    ensure!(!conf.common.check_config, "The check-config mode is only supported by the node.");
     conf.connection.max_allowed_nodes = Some(conf.bootstrapper.max_nodes);
     let data_dir_path = app_prefs.get_data_dir();
 
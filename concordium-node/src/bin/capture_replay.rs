@@ -0,0 +1,246 @@
+//! Replays a network capture written by the `network_dump` feature.
+//!
+//! The messages from the capture are sent, with their original timing or at
+//! an accelerated pace, by a fresh node that connects either to the given
+//! node or to a local node started with the `test_utils` harness. Handshakes
+//! from the capture are skipped, since the replaying node performs its own.
+
+#[macro_use]
+extern crate log;
+
+use anyhow::{ensure, Context};
+use chrono::prelude::{DateTime, Utc};
+use concordium_base::hashes::BlockHash;
+use concordium_node::{
+    capture::{capture_files, CaptureReader, CaptureRecord},
+    common::{P2PNodeId, PeerType},
+    network::{
+        compression::{decompress, is_compressed},
+        NetworkMessage, NetworkPayload, NetworkRequest,
+    },
+    p2p::{connectivity, P2PNode},
+    test_utils::{
+        await_handshakes, connect, dummy_regenesis_blocks, make_node_and_sync, next_available_port,
+        stop_node_delete_dirs,
+    },
+};
+use env_logger::{Builder, Env};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "Network capture replay")]
+struct ReplayConfig {
+    #[structopt(
+        help = "Capture files, or directories containing them, to replay in the given order",
+        required = true
+    )]
+    captures:              Vec<PathBuf>,
+    #[structopt(
+        long = "prefix",
+        help = "Prefix of the capture files to replay from directories; defaults to all the \
+                capture files in a directory"
+    )]
+    prefix:                Option<String>,
+    #[structopt(
+        long = "target",
+        help = "Address of the node to replay the capture into; if omitted, a local node is \
+                started with the test harness"
+    )]
+    target:                Option<SocketAddr>,
+    #[structopt(
+        long = "speed",
+        help = "Replay speed relative to the original timing; 0 replays as fast as possible",
+        default_value = "1"
+    )]
+    speed:                 f64,
+    #[structopt(long = "from", help = "Skip the messages captured before this time (RFC 3339)")]
+    from:                  Option<DateTime<Utc>>,
+    #[structopt(long = "to", help = "Skip the messages captured after this time (RFC 3339)")]
+    to:                    Option<DateTime<Utc>>,
+    #[structopt(long = "peer", help = "Only replay the messages exchanged with this peer")]
+    peer:                  Option<P2PNodeId>,
+    #[structopt(
+        long = "include-outbound",
+        help = "Also replay the messages that were sent by the capturing node"
+    )]
+    include_outbound:      bool,
+    #[structopt(
+        long = "network-id",
+        help = "Network ids of the replaying node",
+        default_value = "1000",
+        use_delimiter = true
+    )]
+    network_ids:           Vec<u16>,
+    #[structopt(
+        long = "regenesis-blocks-file",
+        help = "JSON file with the genesis block hashes of the target node; the test harness \
+                blocks are used if omitted"
+    )]
+    regenesis_blocks_file: Option<PathBuf>,
+}
+
+impl ReplayConfig {
+    /// Check whether a record should be replayed.
+    fn selects(&self, record: &CaptureRecord) -> bool {
+        (record.inbound || self.include_outbound)
+            && self.peer.map_or(true, |peer| record.peer_id == Some(peer))
+            && self.to.map_or(true, |to| record.timestamp <= to)
+    }
+
+    /// The capture files to replay, in order.
+    fn capture_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for path in &self.captures {
+            if !path.is_dir() {
+                files.push(path.clone());
+            } else if let Some(ref prefix) = self.prefix {
+                files.extend(capture_files(path, prefix)?);
+            } else {
+                let mut prefixes = std::fs::read_dir(path)?
+                    .filter_map(|entry| {
+                        let name = entry.ok()?.file_name().into_string().ok()?;
+                        let (prefix, _) = name.strip_suffix(".capture")?.rsplit_once('-')?;
+                        Some(prefix.to_owned())
+                    })
+                    .collect::<Vec<_>>();
+                prefixes.sort();
+                prefixes.dedup();
+                for prefix in prefixes {
+                    files.extend(capture_files(path, &prefix)?);
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Prepare a captured message for sending. Compressed messages are sent in
+/// plain form, since compression is negotiated anew by the replaying node,
+/// and handshakes are skipped.
+fn prepare_message(record: &CaptureRecord) -> Option<Vec<u8>> {
+    let message = if is_compressed(&record.message) {
+        match decompress(&record.message) {
+            Ok((_, message)) => message,
+            Err(e) => {
+                warn!("Replaying an invalid compressed message as is: {}", e);
+                record.message.clone()
+            }
+        }
+    } else {
+        record.message.clone()
+    };
+    // malformed messages are replayed as well, as they might be what
+    // triggers the bug being reproduced
+    if let Ok(NetworkMessage {
+        payload: NetworkPayload::NetworkRequest(NetworkRequest::Handshake(_)),
+        ..
+    }) = NetworkMessage::deserialize(&message)
+    {
+        return None;
+    }
+    Some(message)
+}
+
+fn replay(conf: &ReplayConfig, node: &P2PNode) -> anyhow::Result<u64> {
+    let mut sent = 0u64;
+    // the timestamp of the first replayed message and the time it was sent
+    let mut start: Option<(DateTime<Utc>, Instant)> = None;
+    for path in conf.capture_files()? {
+        info!("Replaying {}", path.display());
+        let mut reader = CaptureReader::open(&path)?;
+        if let Some(from) = conf.from {
+            reader.seek_to(from)?;
+        }
+        for record in reader {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("Stopping the replay of {}: {}", path.display(), e);
+                    break;
+                }
+            };
+            if !conf.selects(&record) {
+                continue;
+            }
+            let message = match prepare_message(&record) {
+                Some(message) => message,
+                None => continue,
+            };
+            if conf.speed > 0.0 {
+                let (first_timestamp, started) =
+                    *start.get_or_insert_with(|| (record.timestamp, Instant::now()));
+                let offset = (record.timestamp - first_timestamp).to_std().unwrap_or_default();
+                let due = started + offset.div_f64(conf.speed);
+                let now = Instant::now();
+                if due > now {
+                    thread::sleep(due - now);
+                }
+            }
+            if node.send_over_all_connections(&message, &|_| true) == 0 {
+                warn!("The replay target is no longer connected");
+                return Ok(sent);
+            }
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+fn main() -> anyhow::Result<()> {
+    let conf = ReplayConfig::from_args();
+    ensure!(conf.speed >= 0.0, "The replay speed can't be negative.");
+    Builder::from_env(Env::default().filter_or("LOG_LEVEL", "info")).init();
+
+    let regenesis_blocks = if let Some(ref path) = conf.regenesis_blocks_file {
+        let contents = std::fs::read(path).with_context(|| {
+            format!("Could not read the genesis blocks from {}.", path.display())
+        })?;
+        serde_json::from_slice::<Vec<BlockHash>>(&contents)?
+    } else {
+        dummy_regenesis_blocks()
+    };
+
+    let (replayer, replayer_dp) = make_node_and_sync(
+        next_available_port(),
+        conf.network_ids.clone(),
+        PeerType::Node,
+        regenesis_blocks.clone(),
+    )?;
+    let local_target = if let Some(target) = conf.target {
+        connectivity::connect(&replayer, PeerType::Node, target, None, false)?;
+        None
+    } else {
+        let (local, local_dp) = make_node_and_sync(
+            next_available_port(),
+            conf.network_ids.clone(),
+            PeerType::Node,
+            regenesis_blocks,
+        )?;
+        connect(&replayer, &local);
+        Some((local, local_dp))
+    };
+    await_handshakes(&replayer);
+
+    let started = Instant::now();
+    let sent = replay(&conf, &replayer)?;
+    info!("Replayed {} messages in {:.1}s", sent, started.elapsed().as_secs_f64());
+
+    if let Some((local, local_dp)) = local_target {
+        // give the local node a moment to process the last messages
+        thread::sleep(Duration::from_secs(1));
+        info!(
+            "The local node received {} messages",
+            local.connection_handler.total_received.load(Ordering::Relaxed)
+        );
+        stop_node_delete_dirs(local_dp, local);
+    }
+    stop_node_delete_dirs(replayer_dp, replayer);
+    Ok(())
+}
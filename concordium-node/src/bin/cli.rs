@@ -11,18 +11,21 @@ static A: System = System;
 
 use anyhow::Context;
 use concordium_node::{
-    common::PeerType,
+    common::{P2PNodeId, PeerType},
     configuration as config,
     consensus_ffi::{
         consensus::{
             ConsensusContainer, ConsensusLogLevel, Regenesis, CALLBACK_QUEUE,
-            CONSENSUS_QUEUE_DEPTH_IN_HI, CONSENSUS_QUEUE_DEPTH_OUT_HI,
+            CONSENSUS_QUEUE_DEPTH_IN_BAKER, CONSENSUS_QUEUE_DEPTH_IN_HI,
+            CONSENSUS_QUEUE_DEPTH_OUT_HI,
         },
         ffi,
         helpers::QueueMsg,
         messaging::ConsensusMessage,
     },
     p2p::{
+        allowlist::PeerAllowlist,
+        baker_proof::check_baker_credentials,
         connectivity::connect,
         maintenance::{attempt_bootstrap, spawn},
         *,
@@ -35,8 +38,9 @@ use concordium_node::{
     utils::get_config_and_logging_setup,
 };
 use mio::{net::TcpListener, Poll};
-use rand::Rng;
 use reqwest::Client;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::{path::Path, sync::Arc, thread::JoinHandle};
 #[cfg(unix)]
 use tokio::signal::unix as unix_signal;
@@ -47,9 +51,20 @@ use tokio::sync::{broadcast, oneshot};
 use concordium_node::stats_export_service::start_push_gateway;
 use std::net::{IpAddr, SocketAddr};
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let (conf, mut app_prefs) = get_config_and_logging_setup()?;
+fn main() -> anyhow::Result<()> {
+    // The configuration is read before the runtime starts, since values from the
+    // configuration file are passed to the command line parser through the
+    // environment, which must not be modified once other threads are running.
+    let (conf, app_prefs) = get_config_and_logging_setup()?;
+    tokio::runtime::Runtime::new()
+        .context("Could not start the async runtime.")?
+        .block_on(run(conf, app_prefs))
+}
+
+async fn run(conf: config::Config, mut app_prefs: config::AppPreferences) -> anyhow::Result<()> {
+    if conf.common.check_config {
+        return check_config(&conf, &app_prefs);
+    }
 
     let stats_export_service = instantiate_stats_export_engine(&conf)?;
     let regenesis_arc: Arc<Regenesis> = Arc::new(Default::default());
@@ -63,6 +78,14 @@ async fn main() -> anyhow::Result<()> {
     // to avoid being interrupted in the middle of sensitive operations, e.g.,
     // creating the database.
     let (shutdown_sender, mut shutdown_receiver) = setup_shutdown_signal_handling();
+    #[cfg(unix)]
+    let reload_sender = setup_reload_signal_handling(
+        &node,
+        conf.common.config_file.clone(),
+        conf.connection
+            .max_allowed_nodes
+            .map_or(Some(conf.connection.max_allowed_nodes_percentage), |_| None),
+    )?;
 
     {
         let shutdown_sender = shutdown_sender.clone();
@@ -84,6 +107,11 @@ async fn main() -> anyhow::Result<()> {
 
     let (gen_data, priv_data) = get_baker_data(&app_prefs, &conf.cli.baker)
         .context("Can't get genesis data or private data. Aborting")?;
+    if let Some(credentials) = &priv_data {
+        if let Err(e) = node.set_baker_credentials(credentials) {
+            warn!("Handshakes will not include a baker proof: {:#}", e);
+        }
+    }
 
     let data_dir_path = app_prefs.get_data_dir();
     let mut database_directory = data_dir_path.to_path_buf();
@@ -175,6 +203,26 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    // Reload the GRPC2 endpoint configuration and TLS certificate on SIGHUP as
+    // well.
+    #[cfg(unix)]
+    if let Some(ref rpc2) = rpc2 {
+        let reloadable = rpc2.reloadable_config();
+        let mut reload_receiver = reload_sender.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match reload_receiver.recv().await {
+                    Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        if let Err(e) = reloadable.reload() {
+                            error!("Could not reload the GRPC V2 configuration: {:#}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
     maybe_do_out_of_band_catchup(
         &consensus,
         regenesis_arc,
@@ -301,6 +349,49 @@ fn setup_shutdown_signal_handling() -> (broadcast::Sender<()>, broadcast::Receiv
     (sender2, receiver)
 }
 
+/// Reload the network limits from the configuration file whenever the node
+/// receives SIGHUP. Limits that are not in the file keep their current values.
+/// Every SIGHUP is also announced on the returned channel, so that other parts
+/// of the node can reload their configuration.
+#[cfg(unix)]
+fn setup_reload_signal_handling(
+    node: &Arc<P2PNode>,
+    config_file: Option<PathBuf>,
+    max_allowed_nodes_percentage: Option<u16>,
+) -> anyhow::Result<broadcast::Sender<()>> {
+    let mut hangup_stream = unix_signal::signal(unix_signal::SignalKind::hangup())?;
+    let (reload_sender, _) = broadcast::channel(4);
+    let node = Arc::clone(node);
+    let sender = reload_sender.clone();
+    tokio::spawn(async move {
+        while hangup_stream.recv().await.is_some() {
+            // There are no receivers until the components that reload are started.
+            let _ = sender.send(());
+            let path = if let Some(path) = &config_file {
+                path
+            } else {
+                warn!(
+                    "Received SIGHUP, but there is no configuration file to reload the network \
+                     limits from."
+                );
+                continue;
+            };
+            match node
+                .config
+                .limits()
+                .with_config_file(path, max_allowed_nodes_percentage)
+                .and_then(|limits| node.config.set_limits(limits, "SIGHUP"))
+            {
+                Ok(()) => info!("Reloaded the network limits from {}.", path.display()),
+                Err(e) => {
+                    error!("Could not reload the network limits from {}: {:#}", path.display(), e)
+                }
+            }
+        }
+    });
+    Ok(reload_sender)
+}
+
 /// Construct a future for shutdown signals (for unix: SIGINT and SIGTERM) (for
 /// windows: ctrl c and ctrl break). The signal handler is set when the future
 /// is polled and until then the default signal handler.
@@ -324,6 +415,44 @@ async fn get_shutdown_signal() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Check the configuration and the files it refers to, without starting the
+/// node. Every problem found is logged, and an error is returned if there are
+/// any.
+fn check_config(conf: &config::Config, app_prefs: &config::AppPreferences) -> anyhow::Result<()> {
+    let mut problems = config::validate_config(conf);
+    if let Err(e) = PeerAllowlist::parse(&conf.connection.allowed_peers) {
+        problems.push(format!("{:#}", e));
+    }
+    if let Err(e) = read_genesis_data(app_prefs, &conf.cli.baker) {
+        problems.push(format!("{:#}", e));
+    }
+    match read_baker_credentials(&conf.cli.baker) {
+        Ok(Some(credentials)) => {
+            if let Err(e) = check_baker_credentials(&credentials) {
+                problems.push(format!("Invalid baker credentials: {:#}", e));
+            }
+        }
+        Ok(None) => {}
+        Err(e) => problems.push(format!("{:#}", e)),
+    }
+    problems.extend(
+        concordium_node::grpc2::server::check_files(&conf.cli.grpc2)
+            .into_iter()
+            .map(|e| format!("{:#}", e)),
+    );
+
+    for problem in &problems {
+        error!("{}", problem);
+    }
+    anyhow::ensure!(
+        problems.is_empty(),
+        "Found {} problem(s) in the configuration.",
+        problems.len()
+    );
+    info!("The configuration is valid.");
+    Ok(())
+}
+
 fn instantiate_node(
     conf: &config::Config,
     app_prefs: &mut config::AppPreferences,
@@ -331,29 +460,26 @@ fn instantiate_node(
     regenesis_arc: Arc<Regenesis>,
 ) -> anyhow::Result<(Arc<P2PNode>, TcpListener, Poll)> {
     // If the node id is supplied on the command line (in the conf argument) use it.
-    // Otherwise try to look it up from the persistent config.
-    let node_id = match conf.common.id {
-        None => {
-            let maybe_id =
-                app_prefs.get_config(config::APP_PREFERENCES_PERSISTED_NODE_ID).context(
-                    "Could not read ID from persistent config.\nFix or delete the \
-                     `main.config.json` file in the configuration directory.",
-                )?;
-            // we generate a fresh id here if it is not already present so that it can be
-            // stored in the persistent config, even though this is duplicating
-            // the logic from the node.
-            maybe_id.unwrap_or_else(|| rand::thread_rng().gen())
+    // Otherwise the node uses the id derived from its node key.
+    let (node, server, poll) =
+        P2PNode::new(conf.common.id, conf, PeerType::Node, stats_export_service, regenesis_arc)?;
+
+    // Nodes used to persist a random id. It is replaced by the one in use now.
+    if let Ok(Some(persisted_id)) =
+        app_prefs.get_config::<P2PNodeId>(config::APP_PREFERENCES_PERSISTED_NODE_ID)
+    {
+        if persisted_id != node.id() {
+            info!("Replacing the persisted node id {} with {}.", persisted_id, node.id());
         }
-        Some(id) => id,
-    };
+    }
 
     // Failing to persist the node id does not stop the node starting.
     // This failure is unlikely.
-    if !app_prefs.set_config(config::APP_PREFERENCES_PERSISTED_NODE_ID, Some(node_id)) {
+    if !app_prefs.set_config(config::APP_PREFERENCES_PERSISTED_NODE_ID, Some(node.id())) {
         error!("Failed to persist own node id.");
     };
 
-    P2PNode::new(Some(node_id), conf, PeerType::Node, stats_export_service, regenesis_arc)
+    Ok((node, server, poll))
 }
 
 fn establish_connections(conf: &config::Config, node: &Arc<P2PNode>) -> anyhow::Result<()> {
@@ -393,6 +519,8 @@ fn start_consensus_message_threads(
             CALLBACK_QUEUE.inbound.receiver_high_priority.lock().unwrap();
         let consensus_receiver_low_priority =
             CALLBACK_QUEUE.inbound.receiver_low_priority.lock().unwrap();
+        let consensus_receiver_baker_priority =
+            CALLBACK_QUEUE.inbound.receiver_baker_priority.lock().unwrap();
         let mut exhausted: bool;
 
         'outer_loop: loop {
@@ -402,8 +530,20 @@ fn start_consensus_message_threads(
                 consensus_receiver_low_priority.len() as i64,
             );
             node_ref.stats.set_inbound_high_priority_consensus_size(
-                consensus_receiver_high_priority.len() as i64,
+                (consensus_receiver_high_priority.len() + consensus_receiver_baker_priority.len())
+                    as i64,
             );
+            // messages from verified bakers are handled first
+            for message in
+                consensus_receiver_baker_priority.try_iter().take(CONSENSUS_QUEUE_DEPTH_IN_BAKER)
+            {
+                let stop_loop = !handle_queue_stop(message, "inbound", |msg| {
+                    handle_consensus_inbound_msg(&node_ref, &consensus, msg)
+                });
+                if stop_loop {
+                    break 'outer_loop;
+                }
+            }
             // instead of using `try_iter()` we specifically only loop over the max numbers
             // possible to ever be in the queue
             for _ in 0..CONSENSUS_QUEUE_DEPTH_IN_HI {
@@ -430,8 +570,9 @@ fn start_consensus_message_threads(
                 }
             }
             if exhausted {
-                // Both queues were emptied, so wait for a message in either of them.
+                // All queues were emptied, so wait for a message in any of them.
                 let msg = crossbeam_channel::select! {
+                    recv(consensus_receiver_baker_priority) -> msg => msg,
                     recv(consensus_receiver_high_priority) -> msg => msg,
                     recv(consensus_receiver_low_priority) -> msg => msg,
                 };
@@ -0,0 +1,544 @@
+//! The network capture format used by the `network_dump` feature.
+//!
+//! A capture is a sequence of append-only files, each of which starts with a
+//! header identifying the capturing node and is followed by length-prefixed
+//! records containing the timestamp, the direction, the remote address, the
+//! remote peer id (if known) and the raw bytes of a single network message.
+//! Once a file reaches the configured size, the writer rotates to a new file
+//! with the next sequence number.
+//!
+//! Every file is accompanied by a sparse index that maps timestamps to record
+//! offsets, so that a reader can start at a given point in time without
+//! scanning the whole file. The index is only an optimization; a file whose
+//! index is missing or incomplete can still be read sequentially.
+
+use crate::common::P2PNodeId;
+use anyhow::{bail, ensure, Context};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::prelude::{DateTime, TimeZone, Utc};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+};
+
+/// The first bytes of a capture file.
+const CAPTURE_MAGIC: [u8; 8] = *b"CCDCAPT\0";
+/// The first bytes of a capture index file.
+const INDEX_MAGIC: [u8; 8] = *b"CCDCIDX\0";
+/// The version of the capture format.
+const CAPTURE_VERSION: u16 = 1;
+/// The extension of capture files.
+const CAPTURE_EXTENSION: &str = "capture";
+/// The extension of capture index files.
+const INDEX_EXTENSION: &str = "capture.idx";
+/// An index entry is written for every `INDEX_INTERVAL`-th record of a file.
+const INDEX_INTERVAL: u64 = 256;
+/// The size of a single index entry: a timestamp and an offset.
+const INDEX_ENTRY_SIZE: usize = 16;
+/// The upper bound of the size of a single record, which is a sanity check
+/// protecting the reader from allocating arbitrary amounts of memory.
+const MAX_RECORD_SIZE: u32 = 512 * 1024 * 1024;
+
+const FLAG_INBOUND: u8 = 1;
+const FLAG_PEER_ID: u8 = 1 << 1;
+
+/// A single captured network message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub timestamp:   DateTime<Utc>,
+    /// Whether the message was received (as opposed to sent) by the node.
+    pub inbound:     bool,
+    pub remote_addr: IpAddr,
+    /// The id of the remote peer; it is unknown before the handshake.
+    pub peer_id:     Option<P2PNodeId>,
+    /// The message as it was read from or written to the socket.
+    pub message:     Vec<u8>,
+}
+
+/// The header of a capture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureHeader {
+    /// The id of the capturing node.
+    pub node_id:    P2PNodeId,
+    /// The address of the capturing node.
+    pub node_addr:  IpAddr,
+    /// The time the file was created.
+    pub created_at: DateTime<Utc>,
+}
+
+fn timestamp_to_nanos(timestamp: &DateTime<Utc>) -> i64 { timestamp.timestamp_nanos() }
+
+fn timestamp_from_nanos(nanos: i64) -> DateTime<Utc> { Utc.timestamp_nanos(nanos) }
+
+fn write_addr<W: Write>(target: &mut W, addr: &IpAddr) -> io::Result<()> {
+    match addr {
+        IpAddr::V4(addr) => {
+            target.write_u8(4)?;
+            target.write_all(&addr.octets())
+        }
+        IpAddr::V6(addr) => {
+            target.write_u8(6)?;
+            target.write_all(&addr.octets())
+        }
+    }
+}
+
+fn read_addr<R: Read>(source: &mut R) -> anyhow::Result<IpAddr> {
+    match source.read_u8()? {
+        4 => {
+            let mut octets = [0u8; 4];
+            source.read_exact(&mut octets)?;
+            Ok(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        6 => {
+            let mut octets = [0u8; 16];
+            source.read_exact(&mut octets)?;
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        family => bail!("Unknown address family in a capture: {}", family),
+    }
+}
+
+impl CaptureHeader {
+    fn write<W: Write>(&self, target: &mut W) -> io::Result<()> {
+        target.write_all(&CAPTURE_MAGIC)?;
+        target.write_u16::<BigEndian>(CAPTURE_VERSION)?;
+        target.write_u64::<BigEndian>(self.node_id.as_raw())?;
+        write_addr(target, &self.node_addr)?;
+        target.write_i64::<BigEndian>(timestamp_to_nanos(&self.created_at))
+    }
+
+    fn read<R: Read>(source: &mut R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 8];
+        source.read_exact(&mut magic).context("The capture file is too short.")?;
+        ensure!(magic == CAPTURE_MAGIC, "Not a capture file.");
+        let version = source.read_u16::<BigEndian>()?;
+        ensure!(version == CAPTURE_VERSION, "Unsupported capture format version: {}", version);
+        Ok(CaptureHeader {
+            node_id:    P2PNodeId(source.read_u64::<BigEndian>()?),
+            node_addr:  read_addr(source)?,
+            created_at: timestamp_from_nanos(source.read_i64::<BigEndian>()?),
+        })
+    }
+}
+
+impl CaptureRecord {
+    /// Serialize the record, prefixed with its length.
+    fn write<W: Write>(&self, target: &mut W) -> anyhow::Result<usize> {
+        let mut body = Vec::with_capacity(32 + self.message.len());
+        body.write_i64::<BigEndian>(timestamp_to_nanos(&self.timestamp))?;
+        let mut flags = 0;
+        if self.inbound {
+            flags |= FLAG_INBOUND;
+        }
+        if self.peer_id.is_some() {
+            flags |= FLAG_PEER_ID;
+        }
+        body.write_u8(flags)?;
+        write_addr(&mut body, &self.remote_addr)?;
+        if let Some(peer_id) = self.peer_id {
+            body.write_u64::<BigEndian>(peer_id.as_raw())?;
+        }
+        body.write_all(&self.message)?;
+        ensure!(body.len() <= MAX_RECORD_SIZE as usize, "The captured message is too large.");
+        target.write_u32::<BigEndian>(body.len() as u32)?;
+        target.write_all(&body)?;
+        Ok(4 + body.len())
+    }
+
+    /// Deserialize a record. Returns `None` at the end of the input; a record
+    /// that is cut short, e.g., because the node was stopped while writing
+    /// it, is an error.
+    fn read<R: Read>(source: &mut R) -> anyhow::Result<Option<Self>> {
+        let mut len_bytes = [0u8; 4];
+        let mut filled = 0;
+        while filled < len_bytes.len() {
+            match source.read(&mut len_bytes[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => bail!("A capture record is truncated."),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let len = u32::from_be_bytes(len_bytes);
+        ensure!(len <= MAX_RECORD_SIZE, "A capture record is too large ({}B).", len);
+        let mut body = vec![0u8; len as usize];
+        source.read_exact(&mut body).context("A capture record is truncated.")?;
+
+        let mut cursor = io::Cursor::new(&body[..]);
+        let timestamp = timestamp_from_nanos(cursor.read_i64::<BigEndian>()?);
+        let flags = cursor.read_u8()?;
+        let remote_addr = read_addr(&mut cursor)?;
+        let peer_id = if flags & FLAG_PEER_ID != 0 {
+            Some(P2PNodeId(cursor.read_u64::<BigEndian>()?))
+        } else {
+            None
+        };
+        let message = body[cursor.position() as usize..].to_vec();
+        Ok(Some(CaptureRecord {
+            timestamp,
+            inbound: flags & FLAG_INBOUND != 0,
+            remote_addr,
+            peer_id,
+            message,
+        }))
+    }
+}
+
+/// The path of the capture file with the given prefix and sequence number.
+fn capture_file_path(dir: &Path, prefix: &str, sequence: u32) -> PathBuf {
+    dir.join(format!("{}-{:06}.{}", prefix, sequence, CAPTURE_EXTENSION))
+}
+
+/// The path of the index accompanying a capture file.
+pub fn index_file_path(capture_file: &Path) -> PathBuf {
+    capture_file.with_extension(INDEX_EXTENSION)
+}
+
+/// List the capture files with the given prefix in a directory, in the order
+/// in which they were written.
+pub fn capture_files(dir: &Path, prefix: &str) -> anyhow::Result<Vec<PathBuf>> {
+    Ok(numbered_capture_files(dir, prefix)?.into_iter().map(|(_, path)| path).collect())
+}
+
+/// List the capture files with the given prefix in a directory along with
+/// their sequence numbers.
+fn numbered_capture_files(dir: &Path, prefix: &str) -> anyhow::Result<Vec<(u32, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let sequence = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(CAPTURE_EXTENSION))
+            .and_then(|rest| rest.strip_suffix('.'))
+            .and_then(|sequence| sequence.parse::<u32>().ok());
+        if let Some(sequence) = sequence {
+            files.push((sequence, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Writes network messages to a sequence of capture files, rotating to a new
+/// file once the current one exceeds the maximum size.
+pub struct CaptureWriter {
+    dir:             PathBuf,
+    prefix:          String,
+    node_id:         P2PNodeId,
+    node_addr:       IpAddr,
+    /// The size at which the current file is rotated.
+    max_file_size:   u64,
+    /// The sequence number of the current file.
+    sequence:        u32,
+    file:            BufWriter<File>,
+    index:           BufWriter<File>,
+    /// The number of bytes written to the current file.
+    file_size:       u64,
+    /// The number of records written to the current file.
+    records_in_file: u64,
+}
+
+impl CaptureWriter {
+    /// Start a capture in the given directory. Files are named after the
+    /// prefix and a sequence number, which continues after the existing files
+    /// with the same prefix so that a restarted capture doesn't overwrite
+    /// them.
+    pub fn new(
+        dir: &Path,
+        prefix: &str,
+        node_id: P2PNodeId,
+        node_addr: IpAddr,
+        max_file_size: u64,
+    ) -> anyhow::Result<Self> {
+        ensure!(max_file_size > 0, "The maximum capture file size must be positive.");
+        std::fs::create_dir_all(dir)?;
+        let sequence = numbered_capture_files(dir, prefix)?.last().map_or(0, |(last, _)| last + 1);
+        let (file, index, file_size) =
+            Self::create_files(dir, prefix, sequence, node_id, node_addr)?;
+        Ok(CaptureWriter {
+            dir: dir.to_owned(),
+            prefix: prefix.to_owned(),
+            node_id,
+            node_addr,
+            max_file_size,
+            sequence,
+            file,
+            index,
+            file_size,
+            records_in_file: 0,
+        })
+    }
+
+    fn create_files(
+        dir: &Path,
+        prefix: &str,
+        sequence: u32,
+        node_id: P2PNodeId,
+        node_addr: IpAddr,
+    ) -> anyhow::Result<(BufWriter<File>, BufWriter<File>, u64)> {
+        let path = capture_file_path(dir, prefix, sequence);
+        let mut file = BufWriter::new(
+            OpenOptions::new().write(true).create_new(true).open(&path).with_context(|| {
+                format!("Could not create the capture file {}.", path.display())
+            })?,
+        );
+        let header = CaptureHeader {
+            node_id,
+            node_addr,
+            created_at: Utc::now(),
+        };
+        let mut header_bytes = Vec::new();
+        header.write(&mut header_bytes)?;
+        file.write_all(&header_bytes)?;
+        let mut index = BufWriter::new(File::create(index_file_path(&path))?);
+        index.write_all(&INDEX_MAGIC)?;
+        Ok((file, index, header_bytes.len() as u64))
+    }
+
+    /// The path of the file currently being written.
+    pub fn current_file(&self) -> PathBuf {
+        capture_file_path(&self.dir, &self.prefix, self.sequence)
+    }
+
+    /// Append a record to the capture, rotating the file beforehand if it is
+    /// already at the maximum size.
+    pub fn write(&mut self, record: &CaptureRecord) -> anyhow::Result<()> {
+        if self.file_size >= self.max_file_size && self.records_in_file > 0 {
+            self.rotate()?;
+        }
+        if self.records_in_file % INDEX_INTERVAL == 0 {
+            self.index.write_i64::<BigEndian>(timestamp_to_nanos(&record.timestamp))?;
+            self.index.write_u64::<BigEndian>(self.file_size)?;
+        }
+        self.file_size += record.write(&mut self.file)? as u64;
+        self.records_in_file += 1;
+        Ok(())
+    }
+
+    /// Flush the buffered records to the disk.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.file.flush()?;
+        self.index.flush()?;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        let (file, index, file_size) = Self::create_files(
+            &self.dir,
+            &self.prefix,
+            self.sequence + 1,
+            self.node_id,
+            self.node_addr,
+        )?;
+        self.sequence += 1;
+        self.file = file;
+        self.index = index;
+        self.file_size = file_size;
+        self.records_in_file = 0;
+        Ok(())
+    }
+}
+
+impl Drop for CaptureWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Could not flush the network capture: {}", e);
+        }
+    }
+}
+
+/// Reads the records of a single capture file.
+pub struct CaptureReader {
+    path:   PathBuf,
+    header: CaptureHeader,
+    /// The offset of the first record.
+    start:  u64,
+    file:   BufReader<File>,
+}
+
+impl CaptureReader {
+    /// Open a capture file and read its header.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut file = BufReader::new(
+            File::open(path)
+                .with_context(|| format!("Could not open the capture file {}.", path.display()))?,
+        );
+        let header = CaptureHeader::read(&mut file)?;
+        let start = file.stream_position()?;
+        Ok(CaptureReader {
+            path: path.to_owned(),
+            header,
+            start,
+            file,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader { &self.header }
+
+    /// Read the next record, or `None` at the end of the file.
+    pub fn next_record(&mut self) -> anyhow::Result<Option<CaptureRecord>> {
+        CaptureRecord::read(&mut self.file)
+    }
+
+    /// Position the reader at the first record with a timestamp not earlier
+    /// than the given one, using the index if it is available.
+    pub fn seek_to(&mut self, timestamp: DateTime<Utc>) -> anyhow::Result<()> {
+        let target = timestamp_to_nanos(&timestamp);
+        let index = self.read_index().unwrap_or_else(|e| {
+            warn!("Ignoring the index of {}: {}", self.path.display(), e);
+            Vec::new()
+        });
+        // the last indexed record that is earlier than the target
+        let offset = match index.partition_point(|(ts, _)| *ts < target) {
+            0 => self.start,
+            n => index[n - 1].1,
+        };
+        self.file.seek(SeekFrom::Start(offset))?;
+        loop {
+            let position = self.file.stream_position()?;
+            match self.next_record()? {
+                Some(record) if timestamp_to_nanos(&record.timestamp) < target => {}
+                _ => {
+                    self.file.seek(SeekFrom::Start(position))?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn read_index(&self) -> anyhow::Result<Vec<(i64, u64)>> {
+        let bytes = std::fs::read(index_file_path(&self.path))?;
+        ensure!(bytes.starts_with(&INDEX_MAGIC), "Not a capture index file.");
+        // an entry that was cut short is ignored
+        Ok(bytes[INDEX_MAGIC.len()..]
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|mut entry| {
+                let timestamp = entry.read_i64::<BigEndian>().unwrap_or_default();
+                let offset = entry.read_u64::<BigEndian>().unwrap_or_default();
+                (timestamp, offset)
+            })
+            .collect())
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = anyhow::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> { self.next_record().transpose() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(i: u32) -> CaptureRecord {
+        CaptureRecord {
+            timestamp:   Utc.timestamp(1_600_000_000 + i as i64, i),
+            inbound:     i % 2 == 0,
+            remote_addr: if i % 3 == 0 {
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            } else {
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, i as u8))
+            },
+            peer_id:     if i % 5 == 0 {
+                None
+            } else {
+                Some(P2PNodeId(i as u64))
+            },
+            message:     i.to_be_bytes().repeat(i as usize % 16),
+        }
+    }
+
+    fn write_capture(dir: &Path, count: u32, max_file_size: u64) -> anyhow::Result<()> {
+        let mut writer = CaptureWriter::new(
+            dir,
+            "node",
+            P2PNodeId(42),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            max_file_size,
+        )?;
+        for i in 0..count {
+            writer.write(&record(i))?;
+        }
+        Ok(())
+    }
+
+    fn read_capture(dir: &Path) -> anyhow::Result<Vec<CaptureRecord>> {
+        let mut records = Vec::new();
+        for path in capture_files(dir, "node")? {
+            let reader = CaptureReader::open(&path)?;
+            assert_eq!(reader.header().node_id, P2PNodeId(42));
+            for record in reader {
+                records.push(record?);
+            }
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn capture_roundtrip() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_capture(dir.path(), 1000, u64::MAX)?;
+        assert_eq!(capture_files(dir.path(), "node")?.len(), 1);
+        assert_eq!(read_capture(dir.path())?, (0..1000).map(record).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn capture_rotation() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_capture(dir.path(), 1000, 4096)?;
+        let files = capture_files(dir.path(), "node")?;
+        assert!(files.len() > 1);
+        for file in &files {
+            // a file only exceeds the maximum size by its last record
+            assert!(std::fs::metadata(file)?.len() < 4096 + 128);
+        }
+        // a restarted capture continues after the existing files
+        write_capture(dir.path(), 10, u64::MAX)?;
+        assert_eq!(capture_files(dir.path(), "node")?.len(), files.len() + 1);
+        let expected = (0..1000).chain(0..10).map(record).collect::<Vec<_>>();
+        assert_eq!(read_capture(dir.path())?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn capture_seek() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_capture(dir.path(), 1000, u64::MAX)?;
+        let path = &capture_files(dir.path(), "node")?[0];
+        for &i in &[0, 1, 255, 256, 257, 700, 999] {
+            let mut reader = CaptureReader::open(path)?;
+            reader.seek_to(record(i).timestamp)?;
+            assert_eq!(reader.next_record()?, Some(record(i)));
+        }
+        // seeking works without the index as well
+        std::fs::remove_file(index_file_path(path))?;
+        let mut reader = CaptureReader::open(path)?;
+        reader.seek_to(record(500).timestamp)?;
+        assert_eq!(reader.next_record()?, Some(record(500)));
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_capture() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_capture(dir.path(), 10, u64::MAX)?;
+        let path = &capture_files(dir.path(), "node")?[0];
+        let len = std::fs::metadata(path)?.len();
+        OpenOptions::new().write(true).open(path)?.set_len(len - 1)?;
+        let records = CaptureReader::open(path)?.collect::<Vec<_>>();
+        assert_eq!(records.len(), 10);
+        assert!(records[..9].iter().all(|record| record.is_ok()));
+        assert!(records[9].is_err());
+        Ok(())
+    }
+}
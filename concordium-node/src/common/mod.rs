@@ -1,6 +1,7 @@
 //! Common objects used by the client.
 
 pub mod grpc_api;
+pub mod node_key;
 pub mod p2p_node_id;
 pub mod p2p_peer;
 #[macro_use]
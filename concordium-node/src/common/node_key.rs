@@ -0,0 +1,98 @@
+//! The long-term static key of the node.
+//!
+//! The key is used in the noise handshake of every connection, so a peer
+//! learns it in an authenticated way. The node id is derived from it, which
+//! lets peers check that a node is not claiming somebody else's id.
+
+use super::P2PNodeId;
+use anyhow::{ensure, Context};
+use noiseexplorer_xx::{
+    consts::DHLEN,
+    types::{Keypair, PrivateKey},
+};
+use rand::Rng;
+use std::{convert::TryInto, path::Path};
+
+/// The name of the file in the data directory that holds the private key, hex
+/// encoded.
+pub const NODE_KEY_FILE: &str = "node.key";
+
+/// The static noise keypair of the node.
+pub struct NodeKey {
+    keypair: Keypair,
+}
+
+impl NodeKey {
+    /// Load the key from the data directory, or generate and store a fresh one
+    /// if there is none.
+    pub fn load_or_generate(data_dir: &Path) -> anyhow::Result<Self> {
+        let path = data_dir.join(NODE_KEY_FILE);
+        if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read the node key from {}.", path.display()))?;
+            let bytes = hex::decode(contents.trim())
+                .with_context(|| format!("The node key in {} is not hex.", path.display()))?;
+            ensure!(
+                bytes.len() == DHLEN,
+                "The node key in {} should be {} bytes long.",
+                path.display(),
+                DHLEN
+            );
+            Self::from_private_key(bytes[..].try_into()?)
+        } else {
+            let private_key = rand::thread_rng().gen::<[u8; DHLEN]>();
+            write_private(&path, hex::encode(private_key).as_bytes())
+                .with_context(|| format!("Could not store the node key in {}.", path.display()))?;
+            info!("Generated a new node key in {}", path.display());
+            Self::from_private_key(private_key)
+        }
+    }
+
+    fn from_private_key(private_key: [u8; DHLEN]) -> anyhow::Result<Self> {
+        let keypair = PrivateKey::from_bytes(private_key)
+            .and_then(Keypair::from_private_key)
+            .map_err(|e| anyhow::anyhow!("Invalid node key: {:?}", e))?;
+        Ok(NodeKey {
+            keypair,
+        })
+    }
+
+    /// The keypair to use in a noise session.
+    pub fn keypair(&self) -> Keypair { self.keypair.clone() }
+
+    /// The public key, as seen by the peers.
+    pub fn public_key(&self) -> [u8; DHLEN] { self.keypair.get_public_key().as_bytes() }
+
+    /// The node id derived from the public key.
+    pub fn node_id(&self) -> P2PNodeId { P2PNodeId::from_public_key(&self.public_key()) }
+}
+
+/// Write a file that only the owner can read.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_key_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = NodeKey::load_or_generate(dir.path()).unwrap();
+        let reloaded = NodeKey::load_or_generate(dir.path()).unwrap();
+        assert_eq!(key.public_key(), reloaded.public_key());
+        assert_eq!(key.node_id(), reloaded.node_id());
+
+        let other = NodeKey::load_or_generate(tempfile::tempdir().unwrap().path()).unwrap();
+        assert_ne!(key.node_id(), other.node_id());
+    }
+}
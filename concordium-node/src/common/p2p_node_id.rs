@@ -4,6 +4,7 @@ use anyhow::bail;
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use concordium_base::common::{Buffer, Deserial, Serial};
 use rand::distributions::{Distribution, Standard, Uniform};
+use sha2::{Digest, Sha256};
 use std::fmt;
 
 pub type PeerId = u64;
@@ -59,4 +60,13 @@ impl Deserial for P2PNodeId {
 impl P2PNodeId {
     /// Obtain the integer behind the node id.
     pub fn as_raw(self) -> PeerId { self.0 }
+
+    /// The id of the node with the given static public key, which consists of
+    /// the first 8 bytes of the SHA-256 hash of the key.
+    pub fn from_public_key(public_key: &[u8]) -> Self {
+        let hash = Sha256::digest(public_key);
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&hash[..8]);
+        P2PNodeId(PeerId::from_be_bytes(id))
+    }
 }
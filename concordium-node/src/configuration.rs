@@ -1,9 +1,11 @@
 //! The client's parameters and constants used by other modules.
 
+mod file;
+
 use crate::{
     common::P2PNodeId,
     connection::DeduplicationHashAlgorithm,
-    network::{WireProtocolVersion, WIRE_PROTOCOL_VERSIONS},
+    network::{Capabilities, WireProtocolVersion, WIRE_PROTOCOL_VERSIONS},
 };
 use anyhow::{ensure, Context};
 use app_dirs2::*;
@@ -53,14 +55,30 @@ const APP_PREFERENCES_KEY_VERSION: &str = "VERSION";
 /// Used for a persistent node id setup.
 pub const APP_PREFERENCES_PERSISTED_NODE_ID: &str = "PERSISTED_NODE_ID";
 
+/// Maximum number of recently announced transactions kept for peers to
+/// request.
+pub const MAX_RELAYED_TRANSACTIONS: usize = 16_384;
+
+/// Maximum total size of the recently announced transactions kept for peers
+/// to request, in bytes.
+pub const MAX_RELAYED_TRANSACTIONS_SIZE: usize = 64 * 1024 * 1024; // 64 MiB
+
 /// Maximum time allowed for a peer to catch up with, in milliseconds.
 pub const MAX_CATCH_UP_TIME: u64 = 300_000;
 
+/// How long a batch submission of block items waits before checking again
+/// whether there is room in the outbound queue.
+pub const SEND_BLOCK_ITEMS_BACKOFF: std::time::Duration = std::time::Duration::from_millis(50);
+
 // dump queue depths
 #[cfg(feature = "network_dump")]
 pub const DUMP_QUEUE_DEPTH: usize = 100;
 #[cfg(feature = "network_dump")]
 pub const DUMP_SWITCH_QUEUE_DEPTH: usize = 0;
+/// How often a running network dump checks for commands and limits when there
+/// are no messages to dump.
+#[cfg(feature = "network_dump")]
+pub const DUMP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 
 // connection-related consts
 /// Maximum time (in s) a node's connection can remain unreachable.
@@ -289,6 +307,21 @@ pub struct BakerConfig {
         env = "CONCORDIUM_NODE_BAKER_DECRYPT_CREDENTIALS"
     )]
     pub decrypt_baker_credentials: bool,
+    #[structopt(
+        long = "baker-credentials-password-file",
+        help = "File containing the password to decrypt the baker credentials. The file must only \
+                be accessible by its owner. A trailing newline is not part of the password.",
+        env = "CONCORDIUM_NODE_BAKER_CREDENTIALS_PASSWORD_FILE"
+    )]
+    pub baker_credentials_password_file: Option<PathBuf>,
+    #[structopt(
+        long = "baker-credentials-password-fd",
+        help = "File descriptor to read the password to decrypt the baker credentials from (Unix \
+                only). Use 0 for standard input, which is left open; any other descriptor is \
+                closed once the password is read. A trailing newline is not part of the password.",
+        env = "CONCORDIUM_NODE_BAKER_CREDENTIALS_PASSWORD_FD"
+    )]
+    pub baker_credentials_password_fd: Option<i32>,
     #[structopt(
         long = "modules-cache-size",
         help = "The maximum number of smart contract modules that can be stored in the module \
@@ -381,6 +414,15 @@ pub struct GRPC2Config {
         requires = "grpc2-listen-addr"
     )]
     pub enable_grpc_web:            bool,
+    #[structopt(
+        long = "grpc2-rest-port",
+        help = "Port on which to serve the GRPC V2 queries as HTTP routes with JSON requests and \
+                responses. The gateway listens on the address of the GRPC server and uses the \
+                same TLS settings.",
+        env = "CONCORDIUM_NODE_GRPC2_REST_PORT",
+        requires = "grpc2-listen-addr"
+    )]
+    pub rest_port:                  Option<u16>,
     #[structopt(
         long = "grpc2-endpoint-config",
         help = "Configuration file for endpoints, listing which endpoints should be enabled or \
@@ -398,6 +440,23 @@ pub struct GRPC2Config {
         default_value = "300"
     )]
     pub health_max_finalized_delay: concordium_base::base::DurationSeconds,
+    #[structopt(
+        long = "grpc2-stream-capacity",
+        help = "Number of blocks that are buffered for each client of the block streams. A client \
+                that falls further behind is sent an error stating how many blocks it missed, \
+                which ends its stream.",
+        env = "CONCORDIUM_NODE_GRPC2_STREAM_CAPACITY",
+        default_value = "100"
+    )]
+    pub stream_capacity:            usize,
+    #[structopt(
+        long = "grpc2-query-cache-size",
+        help = "Maximum total size, in bytes, of the responses to queries about finalized blocks \
+                that are kept in memory to answer repeated queries. 0 disables the cache.",
+        env = "CONCORDIUM_NODE_GRPC2_QUERY_CACHE_SIZE",
+        default_value = "0"
+    )]
+    pub query_cache_size:           usize,
 }
 
 impl GRPC2Config {
@@ -582,6 +641,63 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_MAX_NORMAL_KEEP_ALIVE"
     )]
     pub max_normal_keep_alive: u64,
+    #[structopt(
+        long = "disable-capabilities",
+        help = "Optional protocol features not to advertise to peers \
+                [compression-zstd|compression-lz4|transaction-pull-relay]",
+        use_delimiter = true, // allow a single argument with a comma separated list of values.
+        env = "CONCORDIUM_NODE_CONNECTION_DISABLE_CAPABILITIES"
+    )]
+    pub disable_capabilities: Vec<Capabilities>,
+    #[structopt(
+        long = "compression-threshold",
+        help = "Minimum size (in bytes) of network messages to be compressed for peers that \
+                support compression",
+        default_value = "4096",
+        env = "CONCORDIUM_NODE_CONNECTION_COMPRESSION_THRESHOLD"
+    )]
+    pub compression_threshold: usize,
+    #[structopt(
+        long = "require-authenticated-node-ids",
+        help = "Reject peers whose node id is not derived from the static key they authenticate \
+                with in the handshake.",
+        env = "CONCORDIUM_NODE_CONNECTION_REQUIRE_AUTHENTICATED_NODE_IDS"
+    )]
+    pub require_authenticated_node_ids: bool,
+    #[structopt(
+        long = "baker-peer-slots",
+        help = "Number of connections to peers that proved to be current bakers that are allowed \
+                on top of the maximum number of nodes. Such peers are also never dropped to bring \
+                the number of peers down.",
+        default_value = "10",
+        env = "CONCORDIUM_NODE_CONNECTION_BAKER_PEER_SLOTS"
+    )]
+    pub baker_peer_slots: u16,
+    #[structopt(
+        long = "network-psk",
+        help = "Pre-shared key that peers must present in the first handshake message. Only nodes \
+                configured with the same key can connect to each other. Note that the key is not \
+                encrypted on the wire. If not given, the key of the public network is used.",
+        env = "CONCORDIUM_NODE_CONNECTION_NETWORK_PSK"
+    )]
+    pub network_psk: Option<String>,
+    #[structopt(
+        long = "network-prologue",
+        help = "Prologue of the noise handshake. The handshake fails between nodes with different \
+                prologues. If not given, the prologue of the public network is used.",
+        env = "CONCORDIUM_NODE_CONNECTION_NETWORK_PROLOGUE"
+    )]
+    pub network_prologue: Option<String>,
+    #[structopt(
+        long = "allowed-peer",
+        help = "Run in private network mode, only connecting to and accepting the given peers, \
+                each an IP address or a node id. This also applies to bootstrappers and to peers \
+                learned from other peers. A node id only matches if the peer authenticates it \
+                with its node key.",
+        use_delimiter = true,
+        env = "CONCORDIUM_NODE_CONNECTION_ALLOWED_PEERS"
+    )]
+    pub allowed_peers: Vec<String>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -597,7 +713,9 @@ pub struct CommonConfig {
         long = "id",
         short = "i",
         help = "Set forced node id (64 bit unsigned integer in zero padded HEX. Must be 16 \
-                characters long)",
+                characters long). By default the id derived from the node key in the data \
+                directory is used. Peers that require authenticated node ids refuse ids that \
+                don't match the key.",
         env = "CONCORDIUM_NODE_ID"
     )]
     pub id: Option<P2PNodeId>,
@@ -687,6 +805,29 @@ pub struct CommonConfig {
         env = "CONCORDIUM_NODE_PRINT_CONFIG"
     )]
     pub print_config: bool,
+    #[structopt(
+        long = "config-file",
+        help = "TOML or YAML file with configuration options, in sections that mirror the \
+                configuration structure, e.g. `desired_nodes` in section `connection`. Options \
+                given on the command line or in environment variables take precedence.",
+        env = "CONCORDIUM_NODE_CONFIG_FILE"
+    )]
+    pub config_file: Option<PathBuf>,
+    #[structopt(
+        long = "dump-effective-config",
+        help = "Print the configuration resulting from the command line, the environment, the \
+                configuration file and the defaults, in the format of the configuration file, and \
+                exit. Keys, passwords and tokens are redacted."
+    )]
+    pub dump_effective_config: bool,
+    #[structopt(
+        long = "check-config",
+        help = "Check the configuration, including the genesis data, the baker credentials and \
+                the GRPC V2 endpoint configuration and TLS certificate, report all problems \
+                found, and exit without starting the node. The mode can also be selected by \
+                giving `check-config` as the first argument."
+    )]
+    pub check_config: bool,
     #[structopt(
         long = "bucket-cleanup-interval",
         help = "Try to timeout entries in the buckets every set interval (in ms)",
@@ -694,6 +835,14 @@ pub struct CommonConfig {
         env = "CONCORDIUM_NODE_BUCKET_CLEANUP_INTERVAL"
     )]
     pub bucket_cleanup_interval: u64,
+    #[cfg(feature = "network_dump")]
+    #[structopt(
+        long = "network-dump-max-file-size",
+        help = "Size (in bytes) at which a network capture file is rotated",
+        default_value = "268435456",
+        env = "CONCORDIUM_NODE_NETWORK_DUMP_MAX_FILE_SIZE"
+    )]
+    pub network_dump_max_file_size: u64,
 }
 
 // Client's parameters.
@@ -822,75 +971,283 @@ impl Config {
 
 /// Verifies the validity of the configuration.
 pub fn parse_config() -> anyhow::Result<Config> {
+    if let Some(path) = file::config_file_path() {
+        file::apply_config_file(&path)?;
+    }
     let conf = {
         let app = Config::clap()
             .setting(AppSettings::ArgRequiredElseHelp)
             .setting(AppSettings::NextLineHelp)
             .global_setting(AppSettings::ColoredHelp);
-        Config::from_clap(&app.get_matches())
+        let matches = app.get_matches_from(command_line_args());
+        if matches.is_present("dump-effective-config") {
+            print!("{}", file::effective_config(&matches)?);
+            std::process::exit(0);
+        }
+        Config::from_clap(&matches)
     };
 
-    ensure!(
-        conf.connection.max_allowed_nodes_percentage >= 100,
-        "Can't provide a lower percentage than 100, as that would limit the maximum amount of \
-         nodes to less than the desired nodes is set to"
-    );
+    // In check mode the problems are reported together with those of the files
+    // the configuration refers to.
+    if !conf.common.check_config {
+        let problems = validate_config(&conf);
+        ensure!(problems.is_empty(), "{}", problems.join("\n"));
+    }
 
-    if let Some(max_allowed_nodes) = conf.connection.max_allowed_nodes {
-        ensure!(
-            max_allowed_nodes >= conf.connection.desired_nodes,
-            "Desired nodes set to {}, but max allowed nodes is set to {}. Max allowed nodes must \
-             be greater or equal to desired amount of nodes",
-            conf.connection.desired_nodes,
-            max_allowed_nodes
-        );
+    Ok(conf)
+}
+
+/// The command line arguments, with the `check-config` mode given as the first
+/// argument translated to the corresponding flag.
+fn command_line_args() -> Vec<std::ffi::OsString> {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    if args.get(1).map_or(false, |arg| arg.as_os_str() == "check-config") {
+        args[1] = "--check-config".into();
     }
+    args
+}
 
-    ensure!(
-        conf.connection.hard_connection_limit >= conf.connection.desired_nodes,
-        "Hard connection limit can't be less than what desired nodes is set to"
-    );
+/// Check the invariants between the configuration options, returning a
+/// description of every one that does not hold.
+pub fn validate_config(conf: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut ensure = |condition: bool, problem: String| {
+        if !condition {
+            problems.push(problem);
+        }
+    };
 
-    ensure!(
-        conf.connection.relay_broadcast_percentage >= 0.0
-            && conf.connection.relay_broadcast_percentage <= 1.0,
-        "Percentage of peers to relay broadcasted packets to, must be between 0.0 and 1.0"
+    ensure(
+        conf.connection.max_allowed_nodes_percentage >= 100,
+        "Can't provide a lower percentage than 100, as that would limit the maximum amount of \
+         nodes to less than the desired nodes is set to"
+            .to_owned(),
     );
 
-    ensure!(
+    ensure(
         conf.cli.baker.maximum_block_size <= 4_000_000_000
             && ((f64::from(conf.cli.baker.maximum_block_size) * 0.9).ceil()) as u32
                 <= PROTOCOL_MAX_MESSAGE_SIZE,
-        "Maximum block size set higher than 90% of network protocol max size ({})",
-        PROTOCOL_MAX_MESSAGE_SIZE
+        format!(
+            "Maximum block size set higher than 90% of network protocol max size ({})",
+            PROTOCOL_MAX_MESSAGE_SIZE
+        ),
     );
 
-    ensure!(
+    ensure(
         conf.connection.socket_read_size >= 65535,
-        "Socket read size must be set to at least 65535"
+        "Socket read size must be set to at least 65535".to_owned(),
     );
 
-    ensure!(
+    ensure(
         conf.connection.socket_read_size >= conf.connection.socket_write_size,
-        "Socket read size must be greater or equal to the write size"
+        "Socket read size must be greater or equal to the write size".to_owned(),
     );
 
-    ensure!(
+    ensure(
         conf.bootstrapper.wait_until_minimum_nodes as usize <= conf.bootstrapper.peer_list_size,
-        "wait-until-minimum-nodes must be lower than or equal to peer-list-size"
+        "wait-until-minimum-nodes must be lower than or equal to peer-list-size".to_owned(),
     );
 
-    ensure!(
-        conf.connection.max_normal_keep_alive
-            >= conf.connection.housekeeping_interval * (KEEP_ALIVE_FACTOR as u64),
-        "max-normal-keep-alive ({}) should be at least {} times greater than the value of \
-         housekeeping-interval ({})",
-        conf.connection.max_normal_keep_alive,
-        KEEP_ALIVE_FACTOR,
-        conf.connection.housekeeping_interval
+    problems.extend(
+        NetworkLimits::from_config(conf)
+            .problems(conf.connection.hard_connection_limit, conf.connection.max_normal_keep_alive),
     );
+    problems
+}
 
-    Ok(conf)
+/// The maximum number of nodes given as a percentage of the desired number of
+/// nodes.
+fn derived_max_allowed_nodes(desired_nodes: u16, percentage: u16) -> u16 {
+    f64::floor(f64::from(desired_nodes) * (f64::from(percentage) / 100f64)) as u16
+}
+
+/// The network limits that can be changed while the node is running, either
+/// through the GRPC V2 interface or by reloading the configuration file on
+/// SIGHUP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkLimits {
+    pub desired_nodes_count:          u16,
+    pub max_allowed_nodes:            u16,
+    pub relay_broadcast_percentage:   f64,
+    /// The maximum allowed connection latency in ms.
+    pub max_latency:                  Option<u64>,
+    /// The connection housekeeping interval in seconds.
+    pub housekeeping_interval:        u64,
+    pub drop_rebroadcast_probability: Option<f64>,
+}
+
+impl NetworkLimits {
+    /// The limits given by the configuration. If the maximum number of nodes
+    /// is not given explicitly it is derived from the desired number of nodes.
+    pub fn from_config(conf: &Config) -> Self {
+        NetworkLimits {
+            desired_nodes_count:          conf.connection.desired_nodes,
+            max_allowed_nodes:            conf.connection.max_allowed_nodes.unwrap_or_else(|| {
+                derived_max_allowed_nodes(
+                    conf.connection.desired_nodes,
+                    conf.connection.max_allowed_nodes_percentage,
+                )
+            }),
+            relay_broadcast_percentage:   conf.connection.relay_broadcast_percentage,
+            max_latency:                  conf.connection.max_latency,
+            housekeeping_interval:        conf.connection.housekeeping_interval,
+            drop_rebroadcast_probability: conf.cli.drop_rebroadcast_probability,
+        }
+    }
+
+    /// Check the limits against each other and against the settings that
+    /// cannot be changed at runtime. The keep alive is in seconds.
+    pub fn check(
+        &self,
+        hard_connection_limit: u16,
+        max_normal_keep_alive: u64,
+    ) -> anyhow::Result<()> {
+        let problems = self.problems(hard_connection_limit, max_normal_keep_alive);
+        ensure!(problems.is_empty(), "{}", problems.join("\n"));
+        Ok(())
+    }
+
+    /// Describe every way in which the limits fail the checks of
+    /// [check](Self::check).
+    fn problems(&self, hard_connection_limit: u16, max_normal_keep_alive: u64) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut ensure = |condition: bool, problem: String| {
+            if !condition {
+                problems.push(problem);
+            }
+        };
+
+        ensure(
+            self.max_allowed_nodes >= self.desired_nodes_count,
+            format!(
+                "Desired nodes set to {}, but max allowed nodes is set to {}. Max allowed nodes \
+                 must be greater or equal to desired amount of nodes",
+                self.desired_nodes_count, self.max_allowed_nodes
+            ),
+        );
+
+        ensure(
+            hard_connection_limit >= self.desired_nodes_count,
+            "Hard connection limit can't be less than what desired nodes is set to".to_owned(),
+        );
+
+        ensure(
+            self.relay_broadcast_percentage >= 0.0 && self.relay_broadcast_percentage <= 1.0,
+            "Percentage of peers to relay broadcasted packets to, must be between 0.0 and 1.0"
+                .to_owned(),
+        );
+
+        ensure(
+            max_normal_keep_alive >= self.housekeeping_interval * (KEEP_ALIVE_FACTOR as u64),
+            format!(
+                "max-normal-keep-alive ({}) should be at least {} times greater than the value of \
+                 housekeeping-interval ({})",
+                max_normal_keep_alive, KEEP_ALIVE_FACTOR, self.housekeeping_interval
+            ),
+        );
+
+        ensure(
+            self.drop_rebroadcast_probability.map_or(true, |p| (0.0..=1.0).contains(&p)),
+            "The probability to drop rebroadcasts must be between 0.0 and 1.0".to_owned(),
+        );
+
+        problems
+    }
+
+    /// The limits with the ones given in the configuration file replacing the
+    /// current ones. Limits that are not in the file are kept, except that
+    /// the maximum number of nodes is derived from the desired number of
+    /// nodes as in [from_config](Self::from_config) if the file does not set
+    /// it. `max_allowed_nodes_percentage` is the percentage given at startup,
+    /// or `None` if the maximum was set explicitly then.
+    pub fn with_config_file(
+        &self,
+        path: &Path,
+        max_allowed_nodes_percentage: Option<u16>,
+    ) -> anyhow::Result<Self> {
+        fn setting<T: FromStr>(
+            settings: &std::collections::HashMap<&str, String>,
+            key: &str,
+        ) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static, {
+            settings
+                .get(key)
+                .map(|value| value.parse().with_context(|| format!("Invalid value for '{}'.", key)))
+                .transpose()
+        }
+
+        let settings = file::read_settings(path)?;
+        let mut limits = *self;
+        if let Some(desired) = setting(&settings, "connection.desired_nodes")? {
+            limits.desired_nodes_count = desired;
+        }
+        if let Some(max) = setting(&settings, "connection.max_allowed_nodes")? {
+            limits.max_allowed_nodes = max;
+        } else if let Some(percentage) =
+            setting(&settings, "connection.max_allowed_nodes_percentage")?
+                .or(max_allowed_nodes_percentage)
+        {
+            limits.max_allowed_nodes =
+                derived_max_allowed_nodes(limits.desired_nodes_count, percentage);
+        }
+        if let Some(relay) = setting(&settings, "connection.relay_broadcast_percentage")? {
+            limits.relay_broadcast_percentage = relay;
+        }
+        if let Some(latency) = setting(&settings, "connection.max_latency")? {
+            limits.max_latency = Some(latency);
+        }
+        if let Some(interval) = setting(&settings, "connection.housekeeping_interval")? {
+            limits.housekeeping_interval = interval;
+        }
+        if let Some(probability) = setting(&settings, "cli.drop_rebroadcast_probability")? {
+            limits.drop_rebroadcast_probability = Some(probability);
+        }
+        Ok(limits)
+    }
+
+    /// Describe the limits that differ between `self` and `other`, for the
+    /// audit log.
+    pub fn changes(&self, other: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut compare = |name: &str, old: String, new: String| {
+            if old != new {
+                changes.push(format!("{}: {} -> {}", name, old, new));
+            }
+        };
+        compare(
+            "desired-nodes",
+            self.desired_nodes_count.to_string(),
+            other.desired_nodes_count.to_string(),
+        );
+        compare(
+            "max-allowed-nodes",
+            self.max_allowed_nodes.to_string(),
+            other.max_allowed_nodes.to_string(),
+        );
+        compare(
+            "relay-broadcast-percentage",
+            self.relay_broadcast_percentage.to_string(),
+            other.relay_broadcast_percentage.to_string(),
+        );
+        compare(
+            "max-latency",
+            format!("{:?}", self.max_latency),
+            format!("{:?}", other.max_latency),
+        );
+        compare(
+            "housekeeping-interval",
+            self.housekeeping_interval.to_string(),
+            other.housekeeping_interval.to_string(),
+        );
+        compare(
+            "drop-rebroadcast-probability",
+            format!("{:?}", self.drop_rebroadcast_probability),
+            format!("{:?}", other.drop_rebroadcast_probability),
+        );
+        changes
+    }
 }
 
 /// Handles the configuration data.
@@ -997,3 +1354,40 @@ impl AppPreferences {
     /// Returns the path to the config directory.
     pub fn get_config_dir(&self) -> &Path { &self.override_config_dir }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloading_derives_the_maximum_number_of_nodes() {
+        let limits = NetworkLimits {
+            desired_nodes_count:          10,
+            max_allowed_nodes:            15,
+            relay_broadcast_percentage:   1.0,
+            max_latency:                  None,
+            housekeeping_interval:        30,
+            drop_rebroadcast_probability: None,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.toml");
+        let reload = |contents: &str, percentage| {
+            std::fs::write(&path, contents).unwrap();
+            limits.with_config_file(&path, percentage).unwrap()
+        };
+
+        let reloaded = reload("[connection]\ndesired_nodes = 20", Some(150));
+        assert_eq!((reloaded.desired_nodes_count, reloaded.max_allowed_nodes), (20, 30));
+        let reloaded = reload(
+            "[connection]\ndesired_nodes = 20\nmax_allowed_nodes_percentage = 200",
+            Some(150),
+        );
+        assert_eq!(reloaded.max_allowed_nodes, 40);
+        let reloaded =
+            reload("[connection]\ndesired_nodes = 20\nmax_allowed_nodes = 25", Some(150));
+        assert_eq!(reloaded.max_allowed_nodes, 25);
+        // an explicit maximum given at startup is kept
+        let reloaded = reload("[connection]\ndesired_nodes = 12", None);
+        assert_eq!((reloaded.desired_nodes_count, reloaded.max_allowed_nodes), (12, 15));
+    }
+}
@@ -0,0 +1,730 @@
+//! Loading the node configuration from a TOML or YAML file.
+//!
+//! The file mirrors the sections of [Config](super::Config), with the names of
+//! the fields as keys, e.g.
+//!
+//! ```toml
+//! [common]
+//! data_dir = "/var/lib/concordium/data"
+//! config_dir = "/var/lib/concordium/config"
+//!
+//! [connection]
+//! desired_nodes = 10
+//! bootstrap_nodes = ["bootstrap.mainnet.concordium.software:8888"]
+//!
+//! [cli.grpc2]
+//! listen_addr = "127.0.0.1"
+//! listen_port = 20000
+//! ```
+//!
+//! Values from the file are handed to the command line parser through the
+//! environment variables of the corresponding options, unless those are
+//! already set. Hence options given on the command line or in the environment
+//! take precedence over the file.
+
+use anyhow::{bail, Context};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use structopt::clap::ArgMatches;
+
+/// The option that points to the configuration file.
+const CONFIG_FILE_OPTION: &str = "--config-file";
+/// The environment variable that points to the configuration file.
+pub const CONFIG_FILE_ENV: &str = "CONCORDIUM_NODE_CONFIG_FILE";
+
+/// How a value in the file is passed to the option.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    /// A single value.
+    Value,
+    /// A list of values, passed as a comma separated string.
+    List,
+    /// A flag, which is set if the value is `true`.
+    Flag,
+    /// A single value that is redacted in the effective configuration.
+    Secret,
+}
+
+use Kind::*;
+
+/// What is shown in place of the values of secret settings.
+const REDACTED: &str = "<redacted>";
+
+/// The settings that can be given in the file: the path in the file, the name
+/// of the command line argument and its environment variable.
+const SETTINGS: &[(&str, &str, &str, Kind)] = &[
+    ("common.external_port", "external-port", "CONCORDIUM_NODE_EXTERNAL_PORT", Value),
+    ("common.id", "id", "CONCORDIUM_NODE_ID", Value),
+    ("common.listen_port", "listen-port", "CONCORDIUM_NODE_LISTEN_PORT", Value),
+    ("common.listen_address", "listen-address", "CONCORDIUM_NODE_LISTEN_ADDRESS", Value),
+    ("common.debug", "debug", "CONCORDIUM_NODE_LOG_LEVEL_DEBUG", Flag),
+    ("common.trace", "trace", "CONCORDIUM_NODE_LOG_LEVEL_TRACE", Flag),
+    ("common.info", "info", "CONCORDIUM_NODE_LOG_LEVEL_INFO", Flag),
+    ("common.no_consensus_logs", "no-consensus-logs", "CONCORDIUM_NODE_NO_CONSENSUS_LOG", Flag),
+    ("common.network_ids", "network-ids", "CONCORDIUM_NODE_NETWORK_ID", List),
+    ("common.config_dir", "config-dir", "CONCORDIUM_NODE_CONFIG_DIR", Value),
+    ("common.data_dir", "data-dir", "CONCORDIUM_NODE_DATA_DIR", Value),
+    ("common.no_log_timestamp", "no-log-timestamp", "CONCORDIUM_NODE_NO_LOG_TIMESTAMP", Flag),
+    ("common.log_config", "log-config", "CONCORDIUM_NODE_LOG_CONFIG", Value),
+    ("common.min_peers_bucket", "min-peers-bucket", "CONCORDIUM_NODE_MINIMUM_PEERS_BUCKET", Value),
+    ("common.print_config", "print-config", "CONCORDIUM_NODE_PRINT_CONFIG", Flag),
+    (
+        "common.bucket_cleanup_interval",
+        "bucket-cleanup-interval",
+        "CONCORDIUM_NODE_BUCKET_CLEANUP_INTERVAL",
+        Value,
+    ),
+    (
+        "common.network_dump_max_file_size",
+        "network-dump-max-file-size",
+        "CONCORDIUM_NODE_NETWORK_DUMP_MAX_FILE_SIZE",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_listen_addr",
+        "prometheus-listen-addr",
+        "CONCORDIUM_NODE_PROMETHEUS_LISTEN_ADDRESS",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_listen_port",
+        "prometheus-listen-port",
+        "CONCORDIUM_NODE_PROMETHEUS_LISTEN_PORT",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_push_gateway",
+        "prometheus-push-gateway",
+        "CONCORDIUM_NODE_PROMETHEUS_PUSH_GATEWAY",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_job_name",
+        "prometheus-job-name",
+        "CONCORDIUM_NODE_PROMETHEUS_JOB_NAME",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_instance_name",
+        "prometheus-instance-name",
+        "CONCORDIUM_NODE_PROMETHEUS_INSTANCE_NAME",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_push_username",
+        "prometheus-push-username",
+        "CONCORDIUM_NODE_PROMETHEUS_PUSH_GATEWAY_USERNAME",
+        Value,
+    ),
+    (
+        "prometheus.prometheus_push_password",
+        "prometheus-push-password",
+        "CONCORDIUM_NODE_PROMETHEUS_PUSH_GATEWAY_PASSWORD",
+        Secret,
+    ),
+    (
+        "prometheus.prometheus_push_interval",
+        "prometheus-push-interval",
+        "CONCORDIUM_NODE_PROMETHEUS_PUSH_GATEWAY_INTERVAL",
+        Value,
+    ),
+    (
+        "connection.desired_nodes",
+        "desired-nodes",
+        "CONCORDIUM_NODE_CONNECTION_DESIRED_NODES",
+        Value,
+    ),
+    (
+        "connection.max_allowed_nodes",
+        "max-allowed-nodes",
+        "CONCORDIUM_NODE_CONNECTION_MAX_ALLOWED_NODES",
+        Value,
+    ),
+    (
+        "connection.max_allowed_nodes_percentage",
+        "max-allowed-nodes-percentage",
+        "CONCORDIUM_NODE_CONNECTION_MAX_ALLOWED_NODES_PERCENTAGE",
+        Value,
+    ),
+    (
+        "connection.no_bootstrap_dns",
+        "no-bootstrap-dns",
+        "CONCORDIUM_NODE_CONNECTION_NO_BOOTSTRAP_DNS",
+        Flag,
+    ),
+    ("connection.no_clear_bans", "no-clear-bans", "CONCORDIUM_NODE_CONNECTION_NO_CLEAR_BANS", Flag),
+    (
+        "connection.relay_broadcast_percentage",
+        "relay-broadcast-percentage",
+        "CONCORDIUM_NODE_CONNECTION_RELAY_BROADCAST_PERCENTAGE",
+        Value,
+    ),
+    ("connection.connect_to", "connect-to", "CONCORDIUM_NODE_CONNECTION_CONNECT_TO", List),
+    (
+        "connection.disallow_multiple_peers_on_ip",
+        "disallow-multiple-peers-on-ip",
+        "CONCORDIUM_NODE_CONNECTION_DISALLOW_MULTIPLE_PEERS_ON_SAME_IP",
+        Flag,
+    ),
+    ("connection.dns_resolver", "dns-resolver", "CONCORDIUM_NODE_CONNECTION_DNS_RESOLVER", List),
+    (
+        "connection.bootstrap_nodes",
+        "bootstrap-node",
+        "CONCORDIUM_NODE_CONNECTION_BOOTSTRAP_NODES",
+        List,
+    ),
+    (
+        "connection.housekeeping_interval",
+        "housekeeping-interval",
+        "CONCORDIUM_NODE_CONNECTION_HOUSEKEEPING_INTERVAL",
+        Value,
+    ),
+    (
+        "connection.bootstrapping_interval",
+        "bootstrapping-interval",
+        "CONCORDIUM_NODE_CONNECTION_BOOTSTRAPPING_INTERVAL",
+        Value,
+    ),
+    ("connection.max_latency", "max-latency", "CONCORDIUM_NODE_CONNECTION_MAX_LATENCY", Value),
+    (
+        "connection.hard_connection_limit",
+        "hard-connection-limit",
+        "CONCORDIUM_NODE_CONNECTION_HARD_CONNECTION_LIMIT",
+        Value,
+    ),
+    (
+        "connection.conn_requests_batch_limit",
+        "conn-requests-batch-limit",
+        "CONCORDIUM_NODE_CONNECTION_REQUESTS_BATCH_LIMIT",
+        Value,
+    ),
+    (
+        "connection.catch_up_batch_limit",
+        "catch-up-batch-limit",
+        "CONCORDIUM_NODE_CONNECTION_CATCH_UP_BATCH_LIMIT",
+        Value,
+    ),
+    (
+        "connection.thread_pool_size",
+        "thread-pool-size",
+        "CONCORDIUM_NODE_CONNECTION_THREAD_POOL_SIZE",
+        Value,
+    ),
+    (
+        "connection.dedup_size_long",
+        "dedup-size-long",
+        "CONCORDIUM_NODE_CONNECTION_DEDUP_SIZE_LONG",
+        Value,
+    ),
+    (
+        "connection.dedup_size_short",
+        "dedup-size-short",
+        "CONCORDIUM_NODE_CONNECTION_DEDUP_SIZE_SHORT",
+        Value,
+    ),
+    (
+        "connection.socket_write_size",
+        "socket-write-size",
+        "CONCORDIUM_NODE_CONNECTION_SOCKET_WRITE_SIZE",
+        Value,
+    ),
+    (
+        "connection.socket_read_size",
+        "socket-read-size",
+        "CONCORDIUM_NODE_CONNECTION_SOCKET_READ_SIZE",
+        Value,
+    ),
+    (
+        "connection.socket_so_linger",
+        "socket-so-linger",
+        "CONCORDIUM_NODE_CONNECTION_SOCKET_SO_LINGER",
+        Value,
+    ),
+    (
+        "connection.events_queue_size",
+        "events-queue-size",
+        "CONCORDIUM_NODE_CONNECTION_EVENTS_QUEUE_SIZE",
+        Value,
+    ),
+    (
+        "connection.deduplication_hashing_algorithm",
+        "deduplication-hashing-algorithm",
+        "CONCORDIUM_NODE_CONNECTION_DEDUPLICATION_HASHING_ALGORITHM",
+        Value,
+    ),
+    (
+        "connection.max_normal_keep_alive",
+        "max-normal-keep-alive",
+        "CONCORDIUM_NODE_MAX_NORMAL_KEEP_ALIVE",
+        Value,
+    ),
+    (
+        "connection.disable_capabilities",
+        "disable-capabilities",
+        "CONCORDIUM_NODE_CONNECTION_DISABLE_CAPABILITIES",
+        List,
+    ),
+    (
+        "connection.compression_threshold",
+        "compression-threshold",
+        "CONCORDIUM_NODE_CONNECTION_COMPRESSION_THRESHOLD",
+        Value,
+    ),
+    (
+        "connection.require_authenticated_node_ids",
+        "require-authenticated-node-ids",
+        "CONCORDIUM_NODE_CONNECTION_REQUIRE_AUTHENTICATED_NODE_IDS",
+        Flag,
+    ),
+    (
+        "connection.baker_peer_slots",
+        "baker-peer-slots",
+        "CONCORDIUM_NODE_CONNECTION_BAKER_PEER_SLOTS",
+        Value,
+    ),
+    ("connection.network_psk", "network-psk", "CONCORDIUM_NODE_CONNECTION_NETWORK_PSK", Secret),
+    (
+        "connection.network_prologue",
+        "network-prologue",
+        "CONCORDIUM_NODE_CONNECTION_NETWORK_PROLOGUE",
+        Value,
+    ),
+    ("connection.allowed_peers", "allowed-peers", "CONCORDIUM_NODE_CONNECTION_ALLOWED_PEERS", List),
+    ("cli.no_network", "no-network", "CONCORDIUM_NODE_NO_NETWORK", Flag),
+    ("cli.poll_interval", "poll-interval", "CONCORDIUM_NODE_POLL_INTERVAL", Value),
+    (
+        "cli.timeout_bucket_entry_period",
+        "timeout-bucket-entry-period",
+        "CONCORDIUM_NODE_TIMEOUT_BUCKET_ENTRY_PERIOD",
+        Value,
+    ),
+    (
+        "cli.drop_rebroadcast_probability",
+        "drop-rebroadcast-probability",
+        "CONCORDIUM_NODE_DROP_REBROADCSAT_PROBABILITY",
+        Value,
+    ),
+    ("cli.baker.heap_profiling", "heap-profiling", "CONCORDIUM_NODE_RUNTIME_HEAP_PROFILING", Value),
+    ("cli.baker.time_profiling", "time-profiling", "CONCORDIUM_NODE_RUNTIME_TIME_PROFILING", Flag),
+    (
+        "cli.baker.backtraces_profiling",
+        "backtraces-profiling",
+        "CONCORDIUM_NODE_RUNTIME_SHOW_BACKTRACES",
+        Flag,
+    ),
+    (
+        "cli.baker.stack_profiling",
+        "stack-profiling",
+        "CONCORDIUM_NODE_RUNTIME_STACK_PROFILING",
+        Flag,
+    ),
+    (
+        "cli.baker.profiling_sampling_interval",
+        "profiling-sampling-interval",
+        "CONCORDIUM_NODE_PROFILING_SAMPLING_INTERVAL",
+        Value,
+    ),
+    ("cli.baker.gc_logging", "gc-logging", "CONCORDIUM_NODE_RUNTIME_HASKELL_GC_LOGGING", Value),
+    ("cli.baker.rts_flags", "rts-flags", "CONCORDIUM_NODE_RUNTIME_HASKELL_RTS_FLAGS", List),
+    (
+        "cli.baker.maximum_block_size",
+        "maximum-block-size",
+        "CONCORDIUM_NODE_BAKER_MAXIMUM_BLOCK_SIZE",
+        Value,
+    ),
+    (
+        "cli.baker.block_construction_timeout",
+        "block-construction-timeout",
+        "CONCORDIUM_NODE_BAKER_BLOCK_CONSTRUCTION_TIMEOUT",
+        Value,
+    ),
+    (
+        "cli.baker.transaction_insertions_before_purge",
+        "transaction-insertions-before-purge",
+        "CONCORDIUM_NODE_CONSENSUS_TRANSACTION_INSERTIONS_BEFORE_PURGE",
+        Value,
+    ),
+    (
+        "cli.baker.transaction_keep_alive",
+        "transaction-keep-alive",
+        "CONCORDIUM_NODE_CONSENSUS_TRANSACTION_KEEP_ALIVE",
+        Value,
+    ),
+    (
+        "cli.baker.transactions_purging_delay",
+        "transactions-purging-delay",
+        "CONCORDIUM_NODE_CONSENSUS_TRANSACTIONS_PURGING_DELAY",
+        Value,
+    ),
+    (
+        "cli.baker.import_blocks_from",
+        "import-blocks-from",
+        "CONCORDIUM_NODE_CONSENSUS_IMPORT_BLOCKS_FROM",
+        Value,
+    ),
+    (
+        "cli.baker.download_blocks_from",
+        "download-blocks-from",
+        "CONCORDIUM_NODE_CONSENSUS_DOWNLOAD_BLOCKS_FROM",
+        Value,
+    ),
+    (
+        "cli.baker.download_blocks_timeout",
+        "download-blocks-timeout",
+        "CONCORDIUM_NODE_CONSENSUS_DOWNLOAD_BLOCKS_TIMEOUT",
+        Value,
+    ),
+    (
+        "cli.baker.genesis_data_file",
+        "genesis-data-file",
+        "CONCORDIUM_NODE_CONSENSUS_GENESIS_DATA_FILE",
+        Value,
+    ),
+    (
+        "cli.baker.account_cache_size",
+        "account-cache-size",
+        "CONCORDIUM_NODE_CONSENSUS_ACCOUNTS_CACHE_SIZE",
+        Value,
+    ),
+    (
+        "cli.baker.baker_credentials_file",
+        "baker-credentials-file",
+        "CONCORDIUM_NODE_BAKER_CREDENTIALS_FILE",
+        Value,
+    ),
+    (
+        "cli.baker.decrypt_baker_credentials",
+        "decrypt-baker-credentials",
+        "CONCORDIUM_NODE_BAKER_DECRYPT_CREDENTIALS",
+        Flag,
+    ),
+    (
+        "cli.baker.baker_credentials_password_file",
+        "baker-credentials-password-file",
+        "CONCORDIUM_NODE_BAKER_CREDENTIALS_PASSWORD_FILE",
+        Value,
+    ),
+    (
+        "cli.baker.baker_credentials_password_fd",
+        "baker-credentials-password-fd",
+        "CONCORDIUM_NODE_BAKER_CREDENTIALS_PASSWORD_FD",
+        Value,
+    ),
+    (
+        "cli.baker.modules_cache_size",
+        "modules-cache-size",
+        "CONCORDIUM_NODE_CONSENSUS_MODULES_CACHE_SIZE",
+        Value,
+    ),
+    ("cli.rpc.no_rpc_server", "no-rpc-server", "CONCORDIUM_NODE_DISABLE_RPC_SERVER", Flag),
+    (
+        "cli.rpc.no_rpc_server_node_endpoints",
+        "no-rpc-server-node-endpoints",
+        "CONCORDIUM_NODE_DISABLE_RPC_SERVER_NODE_ENDPOINTS",
+        Flag,
+    ),
+    ("cli.rpc.rpc_server_port", "rpc-server-port", "CONCORDIUM_NODE_RPC_SERVER_PORT", Value),
+    ("cli.rpc.rpc_server_addr", "rpc-server-addr", "CONCORDIUM_NODE_RPC_SERVER_ADDR", Value),
+    ("cli.rpc.rpc_server_token", "rpc-server-token", "CONCORDIUM_NODE_RPC_SERVER_TOKEN", Secret),
+    ("cli.grpc2.listen_addr", "grpc2-listen-addr", "CONCORDIUM_NODE_GRPC2_LISTEN_ADDRESS", Value),
+    ("cli.grpc2.listen_port", "grpc2-listen-port", "CONCORDIUM_NODE_GRPC2_LISTEN_PORT", Value),
+    ("cli.grpc2.x509_cert", "x509-cert", "CONCORDIUM_NODE_GRPC2_X509_CERT", Value),
+    (
+        "cli.grpc2.cert_private_key",
+        "cert-private-key",
+        "CONCORDIUM_NODE_GRPC2_CERT_PRIVATE_KEY",
+        Value,
+    ),
+    ("cli.grpc2.enable_grpc_web", "enable-grpc-web", "CONCORDIUM_NODE_GRPC2_ENABLE_GRPC_WEB", Flag),
+    ("cli.grpc2.rest_port", "rest-port", "CONCORDIUM_NODE_GRPC2_REST_PORT", Value),
+    (
+        "cli.grpc2.endpoint_config",
+        "endpoint-config",
+        "CONCORDIUM_NODE_GRPC2_ENDPOINT_CONFIG",
+        Value,
+    ),
+    (
+        "cli.grpc2.health_max_finalized_delay",
+        "health-max-finalized-delay",
+        "CONCORDIUM_NODE_GRPC2_HEALTH_MAX_FINALIZED_DELAY",
+        Value,
+    ),
+    (
+        "cli.grpc2.stream_capacity",
+        "stream-capacity",
+        "CONCORDIUM_NODE_GRPC2_STREAM_CAPACITY",
+        Value,
+    ),
+    (
+        "cli.grpc2.query_cache_size",
+        "query-cache-size",
+        "CONCORDIUM_NODE_GRPC2_QUERY_CACHE_SIZE",
+        Value,
+    ),
+    ("bootstrapper.max_nodes", "max-nodes", "CONCORDIUM_NODE_BOOTSTRAPPER_MAX_NODES", Value),
+    (
+        "bootstrapper.wait_until_minimum_nodes",
+        "wait-until-minimum-nodes",
+        "CONCORDIUM_NODE_BOOTSTRAPPER_WAIT_UNTIL_MINIMUM_NODES",
+        Value,
+    ),
+    (
+        "bootstrapper.bootstrapper_timeout_bucket_entry_period",
+        "bootstrapper-timeout-bucket-entry-period",
+        "CONCORDIUM_NODE_BOOTSTRAPPER_TIMEOUT_BUCKET_ENTRY_PERIOD",
+        Value,
+    ),
+    (
+        "bootstrapper.peer_list_size",
+        "peer-list-size",
+        "CONCORDIUM_NODE_BOOTSTRAPPER_PEER_LIST_SIZE",
+        Value,
+    ),
+    (
+        "bootstrapper.regenesis_block_hashes",
+        "regenesis-block-hashes",
+        "CONCORDIUM_NODE_BOOTSTRAPPER_REGENESIS_BLOCK_HASHES_FILE",
+        Value,
+    ),
+    ("macos.use_mac_log", "use-mac-log", "CONCORDIUM_NODE_MACOS_USE_MAC_LOG", Value),
+];
+
+/// Find the configuration file given on the command line or in the
+/// environment, if any. This is done before the command line is parsed, since
+/// the file can provide required options.
+pub fn config_file_path() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_FILE_OPTION {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) =
+            arg.to_str().and_then(|arg| arg.strip_prefix(CONFIG_FILE_OPTION)?.strip_prefix('='))
+        {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os(CONFIG_FILE_ENV).map(PathBuf::from)
+}
+
+/// Load the configuration file and set the environment variables of the
+/// options it contains, except those already set. Since this modifies the
+/// environment, it must be called before any other threads are started.
+pub fn apply_config_file(path: &Path) -> anyhow::Result<()> {
+    for (env, value) in read_config_file(path)? {
+        if std::env::var_os(env).is_none() {
+            std::env::set_var(env, value);
+        }
+    }
+    Ok(())
+}
+
+/// Load the configuration file and return the values of the settings it
+/// contains, by their path in the file, in the form they would be passed to
+/// the command line parser.
+pub fn read_settings(path: &Path) -> anyhow::Result<HashMap<&'static str, String>> {
+    let mut settings = HashMap::new();
+    for (env, value) in read_config_file(path)? {
+        if let Some(&(key, ..)) = SETTINGS.iter().find(|setting| setting.2 == env) {
+            settings.insert(key, value.to_string_lossy().into_owned());
+        }
+    }
+    Ok(settings)
+}
+
+/// Load the configuration file and return the environment variables of the
+/// options it contains together with their values.
+fn read_config_file(path: &Path) -> anyhow::Result<Vec<(&'static str, OsString)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read the configuration file {}.", path.display()))?;
+    let is_yaml = matches!(path.extension().and_then(|ext| ext.to_str()), Some("yaml" | "yml"));
+    let value: serde_json::Value = if is_yaml {
+        serde_yaml::from_str(&contents).with_context(|| {
+            format!("The configuration file {} is not valid YAML.", path.display())
+        })?
+    } else {
+        toml::from_str(&contents).with_context(|| {
+            format!("The configuration file {} is not valid TOML.", path.display())
+        })?
+    };
+    settings_from_file(&value)
+        .with_context(|| format!("Invalid configuration file {}.", path.display()))
+}
+
+/// Collect the environment variables and their values from the parsed file.
+fn settings_from_file(value: &serde_json::Value) -> anyhow::Result<Vec<(&'static str, OsString)>> {
+    let mut out = Vec::new();
+    collect_settings("", value, &mut out)?;
+    Ok(out)
+}
+
+fn collect_settings(
+    prefix: &str,
+    value: &serde_json::Value,
+    out: &mut Vec<(&'static str, OsString)>,
+) -> anyhow::Result<()> {
+    let table = match value {
+        serde_json::Value::Object(table) => table,
+        _ if prefix.is_empty() => bail!("The configuration must be a table."),
+        _ => bail!("'{}' must be a table.", prefix),
+    };
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        if let Some(&(_, _, env, kind)) = SETTINGS.iter().find(|setting| setting.0 == path) {
+            if let Some(value) = setting_value(&path, kind, value)? {
+                out.push((env, value.into()));
+            }
+        } else if SETTINGS.iter().any(|setting| setting.0.starts_with(&format!("{}.", path))) {
+            collect_settings(&path, value, out)?;
+        } else {
+            bail!("Unknown setting '{}'.", path);
+        }
+    }
+    Ok(())
+}
+
+/// The string to pass in the environment variable, if any.
+fn setting_value(
+    path: &str,
+    kind: Kind,
+    value: &serde_json::Value,
+) -> anyhow::Result<Option<String>> {
+    use serde_json::Value as V;
+    let scalar = |value: &V| match value {
+        V::String(s) => Ok(s.clone()),
+        V::Number(n) => Ok(n.to_string()),
+        V::Bool(b) => Ok(b.to_string()),
+        _ => bail!("'{}' must be a string, a number or a boolean.", path),
+    };
+    match (kind, value) {
+        (Flag, V::Bool(true)) => Ok(Some("true".into())),
+        (Flag, V::Bool(false)) => Ok(None),
+        (Flag, _) => bail!("'{}' must be a boolean.", path),
+        (List, V::Array(values)) => {
+            Ok(Some(values.iter().map(scalar).collect::<anyhow::Result<Vec<_>>>()?.join(",")))
+        }
+        (_, value) => scalar(value).map(Some),
+    }
+}
+
+/// Render the configuration resulting from the command line, the environment,
+/// the configuration file and the defaults as a TOML configuration file. The
+/// values of secret settings are replaced by [REDACTED].
+pub fn effective_config(matches: &ArgMatches) -> anyhow::Result<String> {
+    let mut root = toml::value::Table::new();
+    for &(path, arg, _, kind) in SETTINGS {
+        let value = match kind {
+            Flag => toml::Value::Boolean(matches.is_present(arg)),
+            List => match matches.values_of(arg) {
+                Some(values) => toml::Value::Array(values.map(typed).collect()),
+                None => continue,
+            },
+            Value => match matches.value_of(arg) {
+                Some(value) => typed(value),
+                None => continue,
+            },
+            Secret if matches.is_present(arg) => toml::Value::String(REDACTED.to_owned()),
+            Secret => continue,
+        };
+        let mut keys = path.split('.').collect::<Vec<_>>();
+        let key = keys.pop().unwrap(); // paths are non-empty
+        let mut table = &mut root;
+        for section in keys {
+            table = match table
+                .entry(section.to_owned())
+                .or_insert_with(|| toml::Value::Table(Default::default()))
+            {
+                toml::Value::Table(table) => table,
+                _ => unreachable!("Sections are tables."),
+            };
+        }
+        table.insert(key.to_owned(), value);
+    }
+    Ok(toml::to_string_pretty(&toml::Value::Table(root))?)
+}
+
+/// Numbers and booleans are rendered as such, and everything else as strings.
+fn typed(value: &str) -> toml::Value {
+    if let Ok(n) = value.parse::<i64>() {
+        toml::Value::Integer(n)
+    } else if let Ok(b) = value.parse::<bool>() {
+        toml::Value::Boolean(b)
+    } else {
+        match value.parse::<f64>() {
+            Ok(f) if f.is_finite() && value.contains('.') => toml::Value::Float(f),
+            _ => toml::Value::String(value.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_option_can_be_set_in_the_file() {
+        let source = include_str!("../configuration.rs");
+        for env in source.split("env = \"").skip(1).filter_map(|rest| rest.split('"').next()) {
+            assert!(
+                env == CONFIG_FILE_ENV || SETTINGS.iter().any(|setting| setting.2 == env),
+                "{} is missing from the configuration file settings",
+                env
+            );
+        }
+    }
+
+    #[test]
+    fn settings_are_read_from_sections() {
+        let value: serde_json::Value = toml::from_str(
+            r#"
+            [common]
+            data_dir = "/data"
+            debug = true
+            trace = false
+            [connection]
+            desired_nodes = 10
+            bootstrap_nodes = ["a:8888", "b:8888"]
+            [cli.grpc2]
+            listen_port = 20000
+            "#,
+        )
+        .unwrap();
+        let mut settings = settings_from_file(&value).unwrap();
+        settings.sort();
+        assert_eq!(settings, vec![
+            ("CONCORDIUM_NODE_CONNECTION_BOOTSTRAP_NODES", "a:8888,b:8888".into()),
+            ("CONCORDIUM_NODE_CONNECTION_DESIRED_NODES", "10".into()),
+            ("CONCORDIUM_NODE_DATA_DIR", "/data".into()),
+            ("CONCORDIUM_NODE_GRPC2_LISTEN_PORT", "20000".into()),
+            ("CONCORDIUM_NODE_LOG_LEVEL_DEBUG", "true".into()),
+        ]);
+
+        let unknown: serde_json::Value =
+            toml::from_str("[connection]\nno_such_setting = 1").unwrap();
+        assert!(settings_from_file(&unknown).is_err());
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        use structopt::StructOpt;
+        let matches = super::super::Config::clap().get_matches_from(vec![
+            "concordium_node",
+            "--config-dir=.",
+            "--data-dir=.",
+            "--network-psk=psk-secret",
+            "--rpc-server-token=token-secret",
+        ]);
+        let effective = effective_config(&matches).unwrap();
+        assert!(!effective.contains("secret"));
+        assert!(effective.contains(&format!("network_psk = \"{}\"", REDACTED)));
+        assert!(effective.contains(&format!("rpc_server_token = \"{}\"", REDACTED)));
+        assert!(!effective.contains("prometheus_push_password"));
+    }
+}
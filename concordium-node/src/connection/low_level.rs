@@ -1,11 +1,10 @@
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use byteorder::{NetworkEndian, WriteBytesExt};
 use bytesize::ByteSize;
 use mio::net::TcpStream;
 use noiseexplorer_xx::{
     consts::{DHLEN, MAC_LENGTH},
     noisesession::NoiseSession,
-    types::Keypair,
 };
 
 use crate::{configuration::PROTOCOL_MAX_MESSAGE_SIZE, p2p::maintenance::P2PNode};
@@ -22,13 +21,15 @@ use std::{
 /// The size of the noise message payload.
 type PayloadSize = u32;
 const PAYLOAD_SIZE: usize = mem::size_of::<PayloadSize>();
-const PROLOGUE: &[u8] = b"CP2P";
+/// The noise prologue used unless configured otherwise.
+pub const DEFAULT_PROLOGUE: &[u8] = b"CP2P";
 pub const NOISE_MAX_MESSAGE_LEN: usize = 64 * 1024 - 1; // 65535
 const NOISE_AUTH_TAG_LEN: usize = 16;
 pub const NOISE_MAX_PAYLOAD_LEN: usize = NOISE_MAX_MESSAGE_LEN - NOISE_AUTH_TAG_LEN;
 pub const HANDSHAKE_SIZE_LIMIT: usize = 1024;
-/// Not really a PSK, but serves a PSK-like function
-pub const PSK: &[u8] = b"b6461bd246843f70ac1328401405b2b4e725994d7d144a75bff1a04a247d64b7";
+/// Not really a PSK, but serves a PSK-like function. Used unless configured
+/// otherwise.
+pub const DEFAULT_PSK: &[u8] = b"b6461bd246843f70ac1328401405b2b4e725994d7d144a75bff1a04a247d64b7";
 /// The size of the initial socket write queue allocation.
 const WRITE_QUEUE_ALLOC: usize = 1024 * 1024;
 
@@ -180,7 +181,11 @@ impl ConnectionLowLevel {
         ConnectionLowLevel {
             handler: Arc::downgrade(handler),
             socket,
-            noise_session: NoiseSession::init_session(is_initiator, PROLOGUE, Keypair::default()),
+            noise_session: NoiseSession::init_session(
+                is_initiator,
+                &handler.config.noise_prologue,
+                handler.node_key.keypair(),
+            ),
             noise_buffer: vec![0u8; NOISE_MAX_MESSAGE_LEN].into_boxed_slice(),
             socket_buffer: SocketBuffer::new(read_size),
             incoming_msg: IncomingMessage::default(),
@@ -278,13 +283,15 @@ impl ConnectionLowLevel {
     /// Immediately sends the XX-A handshake message
     pub fn send_handshake_message_a(&mut self) -> anyhow::Result<()> {
         let pad = 16;
-        send_xx_msg!(self, DHLEN, PSK, pad, "A");
+        let psk = self.handler.upgrade().unwrap().config.noise_psk.clone(); // safe
+        send_xx_msg!(self, DHLEN, psk, pad, "A");
         Ok(())
     }
 
     fn process_msg_a(&mut self, len: usize) -> anyhow::Result<Vec<u8>> {
-        recv_xx_msg!(self, len, "A");
         let pad = 16;
+        ensure!(len >= DHLEN + pad, "XX handshake message A is too short ({} bytes)", len);
+        recv_xx_msg!(self, len, "A");
         let payload_in = self.socket_buffer.slice(len)[DHLEN..][..len - DHLEN - pad].try_into()?;
         let payload_out = self.handler.upgrade().unwrap().produce_handshake_request()?; // safe
         send_xx_msg!(self, DHLEN * 2 + MAC_LENGTH, &payload_out, MAC_LENGTH, "B");
@@ -293,6 +300,11 @@ impl ConnectionLowLevel {
     }
 
     fn process_msg_b(&mut self, len: usize) -> anyhow::Result<Vec<u8>> {
+        ensure!(
+            len >= DHLEN * 2 + MAC_LENGTH * 2,
+            "XX handshake message B is too short ({} bytes)",
+            len
+        );
         recv_xx_msg!(self, len, "B");
         let payload_in = self.socket_buffer.slice(len)[DHLEN * 2 + MAC_LENGTH..]
             [..len - DHLEN * 2 - MAC_LENGTH * 2]
@@ -304,6 +316,11 @@ impl ConnectionLowLevel {
     }
 
     fn process_msg_c(&mut self, len: usize) -> anyhow::Result<Vec<u8>> {
+        ensure!(
+            len >= DHLEN + MAC_LENGTH * 2,
+            "XX handshake message C is too short ({} bytes)",
+            len
+        );
         recv_xx_msg!(self, len, "C");
         let payload = self.socket_buffer.slice(len)[DHLEN + MAC_LENGTH..]
             [..len - DHLEN - MAC_LENGTH * 2]
@@ -312,6 +329,12 @@ impl ConnectionLowLevel {
         Ok(payload)
     }
 
+    /// The static key of the peer, once it has been authenticated in the noise
+    /// handshake.
+    pub fn remote_static_key(&self) -> Option<[u8; DHLEN]> {
+        self.noise_session.get_remote_static().map(|key| key.as_bytes())
+    }
+
     #[inline]
     /// Checks whether the low-level noise handshake is complete.
     fn is_post_handshake(&self) -> bool {
@@ -432,7 +455,9 @@ impl ConnectionLowLevel {
                 }?;
 
                 if !self.noise_session.is_initiator() {
-                    if self.noise_session.get_message_count() == 1 && payload != PSK {
+                    if self.noise_session.get_message_count() == 1
+                        && payload != self.handler.upgrade().unwrap().config.noise_psk
+                    {
                         bail!("Invalid PSK");
                     } else if self.noise_session.get_message_count() == 2 {
                         // message C doesn't carry a payload; break the reading loop
@@ -632,3 +657,58 @@ impl ConnectionLowLevel {
     #[inline]
     fn write_size(&self) -> usize { self.write_size }
 }
+
+/// Feed the given bytes to a fresh connection as if they were sent by the
+/// remote end, and read from the connection until the input is exhausted or
+/// an error occurs. Returns the complete messages that were read. If
+/// `is_initiator` is set, the connection sends the first XX handshake message
+/// before reading, so the input is expected to start with the second one.
+///
+/// This exposes the noise framing in `read_from_socket` to fuzzing and
+/// regression tests.
+#[cfg(any(test, bench, feature = "test_utils"))]
+pub fn read_untrusted_input(
+    node: &Arc<P2PNode>,
+    is_initiator: bool,
+    input: &[u8],
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    use std::{
+        net::{Shutdown, TcpListener},
+        thread,
+        time::Duration,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let mut remote = std::net::TcpStream::connect(listener.local_addr()?)?;
+    let (local, _) = listener.accept()?;
+    local.set_nonblocking(true)?;
+    remote.write_all(input)?;
+    remote.shutdown(Shutdown::Write)?;
+
+    let mut low_level = ConnectionLowLevel::new(
+        node,
+        TcpStream::from_std(local),
+        is_initiator,
+        node.config.socket_read_size,
+        node.config.socket_write_size,
+    );
+    if is_initiator {
+        low_level.send_handshake_message_a()?;
+    }
+
+    // The input is written before reading starts, so the socket only blocks
+    // briefly, if at all, before the end of the input is reached.
+    let mut messages = Vec::new();
+    let mut blocked_reads = 0;
+    loop {
+        match low_level.read_from_socket()? {
+            ReadResult::Complete(msg) => messages.push(msg),
+            ReadResult::Incomplete => {}
+            ReadResult::WouldBlock if blocked_reads < 100 => {
+                blocked_reads += 1;
+                thread::sleep(Duration::from_millis(1));
+            }
+            ReadResult::WouldBlock | ReadResult::Closed => return Ok(messages),
+        }
+    }
+}
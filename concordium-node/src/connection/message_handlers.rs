@@ -2,19 +2,23 @@
 
 use crate::{
     common::{
+        get_current_stamp,
         p2p_peer::{PeerStats, RemotePeerId},
-        PeerType,
+        P2PNodeId, PeerType,
     },
     configuration::{is_compatible_version, is_compatible_wire_version, MAX_PEER_NETWORKS},
-    connection::{ConnChange, Connection},
+    connection::{ConnChange, Connection, MessageSendingPriority},
+    lock_or_die, netmsg,
     network::{
-        Handshake, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest, NetworkResponse,
-        PacketDestination,
+        compression::OutgoingMessage, Capabilities, Handshake, NetworkMessage, NetworkPacket,
+        NetworkPayload, NetworkRequest, NetworkResponse, PacketDestination, TransactionDigest,
     },
+    p2p::{baker_proof::BakerClaim, transaction_relay::MAX_TRANSACTION_DIGESTS},
     plugins::consensus::*,
     read_or_die,
 };
 use anyhow::{bail, ensure};
+use std::sync::Arc;
 
 impl Connection {
     /// Processes a network message based on its type.
@@ -68,6 +72,25 @@ impl Connection {
                 debug!("Got a LeaveNetwork request from peer {}", peer_id);
                 self.remove_remote_end_network(network)
             }
+            NetworkPayload::NetworkRequest(
+                NetworkRequest::TransactionAnnouncement(digests),
+                ..,
+            ) => {
+                trace!(
+                    "Got a TransactionAnnouncement ({} digests) from peer {}",
+                    digests.len(),
+                    peer_id
+                );
+                self.handle_transaction_announcement(digests)
+            }
+            NetworkPayload::NetworkRequest(NetworkRequest::TransactionRequest(digests), ..) => {
+                trace!(
+                    "Got a TransactionRequest ({} digests) from peer {}",
+                    digests.len(),
+                    peer_id
+                );
+                self.handle_transaction_request(digests)
+            }
             NetworkPayload::NetworkPacket(pac, ..) => {
                 // packet receipt is logged later, along with its contents
                 self.handle_incoming_packet(pac, peer_id)
@@ -102,6 +125,24 @@ impl Connection {
         if handshake.networks.len() > MAX_PEER_NETWORKS {
             bail!("Rejecting handshake: too many networks.");
         }
+        let remote_static_key = self.low_level.remote_static_key();
+        let authenticated_id = match remote_static_key {
+            Some(key) if P2PNodeId::from_public_key(&key) == handshake.remote_id => {
+                Some(handshake.remote_id)
+            }
+            _ if !self.handler.config.require_authenticated_node_ids => {
+                debug!("Accepting peer {} with an unauthenticated node id.", handshake.remote_id);
+                None
+            }
+            Some(_) => bail!(
+                "Rejecting handshake: node id {} does not match the peer's static key.",
+                handshake.remote_id
+            ),
+            None => bail!("Rejecting handshake: the peer's static key is not authenticated."),
+        };
+        if !self.handler.is_allowed_authenticated_peer(self.remote_addr().ip(), authenticated_id) {
+            bail!("Rejecting handshake: peer {} is not an allowed peer.", handshake.remote_id);
+        }
 
         {
             let our_blocks = read_or_die!(self.handler.config.regenesis_arc.blocks);
@@ -123,11 +164,23 @@ impl Connection {
             }
         }
 
+        let capabilities = self.handler.config.capabilities.intersection(handshake.capabilities);
+
+        // The claim is checked against the current bakers in the poll loop, since that
+        // requires access to consensus.
+        if let (false, Some(key)) = (handshake.proof.is_empty(), remote_static_key) {
+            match BakerClaim::parse(&handshake.proof, key) {
+                Ok(claim) => self.baker_claim = Some(claim),
+                Err(e) => debug!("Ignoring the baker proof of peer {}: {}", handshake.remote_id, e),
+            }
+        }
+
         self.promote_to_post_handshake(
             handshake.remote_id,
             handshake.remote_port,
             &handshake.networks,
             wire_version,
+            capabilities,
         );
 
         if self.handler.peer_type() == PeerType::Bootstrapper {
@@ -143,6 +196,80 @@ impl Connection {
 
     fn handle_pong(&self) -> anyhow::Result<()> { self.stats.notify_pong() }
 
+    fn ensure_pull_relay(&self, digests: &[TransactionDigest]) -> anyhow::Result<()> {
+        ensure!(
+            self.capabilities.contains(Capabilities::TRANSACTION_PULL_RELAY),
+            "The pull-based transaction relay wasn't negotiated with peer {}.",
+            self.remote_peer.local_id
+        );
+        ensure!(
+            digests.len() <= MAX_TRANSACTION_DIGESTS,
+            "Too many transaction digests ({}) in a single message.",
+            digests.len()
+        );
+        Ok(())
+    }
+
+    /// Request the announced transactions that haven't been seen yet, and
+    /// that aren't awaited from another peer.
+    fn handle_transaction_announcement(
+        &mut self,
+        digests: Vec<TransactionDigest>,
+    ) -> anyhow::Result<()> {
+        self.ensure_pull_relay(&digests)?;
+        self.handler.stats.tx_announcements_received_add(digests.len() as u64);
+        // disregard transactions when in bootstrapper mode
+        if self.handler.self_peer.peer_type == PeerType::Bootstrapper {
+            return Ok(());
+        }
+
+        let wanted = digests
+            .into_iter()
+            .filter(|digest| self.handler.should_request_transaction(digest))
+            .collect::<Vec<_>>();
+        if !wanted.is_empty() {
+            self.handler.stats.tx_requests_sent_add(wanted.len() as u64);
+            let request = netmsg!(NetworkRequest, NetworkRequest::TransactionRequest(wanted));
+            let mut serialized = Vec::with_capacity(96);
+            request.serialize(&mut serialized)?;
+            self.async_send(Arc::from(serialized), MessageSendingPriority::Normal);
+        }
+
+        Ok(())
+    }
+
+    /// Send the requested transactions that are still available, as broadcast
+    /// packets so that the peer relays them further.
+    fn handle_transaction_request(
+        &mut self,
+        digests: Vec<TransactionDigest>,
+    ) -> anyhow::Result<()> {
+        self.ensure_pull_relay(&digests)?;
+
+        for digest in digests {
+            let transaction =
+                lock_or_die!(self.handler.connection_handler.relayed_transactions).get(&digest);
+            if let Some((network_id, packet)) = transaction {
+                let message = netmsg!(NetworkPacket, NetworkPacket {
+                    destination: PacketDestination::Broadcast(Vec::new()),
+                    network_id,
+                    message: packet.to_vec(),
+                });
+                let mut serialized = Vec::with_capacity(packet.len() + 64);
+                message.serialize(&mut serialized)?;
+                let serialized =
+                    OutgoingMessage::new(&serialized, self.handler.config.compression_threshold)
+                        .for_capabilities(self.capabilities, &self.handler.stats);
+                self.async_send(serialized, MessageSendingPriority::Normal);
+                self.handler.stats.tx_requests_served_inc();
+            } else {
+                self.handler.stats.tx_requests_missed_inc();
+            }
+        }
+
+        Ok(())
+    }
+
     fn handle_incoming_packet(
         &self,
         pac: NetworkPacket,
@@ -151,6 +278,13 @@ impl Connection {
         let is_broadcast = matches!(pac.destination, PacketDestination::Broadcast(..));
 
         // Ignore the deserialized p2p node ids to be excluded from the wire.
-        handle_pkt_out(&self.handler, vec![peer_id], peer_id, pac.message, is_broadcast)
+        handle_pkt_out(
+            &self.handler,
+            vec![peer_id],
+            peer_id,
+            pac.message,
+            is_broadcast,
+            self.verified_baker().is_some(),
+        )
     }
 }
@@ -5,6 +5,10 @@ pub mod message_handlers;
 #[cfg(test)]
 mod tests;
 
+#[cfg(any(test, bench, feature = "test_utils"))]
+pub use low_level::read_untrusted_input;
+pub use low_level::{DEFAULT_PROLOGUE, DEFAULT_PSK};
+
 use anyhow::{bail, ensure};
 use bytesize::ByteSize;
 use circular_queue::CircularQueue;
@@ -23,10 +27,11 @@ use crate::{
     connection::low_level::ReadResult,
     netmsg,
     network::{
-        NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest, NetworkResponse,
-        Networks, WireProtocolVersion, WIRE_PROTOCOL_CURRENT_VERSION,
+        compression::{decompress, is_compressed},
+        Capabilities, NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest,
+        NetworkResponse, Networks, WireProtocolVersion, WIRE_PROTOCOL_CURRENT_VERSION,
     },
-    p2p::P2PNode,
+    p2p::{baker_proof::BakerClaim, transaction_relay::transaction_digest, P2PNode},
     read_or_die, write_or_die,
 };
 
@@ -43,6 +48,7 @@ use std::{
         atomic::{AtomicI64, AtomicU64, Ordering},
         Arc, RwLock,
     },
+    time::Instant,
 };
 
 /// Designates the sending priority of outgoing messages.
@@ -82,6 +88,8 @@ pub trait DeduplicationQueue: Send + Sync {
     /// Check if element exists, and if not insert it - return status is whether
     /// or not message was a duplicate
     fn check_and_insert(&mut self, input: &[u8]) -> anyhow::Result<bool>;
+    /// Check if element exists without inserting it
+    fn contains(&self, input: &[u8]) -> bool;
     /// Invalidate the entry in the queue if a key is found
     fn invalidate_if_exists(&mut self, input: &[u8]);
 }
@@ -127,6 +135,11 @@ impl DeduplicationQueue for DeduplicationQueueXxHash64 {
         }
     }
 
+    fn contains(&self, input: &[u8]) -> bool {
+        let num = self.hash(input);
+        self.queue.iter().any(|n| n == &num)
+    }
+
     fn invalidate_if_exists(&mut self, input: &[u8]) {
         let num = self.hash(input);
         if let Some(old_val) = self.queue.iter_mut().find(|val| **val == num) {
@@ -170,6 +183,11 @@ impl DeduplicationQueue for DeduplicationQueueSha256 {
         }
     }
 
+    fn contains(&self, input: &[u8]) -> bool {
+        let hash = self.hash(input);
+        self.queue.iter().any(|n| *n == hash)
+    }
+
     fn invalidate_if_exists(&mut self, input: &[u8]) {
         let hash = self.hash(input);
         if let Some(old_val) = self.queue.iter_mut().find(|val| **val == hash) {
@@ -183,29 +201,54 @@ impl DeduplicationQueue for DeduplicationQueueSha256 {
 /// Contains the circular queues of hashes of different consensus objects
 /// for deduplication purposes.
 pub struct DeduplicationQueues {
-    pub finalizations: RwLock<Box<dyn DeduplicationQueue>>,
-    pub transactions:  RwLock<Box<dyn DeduplicationQueue>>,
-    pub blocks:        RwLock<Box<dyn DeduplicationQueue>>,
-    pub fin_records:   RwLock<Box<dyn DeduplicationQueue>>,
+    pub finalizations:       RwLock<Box<dyn DeduplicationQueue>>,
+    pub transactions:        RwLock<Box<dyn DeduplicationQueue>>,
+    pub blocks:              RwLock<Box<dyn DeduplicationQueue>>,
+    pub fin_records:         RwLock<Box<dyn DeduplicationQueue>>,
+    /// The digests of the transactions that were seen, used by the
+    /// pull-based transaction relay.
+    pub transaction_digests: RwLock<Box<dyn DeduplicationQueue>>,
 }
 
 impl DeduplicationQueues {
     /// Creates the deduplication queues of specified sizes: short for blocks
     /// and finalization records and long for finalization messages and
-    /// transactions.
+    /// transactions (and their digests).
     pub fn new(algorithm: DeduplicationHashAlgorithm, long_size: usize, short_size: usize) -> Self {
         match algorithm {
             DeduplicationHashAlgorithm::XxHash64 => Self {
-                finalizations: RwLock::new(Box::new(DeduplicationQueueXxHash64::new(long_size))),
-                transactions:  RwLock::new(Box::new(DeduplicationQueueXxHash64::new(long_size))),
-                blocks:        RwLock::new(Box::new(DeduplicationQueueXxHash64::new(short_size))),
-                fin_records:   RwLock::new(Box::new(DeduplicationQueueXxHash64::new(short_size))),
+                finalizations:       RwLock::new(Box::new(DeduplicationQueueXxHash64::new(
+                    long_size,
+                ))),
+                transactions:        RwLock::new(Box::new(DeduplicationQueueXxHash64::new(
+                    long_size,
+                ))),
+                blocks:              RwLock::new(Box::new(DeduplicationQueueXxHash64::new(
+                    short_size,
+                ))),
+                fin_records:         RwLock::new(Box::new(DeduplicationQueueXxHash64::new(
+                    short_size,
+                ))),
+                transaction_digests: RwLock::new(Box::new(DeduplicationQueueXxHash64::new(
+                    long_size,
+                ))),
             },
             DeduplicationHashAlgorithm::Sha256 => Self {
-                finalizations: RwLock::new(Box::new(DeduplicationQueueSha256::new(long_size))),
-                transactions:  RwLock::new(Box::new(DeduplicationQueueSha256::new(long_size))),
-                blocks:        RwLock::new(Box::new(DeduplicationQueueSha256::new(short_size))),
-                fin_records:   RwLock::new(Box::new(DeduplicationQueueSha256::new(short_size))),
+                finalizations:       RwLock::new(Box::new(DeduplicationQueueSha256::new(
+                    long_size,
+                ))),
+                transactions:        RwLock::new(Box::new(DeduplicationQueueSha256::new(
+                    long_size,
+                ))),
+                blocks:              RwLock::new(Box::new(DeduplicationQueueSha256::new(
+                    short_size,
+                ))),
+                fin_records:         RwLock::new(Box::new(DeduplicationQueueSha256::new(
+                    short_size,
+                ))),
+                transaction_digests: RwLock::new(Box::new(DeduplicationQueueSha256::new(
+                    long_size,
+                ))),
             },
         }
     }
@@ -353,9 +396,15 @@ impl MessageQueues {
 
     /// Dequeue a message, taking from the high priority queue first.
     pub fn dequeue(&mut self) -> Option<Arc<[u8]>> {
+        self.dequeue_with_priority().map(|(_, message)| message)
+    }
+
+    /// Dequeue a message along with the priority it was queued with, taking
+    /// from the high priority queue first.
+    pub fn dequeue_with_priority(&mut self) -> Option<(MessageSendingPriority, Arc<[u8]>)> {
         match self.high.pop_front() {
-            Some(message) => Some(message),
-            None => self.low.pop_front(),
+            Some(message) => Some((MessageSendingPriority::High, message)),
+            None => self.low.pop_front().map(|message| (MessageSendingPriority::Normal, message)),
         }
     }
 }
@@ -375,6 +424,15 @@ pub struct Connection {
     pub pending_messages:    MessageQueues,
     /// The wire protocol version for communicating on the connection.
     pub wire_version:        WireProtocolVersion,
+    /// The optional protocol features supported by both ends of the
+    /// connection; empty until the handshake is concluded.
+    pub capabilities:        Capabilities,
+    /// The peer's claim to be a baker, if it sent a proof in the handshake.
+    pub baker_claim:         Option<BakerClaim>,
+    /// Messages held back by the fault injector, along with the time at
+    /// which they are to be sent and the priority they were queued with.
+    #[cfg(any(test, bench, feature = "test_utils"))]
+    held_messages:           VecDeque<(Instant, MessageSendingPriority, Arc<[u8]>)>,
 }
 
 impl PartialEq for Connection {
@@ -433,6 +491,10 @@ impl Connection {
             // When we create the connection, we set the wire protocol version
             // to the current version, but this is overwritten in the handshake.
             wire_version: WIRE_PROTOCOL_CURRENT_VERSION,
+            capabilities: Capabilities::empty(),
+            baker_claim: None,
+            #[cfg(any(test, bench, feature = "test_utils"))]
+            held_messages: VecDeque::new(),
         })
     }
 
@@ -478,10 +540,17 @@ impl Connection {
                 &packet.message,
                 &mut **write_or_die!(deduplication_queues.finalizations),
             )?,
-            PacketType::Transaction => dedup_with(
-                &packet.message,
-                &mut **write_or_die!(deduplication_queues.transactions),
-            )?,
+            PacketType::Transaction => {
+                let is_duplicate = dedup_with(
+                    &packet.message,
+                    &mut **write_or_die!(deduplication_queues.transactions),
+                )?;
+                // don't request the transaction if it gets announced later on
+                if self.handler.pulls_transactions() {
+                    self.handler.mark_transaction_seen(&transaction_digest(&packet.message))?;
+                }
+                is_duplicate
+            }
             PacketType::Block => {
                 dedup_with(&packet.message, &mut **write_or_die!(deduplication_queues.blocks))?
             }
@@ -526,7 +595,18 @@ impl Connection {
             self.send_to_dump(bytes.clone(), true);
         }
 
-        let mut message = NetworkMessage::deserialize(&bytes)?;
+        let mut message = if is_compressed(&bytes) {
+            NetworkMessage::deserialize(&self.decompress_message(&bytes)?)?
+        } else {
+            NetworkMessage::deserialize(&bytes)?
+        };
+
+        #[cfg(any(test, bench, feature = "test_utils"))]
+        if let Some(ref injector) = *read_or_die!(self.handler.connection_handler.fault_injector) {
+            if !injector.admit_inbound(self.handler.id(), self.remote_id(), &message)? {
+                return Ok(());
+            }
+        }
 
         if let NetworkPayload::NetworkPacket(ref mut packet) = message.payload {
             // disregard packets when in bootstrapper mode
@@ -534,7 +614,21 @@ impl Connection {
                 return Ok(());
             }
             // deduplicate the incoming packet payload
-            if self.is_packet_duplicate(packet)? {
+            let is_duplicate = self.is_packet_duplicate(packet)?;
+
+            #[cfg(any(test, bench, feature = "test_utils"))]
+            if let Some(ref injector) =
+                *read_or_die!(self.handler.connection_handler.fault_injector)
+            {
+                injector.record_packet(
+                    self.handler.id(),
+                    self.remote_peer.local_id,
+                    packet,
+                    is_duplicate,
+                );
+            }
+
+            if is_duplicate {
                 return Ok(());
             }
         }
@@ -543,6 +637,24 @@ impl Connection {
         self.handle_incoming_message(message, conn_stats)
     }
 
+    /// Decompress a message compressed by the peer, making sure it used an
+    /// algorithm negotiated in the handshake.
+    fn decompress_message(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let start = Instant::now();
+        let result = decompress(bytes);
+        self.handler.stats.compression_time_add(start.elapsed().as_secs_f64());
+        let (algorithm, decompressed) = result?;
+        ensure!(
+            self.capabilities.contains(algorithm.capability()),
+            "Received a message compressed with {}, which wasn't negotiated.",
+            algorithm
+        );
+        self.handler.stats.compression_bytes_saved_received_add(
+            decompressed.len().saturating_sub(bytes.len()) as u64,
+        );
+        Ok(decompressed)
+    }
+
     /// Concludes the connection's handshake process.
     pub fn promote_to_post_handshake(
         &mut self,
@@ -550,6 +662,7 @@ impl Connection {
         peer_port: u16,
         nets: &Networks,
         wire_version: WireProtocolVersion,
+        capabilities: Capabilities,
     ) {
         self.remote_peer.self_id = Some(id);
         self.remote_peer.external_port = peer_port;
@@ -559,13 +672,20 @@ impl Connection {
         }
         self.populate_remote_end_networks(self.remote_peer, nets);
         self.wire_version = wire_version;
+        self.capabilities = capabilities;
         self.handler.register_conn_change(ConnChange::Promotion(self.token()));
         debug!(
-            "Concluded handshake with peer {} (their id {}); wire protocol version {}",
-            self.remote_peer.local_id, id, wire_version
+            "Concluded handshake with peer {} (their id {}); wire protocol version {}; \
+             capabilities {}",
+            self.remote_peer.local_id, id, wire_version, capabilities
         );
     }
 
+    /// The baker id of the peer, if it proved to be a current baker.
+    pub fn verified_baker(&self) -> Option<u64> {
+        self.baker_claim.as_ref().and_then(BakerClaim::verified_baker)
+    }
+
     /// Queues a message to be sent to the connection.
     #[inline]
     pub fn async_send(&mut self, message: Arc<[u8]>, priority: MessageSendingPriority) {
@@ -615,8 +735,12 @@ impl Connection {
 
     #[cfg(feature = "network_dump")]
     fn send_to_dump(&self, buf: Arc<[u8]>, inbound: bool) {
+        // a dump that reached one of its limits no longer consumes the items
+        if !self.handler.is_dump_active() {
+            return;
+        }
         if let Some(ref sender) = &*read_or_die!(self.handler.connection_handler.log_dumper) {
-            let di = DumpItem::new(inbound, self.remote_peer.addr.ip(), buf);
+            let di = DumpItem::new(inbound, self.remote_peer.addr.ip(), self.remote_id(), buf);
             let _ = sender.send(di);
         }
     }
@@ -715,6 +839,9 @@ impl Connection {
     /// Processes a queue with pending messages, writing them to the socket.
     #[inline]
     pub fn send_pending_messages(&mut self) -> anyhow::Result<()> {
+        #[cfg(any(test, bench, feature = "test_utils"))]
+        self.hold_back_delayed_messages();
+
         while let Some(msg) = self.pending_messages.dequeue() {
             trace!(
                 "Attempting to send {} to {}",
@@ -737,6 +864,37 @@ impl Connection {
 
         Ok(())
     }
+
+    /// Apply the latency configured in the fault injector, if any, to the
+    /// pending messages. Messages are moved to the queue of held messages and
+    /// put back into the pending queue once their delay has passed.
+    #[cfg(any(test, bench, feature = "test_utils"))]
+    fn hold_back_delayed_messages(&mut self) {
+        let injector = match *read_or_die!(self.handler.connection_handler.fault_injector) {
+            Some(ref injector) => Arc::clone(injector),
+            None => return,
+        };
+        let (local_id, remote_id) = match self.remote_id() {
+            Some(id) => (self.handler.id(), id),
+            None => return,
+        };
+        if self.held_messages.is_empty() && injector.outbound_delay(local_id, remote_id).is_none() {
+            return;
+        }
+
+        let now = Instant::now();
+        while let Some((priority, msg)) = self.pending_messages.dequeue_with_priority() {
+            let delay = injector.outbound_delay(local_id, remote_id).unwrap_or_default();
+            self.held_messages.push_back((now + delay, priority, msg));
+        }
+
+        let (due, held): (VecDeque<_>, VecDeque<_>) =
+            self.held_messages.drain(..).partition(|(send_at, ..)| *send_at <= now);
+        self.held_messages = held;
+        for (_, priority, msg) in due {
+            self.pending_messages.enqueue(priority, msg);
+        }
+    }
 }
 
 /// Drop the connection and deregister it from the connection handler's poll
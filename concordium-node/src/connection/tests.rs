@@ -2,19 +2,29 @@ use itertools::Itertools;
 
 use crate::{
     common::PeerType,
+    connection::read_untrusted_input,
     consensus_ffi::helpers::PacketType,
-    network::NetworkId,
+    fault_injection::{LinkFaults, TestNetwork},
+    network::{Capabilities, NetworkId, SUPPORTED_CAPABILITIES},
     p2p::connectivity::send_broadcast_message,
+    read_or_die,
     test_utils::{
-        await_handshakes, connect, dummy_regenesis_blocks, make_node_and_sync, next_available_port,
+        await_handshakes, connect, dummy_regenesis_blocks, fuzz_regressions, get_test_config,
+        make_node_and_sync, make_node_and_sync_with_config, next_available_port,
         stop_node_delete_dirs,
     },
 };
 
-use std::sync::Arc;
+use anyhow::ensure;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 const NID: u16 = 100;
 const NODE_COUNT: usize = 10;
+/// How long to wait for the invariants to hold in the fault injection tests.
+const FAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[test]
 fn basic_connectivity() {
@@ -71,3 +81,182 @@ fn basic_connectivity() {
         stop_node_delete_dirs(dp, node);
     }
 }
+
+#[test]
+fn capability_negotiation() -> anyhow::Result<()> {
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.disable_capabilities = vec![Capabilities::COMPRESSION_ZSTD];
+    let (node_1, dp_1) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks())?;
+    let (node_2, dp_2) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )?;
+    assert!(!node_1.config.capabilities.contains(Capabilities::COMPRESSION_ZSTD));
+
+    connect(&node_1, &node_2);
+    await_handshakes(&node_1);
+    await_handshakes(&node_2);
+
+    // both ends of the connection agree on the common capabilities, which are
+    // the supported ones except the disabled one
+    let expected = SUPPORTED_CAPABILITIES.difference(Capabilities::COMPRESSION_ZSTD);
+    assert!(!expected.is_empty());
+    assert_eq!(node_2.config.capabilities, SUPPORTED_CAPABILITIES);
+    for node in [&node_1, &node_2] {
+        for conn in read_or_die!(node.connections()).values() {
+            assert_eq!(conn.capabilities, expected);
+        }
+    }
+
+    stop_node_delete_dirs(dp_1, node_1);
+    stop_node_delete_dirs(dp_2, node_2);
+    Ok(())
+}
+
+#[test]
+fn compressed_broadcast() -> anyhow::Result<()> {
+    let (node_1, dp_1) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )?;
+    let (node_2, dp_2) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )?;
+    connect(&node_1, &node_2);
+    await_handshakes(&node_1);
+    await_handshakes(&node_2);
+
+    // an empty Block packet, padded well above the compression threshold
+    let mut payload = vec![PacketType::Block as u8];
+    payload.resize(node_1.config.compression_threshold * 4, 0);
+    send_broadcast_message(&node_1, vec![], NetworkId::from(NID), Arc::from(payload));
+
+    let deadline = Instant::now() + FAULT_TIMEOUT;
+    while node_2.stats.get_compression_bytes_saved_received() == 0 {
+        ensure!(Instant::now() < deadline, "The compressed packet wasn't received.");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        node_1.stats.get_compression_bytes_saved_sent(),
+        node_2.stats.get_compression_bytes_saved_received()
+    );
+
+    stop_node_delete_dirs(dp_1, node_1);
+    stop_node_delete_dirs(dp_2, node_2);
+    Ok(())
+}
+
+#[test]
+fn partition_and_heal() -> anyhow::Result<()> {
+    let mut network = TestNetwork::new(6, NID)?;
+    network.connect_mesh();
+    network.await_connectivity(FAULT_TIMEOUT)?;
+
+    network.partition(&[&[0, 1, 2], &[3, 4, 5]]);
+    network.await_connectivity(FAULT_TIMEOUT)?;
+    let payload = network.broadcast(0, 100)?;
+    network.await_delivery(0, &payload, FAULT_TIMEOUT)?;
+    network.check_dedup(0, &payload)?;
+
+    network.heal();
+    network.await_connectivity(FAULT_TIMEOUT)?;
+    let payload = network.broadcast(5, 100)?;
+    network.await_delivery(5, &payload, FAULT_TIMEOUT)?;
+    network.check_dedup(5, &payload)?;
+
+    network.shutdown()
+}
+
+#[test]
+fn broadcast_over_lossy_links() -> anyhow::Result<()> {
+    let mut network = TestNetwork::new(NODE_COUNT, NID)?;
+    network.connect_mesh();
+    network.await_connectivity(FAULT_TIMEOUT)?;
+
+    // Every node relays the broadcast to all its peers, so in a densely
+    // connected network it gets through even if a third of the packets is lost.
+    network.set_default_faults(LinkFaults {
+        latency:          Duration::from_millis(20),
+        jitter:           Duration::from_millis(30),
+        drop_probability: 0.3,
+    });
+    let payload = network.broadcast(0, 1000)?;
+    network.await_delivery(0, &payload, FAULT_TIMEOUT)?;
+    assert!(network.check_dedup(0, &payload)? > 0, "Relayed duplicates were not filtered.");
+
+    network.shutdown()
+}
+
+#[test]
+fn pull_based_transaction_relay() -> anyhow::Result<()> {
+    let mut network = TestNetwork::new(6, NID)?;
+    network.connect_mesh();
+    network.await_connectivity(FAULT_TIMEOUT)?;
+
+    let payload = network.broadcast_packet(0, PacketType::Transaction, 200)?;
+    network.await_delivery(0, &payload, FAULT_TIMEOUT)?;
+    // every node fetches the transaction exactly once, even though all its
+    // peers announce it
+    assert_eq!(network.check_dedup(0, &payload)?, 0);
+
+    let (mut announced, mut requested, mut served) = (0, 0, 0);
+    for idx in 0..network.len() {
+        let stats = &network.node(idx).expect("No node was killed.").stats;
+        announced += stats.get_tx_announcements_sent();
+        requested += stats.get_tx_requests_sent();
+        served += stats.get_tx_requests_served();
+    }
+    assert!(announced >= requested);
+    assert_eq!(requested, network.len() as u64 - 1);
+    assert_eq!(served, requested);
+
+    network.shutdown()
+}
+
+#[test]
+fn kill_and_restart() -> anyhow::Result<()> {
+    let mut network = TestNetwork::new(4, NID)?;
+    network.connect_ring();
+    network.await_connectivity(FAULT_TIMEOUT)?;
+
+    // a ring stays connected without one of its nodes
+    network.kill(1)?;
+    network.await_connectivity(FAULT_TIMEOUT)?;
+    let payload = network.broadcast(0, 100)?;
+    network.await_delivery(0, &payload, FAULT_TIMEOUT)?;
+
+    network.restart(1)?;
+    network.await_connectivity(FAULT_TIMEOUT)?;
+    let payload = network.broadcast(1, 100)?;
+    network.await_delivery(1, &payload, FAULT_TIMEOUT)?;
+    network.check_dedup(1, &payload)?;
+
+    network.shutdown()
+}
+
+#[test]
+fn noise_framing_fuzz_regressions() -> anyhow::Result<()> {
+    let (node, dp) = make_node_and_sync(next_available_port(), vec![NID], PeerType::Node, vec![])?;
+
+    // The first byte of each input selects the role of the local end.
+    for (name, bytes) in fuzz_regressions("noise_framing") {
+        if let Some((&role, input)) = bytes.split_first() {
+            assert!(
+                read_untrusted_input(&node, role & 1 == 1, input).is_err(),
+                "{} was accepted",
+                name
+            );
+        }
+    }
+
+    stop_node_delete_dirs(dp, node);
+    Ok(())
+}
@@ -8,7 +8,7 @@ use std::{
     convert::TryFrom,
     path::Path,
     sync::{
-        atomic::{AtomicBool, AtomicPtr, Ordering},
+        atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering},
         Arc, Mutex, RwLock,
     },
 };
@@ -42,12 +42,17 @@ pub const CONSENSUS_QUEUE_DEPTH_OUT_HI: usize = 8 * 1024;
 pub const CONSENSUS_QUEUE_DEPTH_OUT_LO: usize = 16 * 1024;
 pub const CONSENSUS_QUEUE_DEPTH_IN_HI: usize = 16 * 1024;
 pub const CONSENSUS_QUEUE_DEPTH_IN_LO: usize = 32 * 1024;
+pub const CONSENSUS_QUEUE_DEPTH_IN_BAKER: usize = 4 * 1024;
 
 pub struct ConsensusInboundQueues {
-    pub receiver_high_priority: Mutex<QueueReceiver<ConsensusMessage>>,
-    pub sender_high_priority:   QueueSyncSender<ConsensusMessage>,
-    pub receiver_low_priority:  Mutex<QueueReceiver<ConsensusMessage>>,
-    pub sender_low_priority:    QueueSyncSender<ConsensusMessage>,
+    pub receiver_high_priority:  Mutex<QueueReceiver<ConsensusMessage>>,
+    pub sender_high_priority:    QueueSyncSender<ConsensusMessage>,
+    pub receiver_low_priority:   Mutex<QueueReceiver<ConsensusMessage>>,
+    pub sender_low_priority:     QueueSyncSender<ConsensusMessage>,
+    /// Non-transaction messages from verified baker peers, which are processed
+    /// before the high priority queue.
+    pub receiver_baker_priority: Mutex<QueueReceiver<ConsensusMessage>>,
+    pub sender_baker_priority:   QueueSyncSender<ConsensusMessage>,
 }
 
 impl Default for ConsensusInboundQueues {
@@ -56,11 +61,15 @@ impl Default for ConsensusInboundQueues {
             crossbeam_channel::bounded(CONSENSUS_QUEUE_DEPTH_IN_HI);
         let (sender_low_priority, receiver_low_priority) =
             crossbeam_channel::bounded(CONSENSUS_QUEUE_DEPTH_IN_LO);
+        let (sender_baker_priority, receiver_baker_priority) =
+            crossbeam_channel::bounded(CONSENSUS_QUEUE_DEPTH_IN_BAKER);
         Self {
             receiver_high_priority: Mutex::new(receiver_high_priority),
             sender_high_priority,
             receiver_low_priority: Mutex::new(receiver_low_priority),
             sender_low_priority,
+            receiver_baker_priority: Mutex::new(receiver_baker_priority),
+            sender_baker_priority,
         }
     }
 }
@@ -98,6 +107,10 @@ impl ConsensusQueues {
         self.inbound.sender_high_priority.send_msg(message).map_err(|e| e.into())
     }
 
+    pub fn send_in_baker_priority_message(&self, message: ConsensusMessage) -> anyhow::Result<()> {
+        self.inbound.sender_baker_priority.send_msg(message).map_err(|e| e.into())
+    }
+
     pub fn send_in_low_priority_message(&self, message: ConsensusMessage) -> anyhow::Result<()> {
         self.inbound.sender_low_priority.send_msg(message).map_err(|e| e.into())
     }
@@ -106,6 +119,12 @@ impl ConsensusQueues {
         self.outbound.sender_low_priority.send_msg(message).map_err(|e| e.into())
     }
 
+    /// Whether the low priority outbound queue, which carries transactions, is
+    /// full.
+    pub fn is_outbound_low_priority_full(&self) -> bool {
+        self.outbound.sender_low_priority.is_full()
+    }
+
     pub fn send_out_blocking_msg(&self, message: ConsensusMessage) -> anyhow::Result<()> {
         self.outbound.sender_high_priority.send_blocking_msg(message).map_err(|e| e.into())
     }
@@ -135,6 +154,12 @@ impl ConsensusQueues {
                 q.try_iter().count()
             );
         }
+        if let Ok(ref mut q) = self.inbound.receiver_baker_priority.try_lock() {
+            debug!(
+                "Drained the Consensus inbound baker priority queue for {} element(s)",
+                q.try_iter().count()
+            );
+        }
     }
 
     pub fn stop(&self) -> anyhow::Result<()> {
@@ -150,6 +175,12 @@ lazy_static! {
     pub static ref CALLBACK_QUEUE: ConsensusQueues = ConsensusQueues::default();
 }
 
+/// The number of blocks baked by the node since startup.
+pub static BLOCKS_BAKED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of finalization messages sent by the node since startup.
+pub static FINALIZATION_MESSAGES_SENT: AtomicU64 = AtomicU64::new(0);
+
 /// If a consensus instance is
 /// - `Active` it is either a baker or a member of the finalization committee
 /// - `Passive` it is neither a baker nor a member of the finalization committee
@@ -189,11 +220,14 @@ pub struct ConsensusRuntimeParameters {
 
 #[derive(Clone)]
 pub struct ConsensusContainer {
-    pub runtime_parameters: ConsensusRuntimeParameters,
-    pub is_baking:          Arc<AtomicBool>,
-    pub consensus:          Arc<AtomicPtr<consensus_runner>>,
-    pub genesis:            Arc<[u8]>,
-    pub consensus_type:     ConsensusType,
+    pub runtime_parameters:        ConsensusRuntimeParameters,
+    pub is_baking:                 Arc<AtomicBool>,
+    pub consensus:                 Arc<AtomicPtr<consensus_runner>>,
+    pub genesis:                   Arc<[u8]>,
+    pub consensus_type:            ConsensusType,
+    /// A pending switch to other baker credentials, see
+    /// [schedule_baker_credentials](crate::plugins::consensus::schedule_baker_credentials).
+    pub pending_baker_credentials: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
 }
 
 impl ConsensusContainer {
@@ -229,12 +263,16 @@ impl ConsensusContainer {
                 consensus: Arc::new(AtomicPtr::new(consensus_ptr)),
                 genesis: Arc::from(genesis_data),
                 consensus_type,
+                pending_baker_credentials: Default::default(),
             }),
             Err(e) => Err(e),
         }
     }
 
     pub fn stop(&self) {
+        if let Some(pending) = crate::lock_or_die!(self.pending_baker_credentials).take() {
+            pending.abort();
+        }
         self.stop_baker();
         let consensus = self.consensus.load(Ordering::SeqCst);
         unsafe {
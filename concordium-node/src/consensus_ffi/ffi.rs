@@ -502,8 +502,19 @@ extern "C" {
         baker_id: *mut u64,
         has_baker_id: *mut u8,
     ) -> u8;
+    pub fn currentBakerSignatureKey(
+        consensus: *mut consensus_runner,
+        baker_id: u64,
+        out_key: *mut u8,
+    ) -> u8;
     pub fn checkIfWeAreFinalizer(consensus: *mut consensus_runner) -> u8;
     pub fn checkIfRunning(consensus: *mut consensus_runner) -> u8;
+    pub fn setBakerIdentity(
+        consensus: *mut consensus_runner,
+        private_data: *const u8,
+        private_data_len: i64,
+    ) -> i64;
+    pub fn getBestBlockEpoch(consensus: *mut consensus_runner, genesis_index: *mut u32) -> u64;
     pub fn getAccountNonFinalizedTransactions(
         consensus: *mut consensus_runner,
         account_address: *const c_char,
@@ -855,6 +866,35 @@ extern "C" {
         copier: CopyToVecCallback,
     ) -> i64;
 
+    /// Execute an account transaction in the state at the end of the given
+    /// block, without adding it to the transaction table or sending it to
+    /// peers.
+    ///
+    /// * `consensus` - Pointer to the current consensus.
+    /// * `block_id_type` - Type of block identifier.
+    /// * `block_id` - Location with the block identifier. Length must match the
+    ///   corresponding type of block identifier.
+    /// * `transaction_ptr` - Pointer to the serialized account transaction.
+    /// * `transaction_len` - Length of the serialized account transaction.
+    /// * `out_hash` - Location to write the block hash used in the query.
+    /// * `out_valid` - Location to write whether the transaction is valid. If
+    ///   it is (1), the output is the protobuf encoded summary of the
+    ///   transaction, otherwise (0) it is the UTF-8 encoded reason why it would
+    ///   not be included in a block.
+    /// * `out` - Location to write the output of the query.
+    /// * `copier` - Callback for writting the output.
+    pub fn dryRunTransactionV2(
+        consensus: *mut consensus_runner,
+        block_id_type: u8,
+        block_id: *const u8,
+        transaction_ptr: *const u8,
+        transaction_len: u32,
+        out_hash: *mut u8,
+        out_valid: *mut u8,
+        out: *mut Vec<u8>,
+        copier: CopyToVecCallback,
+    ) -> i64;
+
     /// Get information, such as height, timings, and transaction counts for the
     /// given block.
     ///
@@ -1745,6 +1785,47 @@ impl ConsensusContainer {
         (status, has_baker_id != 0, baker_id)
     }
 
+    /// Look up the signature verification key of the given baker in the
+    /// current epoch of the best block. Returns `None` if the baker is not
+    /// in the current baking committee.
+    pub fn current_baker_signature_key(&self, baker_id: u64) -> Option<[u8; 32]> {
+        let consensus = self.consensus.load(Ordering::SeqCst);
+        let mut key = [0u8; 32];
+        let found = unsafe { currentBakerSignatureKey(consensus, baker_id, key.as_mut_ptr()) };
+        if found != 0 {
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the baker credentials used by consensus with the given
+    /// (decrypted) ones. They are used for the next block that is baked, for
+    /// finalization rounds that start afterwards, and by
+    /// [in_baking_committee](Self::in_baking_committee).
+    pub fn set_baker_credentials(&self, private_data: &[u8]) -> anyhow::Result<()> {
+        let consensus = self.consensus.load(Ordering::SeqCst);
+        let result = unsafe {
+            setBakerIdentity(consensus, private_data.as_ptr(), private_data.len() as i64)
+        };
+        match result {
+            0 => Ok(()),
+            1 => bail!("The node was started without baker credentials."),
+            2 => bail!("Consensus could not decode the baker credentials."),
+            n => bail!("Unexpected response {} when replacing the baker credentials.", n),
+        }
+    }
+
+    /// The genesis index and the epoch of the best block. Epochs restart at
+    /// every protocol update, so they are only comparable within a genesis
+    /// index.
+    pub fn best_block_epoch(&self) -> (u32, u64) {
+        let consensus = self.consensus.load(Ordering::SeqCst);
+        let mut genesis_index = 0;
+        let epoch = unsafe { getBestBlockEpoch(consensus, &mut genesis_index) };
+        (genesis_index, epoch)
+    }
+
     pub fn in_finalization_committee(&self) -> bool {
         wrap_c_bool_call!(self, |consensus| checkIfWeAreFinalizer(consensus))
     }
@@ -2293,6 +2374,64 @@ impl ConsensusContainer {
         Ok((out_hash, out_data))
     }
 
+    /// Execute an account transaction in the state at the end of the given
+    /// block and report its outcome as an encoded `DryRunTransactionResponse`.
+    /// The transaction is not added to the transaction table and is not sent
+    /// to peers.
+    pub fn dry_run_transaction_v2(
+        &self,
+        request: crate::grpc2::types::DryRunTransactionRequest,
+    ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
+        use crate::grpc2::{types::dry_run_transaction_response::Result as DryRunResult, Require};
+        use prost::Message;
+        let consensus = self.consensus.load(Ordering::SeqCst);
+        let mut out_data: Vec<u8> = Vec::new();
+        let mut out_hash = [0u8; 32];
+        let mut out_valid = 0u8;
+        let (block_id_type, block_id) =
+            crate::grpc2::types::block_hash_input_to_ffi(request.block_hash.as_ref().require()?)
+                .require()?;
+        let transaction: concordium_base::transactions::AccountTransaction<
+            concordium_base::transactions::EncodedPayload,
+        > = request.transaction.require()?.try_into()?;
+        let transaction = concordium_base::common::to_bytes(&transaction);
+        if transaction.len() > crate::configuration::PROTOCOL_MAX_TRANSACTION_SIZE {
+            return Err(tonic::Status::invalid_argument(
+                "Transaction size exceeds maximum allowed size.",
+            ));
+        }
+        let response: ConsensusQueryResponse = unsafe {
+            dryRunTransactionV2(
+                consensus,
+                block_id_type,
+                block_id,
+                transaction.as_ptr(),
+                transaction.len() as u32, // safe since the size is checked above
+                out_hash.as_mut_ptr(),
+                &mut out_valid,
+                &mut out_data,
+                copy_to_vec_callback,
+            )
+        }
+        .try_into()?;
+        response.ensure_ok("block")?;
+        let result = if out_valid == 1 {
+            DryRunResult::Summary(
+                crate::grpc2::types::BlockItemSummary::decode(&out_data[..]).map_err(|e| {
+                    tonic::Status::internal(format!("Invalid transaction summary: {}", e))
+                })?,
+            )
+        } else {
+            DryRunResult::InvalidReason(String::from_utf8(out_data).map_err(|e| {
+                tonic::Status::internal(format!("Invalid dry-run rejection reason: {}", e))
+            })?)
+        };
+        let response = crate::grpc2::types::DryRunTransactionResponse {
+            result: Some(result),
+        };
+        Ok((out_hash, response.encode_to_vec()))
+    }
+
     /// Get information, such as height, timings, and transaction counts for the
     /// given block.
     pub fn get_block_info_v2(
@@ -3007,6 +3146,17 @@ pub extern "C" fn broadcast_callback(
     msg_length: i64,
 ) {
     trace!("Broadcast callback hit - queueing message");
+    // Consensus only broadcasts the blocks and finalization messages of this
+    // node.
+    match CallbackType::try_from(msg_type as u8) {
+        Ok(CallbackType::Block) => {
+            BLOCKS_BAKED.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(CallbackType::FinalizationMessage) => {
+            FINALIZATION_MESSAGES_SENT.fetch_add(1, Ordering::Relaxed);
+        }
+        _ => {}
+    }
     sending_callback!(None, msg_type, genesis_index, msg, msg_length, None);
 }
 
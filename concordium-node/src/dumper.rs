@@ -2,41 +2,72 @@
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "network_dump")] {
-        use crate::common::P2PNodeId;
-        use crossbeam_channel::{self, Receiver};
-        use std::io::Write;
+        use crate::{capture::CaptureWriter, configuration::DUMP_POLL_INTERVAL};
+        use crossbeam_channel::{self, Receiver, RecvTimeoutError};
+        use std::{
+            io::Write,
+            sync::atomic::{AtomicBool, Ordering},
+            time::Instant,
+        };
     }
 }
-use crate::{network::NetworkMessage, spawn_or_die};
+use crate::{
+    capture::CaptureRecord,
+    common::P2PNodeId,
+    consensus_ffi::helpers::PacketType,
+    network::{
+        compression::{decompress, is_compressed},
+        NetworkId, NetworkMessage, NetworkPayload,
+    },
+    spawn_or_die,
+};
 use chrono::prelude::{DateTime, Utc};
 
-use std::{fmt, net::IpAddr, sync::Arc};
+use std::{convert::TryFrom, fmt, net::IpAddr, sync::Arc, time::Duration};
 
 /// A structure containing network data to be dumped to the disk.
 pub struct DumpItem {
     timestamp:   DateTime<Utc>,
     inbound:     bool,
     remote_addr: IpAddr,
+    peer_id:     Option<P2PNodeId>,
     msg:         Arc<[u8]>,
 }
 
 impl DumpItem {
     /// Creates a new dump item object.
-    pub fn new(inbound: bool, remote_addr: IpAddr, msg: Arc<[u8]>) -> Self {
+    pub fn new(
+        inbound: bool,
+        remote_addr: IpAddr,
+        peer_id: Option<P2PNodeId>,
+        msg: Arc<[u8]>,
+    ) -> Self {
         DumpItem {
             timestamp: Utc::now(),
             inbound,
             remote_addr,
+            peer_id,
             msg,
         }
     }
+
+    /// Convert the item to a record of the capture format.
+    pub fn to_capture_record(&self) -> CaptureRecord {
+        CaptureRecord {
+            timestamp:   self.timestamp,
+            inbound:     self.inbound,
+            remote_addr: self.remote_addr,
+            peer_id:     self.peer_id,
+            message:     self.msg.to_vec(),
+        }
+    }
 }
 
 impl fmt::Display for DumpItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{} - {} - {} - {:?} - {:?}",
+            "{} - {} - {} - {} - {:?} - {:?}",
             self.timestamp,
             if self.inbound {
                 "IN"
@@ -44,6 +75,7 @@ impl fmt::Display for DumpItem {
                 "OUT"
             },
             self.remote_addr,
+            self.peer_id.map_or_else(|| "unknown".to_string(), |id| id.to_string()),
             self.msg,
             NetworkMessage::deserialize(&self.msg)
                 .map(|m| format!("{:?}", m))
@@ -52,92 +84,334 @@ impl fmt::Display for DumpItem {
     }
 }
 
+/// The direction of the messages to dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpDirection {
+    Inbound,
+    Outbound,
+}
+
+/// Restricts the network dump to the matching messages, and stops it once
+/// either of the limits is reached. Empty lists impose no restrictions.
+#[derive(Debug, Clone, Default)]
+pub struct DumpFilter {
+    /// Only dump messages exchanged with these peers.
+    pub peer_ids:     Vec<P2PNodeId>,
+    /// Only dump messages exchanged with these addresses.
+    pub ips:          Vec<IpAddr>,
+    /// Only dump messages in this direction; both if absent.
+    pub direction:    Option<DumpDirection>,
+    /// Only dump packets of these types.
+    pub packet_types: Vec<PacketType>,
+    /// Only dump packets in these networks.
+    pub network_ids:  Vec<NetworkId>,
+    /// Stop the dump after this long.
+    pub max_duration: Option<Duration>,
+    /// Stop the dump once this many bytes of messages were dumped.
+    pub max_bytes:    Option<u64>,
+}
+
+impl DumpFilter {
+    /// Check whether an item should be dumped. The packet type and network
+    /// filters require deserializing the message, so messages that are not
+    /// (valid) packets never match them.
+    pub fn matches(&self, item: &DumpItem) -> bool {
+        if !self.peer_ids.is_empty()
+            && !item.peer_id.map_or(false, |id| self.peer_ids.contains(&id))
+        {
+            return false;
+        }
+        if !self.ips.is_empty() && !self.ips.contains(&item.remote_addr) {
+            return false;
+        }
+        match self.direction {
+            Some(DumpDirection::Inbound) if !item.inbound => return false,
+            Some(DumpDirection::Outbound) if item.inbound => return false,
+            _ => {}
+        }
+        if self.packet_types.is_empty() && self.network_ids.is_empty() {
+            return true;
+        }
+        let message = if is_compressed(&item.msg) {
+            match decompress(&item.msg) {
+                Ok((_, message)) => NetworkMessage::deserialize(&message),
+                Err(_) => return false,
+            }
+        } else {
+            NetworkMessage::deserialize(&item.msg)
+        };
+        let packet = match message {
+            Ok(NetworkMessage {
+                payload: NetworkPayload::NetworkPacket(packet),
+                ..
+            }) => packet,
+            _ => return false,
+        };
+        if !self.network_ids.is_empty() && !self.network_ids.contains(&packet.network_id) {
+            return false;
+        }
+        self.packet_types.is_empty()
+            || packet
+                .message
+                .first()
+                .and_then(|&tag| PacketType::try_from(tag).ok())
+                .map_or(false, |packet_type| self.packet_types.contains(&packet_type))
+    }
+}
+
+/// The state of a running network dump.
+#[cfg(feature = "network_dump")]
+struct ActiveDump {
+    dir:         std::path::PathBuf,
+    pretty_dump: std::fs::File,
+    capture:     Option<CaptureWriter>,
+    filter:      DumpFilter,
+    started:     Instant,
+    /// The number of bytes of messages dumped so far.
+    bytes:       u64,
+}
+
+#[cfg(feature = "network_dump")]
+impl ActiveDump {
+    fn start(
+        dir: std::path::PathBuf,
+        raw: bool,
+        filter: DumpFilter,
+        ip: IpAddr,
+        id: P2PNodeId,
+        max_capture_file_size: u64,
+    ) -> anyhow::Result<Self> {
+        // Create directory
+        let _ = std::fs::create_dir(&dir);
+
+        // Create and start pretty dump file
+        let mut pretty_dump =
+            std::fs::File::create(dir.join(std::path::Path::new(&format!("{}-pretty.log", id))))?;
+        pretty_dump.write_fmt(format_args!(
+            "Dumping started at: {}\nLocal IP is: {}\nLocal ID is: {}\nFilter is: {:?}\n\n",
+            Utc::now(),
+            ip,
+            id,
+            filter
+        ))?;
+
+        // Start the capture
+        let capture = if raw {
+            Some(CaptureWriter::new(&dir, &id.to_string(), id, ip, max_capture_file_size)?)
+        } else {
+            None
+        };
+
+        Ok(ActiveDump {
+            dir,
+            pretty_dump,
+            capture,
+            filter,
+            started: Instant::now(),
+            bytes: 0,
+        })
+    }
+
+    fn dump(&mut self, item: &DumpItem) -> anyhow::Result<()> {
+        if !self.filter.matches(item) {
+            return Ok(());
+        }
+        // Raw dump
+        if let Some(ref mut writer) = self.capture {
+            writer.write(&item.to_capture_record())?;
+        }
+        // Pretty dump
+        self.pretty_dump.write_fmt(format_args!("{}\n\n", item))?;
+        self.bytes += item.msg.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(ref mut writer) = self.capture {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Describe the limit that was reached, if any.
+    fn reached_limit(&self) -> Option<String> {
+        if let Some(max_duration) = self.filter.max_duration {
+            if self.started.elapsed() >= max_duration {
+                return Some(format!("the duration limit of {}s", max_duration.as_secs()));
+            }
+        }
+        if let Some(max_bytes) = self.filter.max_bytes {
+            if self.bytes >= max_bytes {
+                return Some(format!("the limit of {}B", max_bytes));
+            }
+        }
+        None
+    }
+}
+
 /// Creates the thread responsible for intercepting and dumping network data.
+/// The messages are always logged in a human-readable form; if the raw dump is
+/// requested, they are also written to a capture, whose files are rotated once
+/// they reach `max_capture_file_size` bytes.
+///
+/// A dump is started by sending a path, the raw flag and a filter over
+/// `act_rx`, and stopped by sending an empty path. The `active` flag, which
+/// tells the connections whether to send items, is set once a dump has
+/// started. When the dump reaches one of the limits of its filter, it stops on
+/// its own and clears the flag.
 #[cfg(feature = "network_dump")]
 pub fn create_dump_thread(
     ip: IpAddr,
     id: P2PNodeId,
     rx: Receiver<DumpItem>,
-    act_rx: Receiver<(std::path::PathBuf, bool)>,
+    act_rx: Receiver<(std::path::PathBuf, bool, DumpFilter)>,
+    active: Arc<AtomicBool>,
     base_dir: std::path::PathBuf,
+    max_capture_file_size: u64,
 ) {
     spawn_or_die!("network dump", move || -> anyhow::Result<()> {
-        let mut dir: Option<std::path::PathBuf> = None;
-        let mut pretty_dump: Option<std::fs::File> = None;
-        let mut raw_dump: Option<std::fs::File> = None;
-        let mut count = 0;
+        let mut dump: Option<ActiveDump> = None;
         loop {
-            if let Ok((new_path, raw)) = act_rx.try_recv() {
+            // wait for a command when idle, otherwise only check for one
+            let command = if dump.is_some() {
+                act_rx.try_recv().ok()
+            } else {
+                match act_rx.recv() {
+                    Ok(command) => Some(command),
+                    // the node is shutting down
+                    Err(_) => break,
+                }
+            };
+            if let Some((new_path, raw, filter)) = command {
+                if let Some(mut old) = dump.take() {
+                    old.flush()?;
+                }
+                // discard the items queued since the previous dump
+                while rx.try_recv().is_ok() {}
                 if new_path.components().next().is_none() {
                     info!("Dump process stopped");
-                    break;
+                    continue;
                 }
                 let new_path = base_dir.join(&new_path);
-                // Create directory
-                let _ = std::fs::create_dir(&new_path.clone());
-
-                // Create and start pretty dump file
-                let mut pretty_dump_file = std::fs::File::create(
-                    base_dir
-                        .join(new_path.join(std::path::Path::new(&format!("{}-pretty.log", id)))),
-                )
-                .map_err(|e| {
-                    error!("Aborting dump due to error: {}", e);
-                    e
-                })?;
-                pretty_dump_file
-                    .write_fmt(format_args!(
-                        "Dumping started at: {}\nLocal IP is: {}\nLocal ID is: {}\n\n",
-                        Utc::now(),
-                        ip,
-                        id
-                    ))
-                    .map_err(|e| {
-                        error!("Aborting dump due to error: {}", e);
-                        e
-                    })?;
-                pretty_dump.replace(pretty_dump_file);
-
-                // Activate raw dump
-                if raw {
-                    count = 1;
-                };
-                info!("Starting dump in: {:?}", &new_path);
-                dir = Some(new_path);
-            };
-            if let Some(ref dir) = dir {
-                let msg = rx.recv()?;
-                // Raw dump
-                if count > 0 {
-                    // Create file
-                    let file = std::fs::File::create(
-                        dir.join(std::path::Path::new(&format!("{}-{}", id, count))),
-                    )
-                    .map_err(|e| {
+                match ActiveDump::start(new_path, raw, filter, ip, id, max_capture_file_size) {
+                    Ok(new_dump) => {
+                        info!("Starting dump in: {:?}", &new_dump.dir);
+                        dump = Some(new_dump);
+                        active.store(true, Ordering::Relaxed);
+                    }
+                    Err(e) => {
                         error!("Aborting dump due to error: {}", e);
-                        e
-                    })?;
-                    raw_dump.replace(file);
-                    // Write message
-                    if let Some(ref mut rd) = raw_dump {
-                        let _ = rd.write(&msg.msg).map_err(|e| {
-                            error!("Aborting dump due to error: {}", e);
-                            e
-                        })?;
-                        count += 1;
+                        active.store(false, Ordering::Relaxed);
                     }
+                }
+            }
+            let stopped = if let Some(ref mut current) = dump {
+                let result = match rx.recv_timeout(DUMP_POLL_INTERVAL) {
+                    Ok(item) => current.dump(&item),
+                    Err(RecvTimeoutError::Timeout) => Ok(()),
+                    Err(RecvTimeoutError::Disconnected) => break,
                 };
-
-                // Pretty dump
-                if let Some(ref mut pd) = pretty_dump {
-                    pd.write_fmt(format_args!("{}\n\n", msg)).map_err(|e| {
+                // flush whenever the queue is drained, so that the capture
+                // doesn't lag behind in quiet periods
+                let result = result.and_then(|_| {
+                    if rx.is_empty() {
+                        current.flush()
+                    } else {
+                        Ok(())
+                    }
+                });
+                match result {
+                    Ok(()) => match current.reached_limit() {
+                        Some(limit) => {
+                            info!("Dump in {:?} stopped after reaching {}", current.dir, limit);
+                            true
+                        }
+                        None => false,
+                    },
+                    Err(e) => {
                         error!("Aborting dump due to error: {}", e);
-                        e
-                    })?;
-                };
+                        true
+                    }
+                }
+            } else {
+                false
             };
-            std::thread::yield_now();
+            if stopped {
+                active.store(false, Ordering::Relaxed);
+                dump = None;
+            }
         }
         Ok(())
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::get_current_stamp,
+        network::{NetworkPacket, PacketDestination},
+    };
+    use std::net::Ipv4Addr;
+
+    fn item(inbound: bool, peer: u64, packet_type: PacketType) -> DumpItem {
+        let packet = NetworkMessage {
+            created:  get_current_stamp(),
+            received: None,
+            payload:  NetworkPayload::NetworkPacket(NetworkPacket {
+                destination: PacketDestination::Broadcast(vec![]),
+                network_id:  NetworkId::from(100),
+                message:     vec![packet_type as u8, 1, 2, 3],
+            }),
+        };
+        let mut msg = Vec::new();
+        packet.serialize(&mut msg).unwrap();
+        DumpItem::new(
+            inbound,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, peer as u8)),
+            Some(P2PNodeId(peer)),
+            Arc::from(msg),
+        )
+    }
+
+    #[test]
+    fn dump_filters() {
+        let block = item(true, 1, PacketType::Block);
+        let transaction = item(false, 2, PacketType::Transaction);
+        assert!(DumpFilter::default().matches(&block));
+
+        let by_peer = DumpFilter {
+            peer_ids: vec![P2PNodeId(2)],
+            ..Default::default()
+        };
+        assert!(!by_peer.matches(&block));
+        assert!(by_peer.matches(&transaction));
+
+        let by_ip = DumpFilter {
+            ips: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))],
+            ..Default::default()
+        };
+        assert!(by_ip.matches(&block));
+        assert!(!by_ip.matches(&transaction));
+
+        let inbound = DumpFilter {
+            direction: Some(DumpDirection::Inbound),
+            ..Default::default()
+        };
+        assert!(inbound.matches(&block));
+        assert!(!inbound.matches(&transaction));
+
+        let blocks = DumpFilter {
+            packet_types: vec![PacketType::Block],
+            ..Default::default()
+        };
+        assert!(blocks.matches(&block));
+        assert!(!blocks.matches(&transaction));
+
+        let other_network = DumpFilter {
+            network_ids: vec![NetworkId::from(101)],
+            ..Default::default()
+        };
+        assert!(!other_network.matches(&block));
+    }
+}
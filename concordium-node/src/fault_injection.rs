@@ -0,0 +1,662 @@
+//! A harness for multi-node network scenarios with fault injection.
+//!
+//! [`TestNetwork`] starts a number of in-process [`P2PNode`]s that share a
+//! single [`FaultInjector`]. The injector is consulted by the connection layer
+//! for every message, which allows the harness to partition the nodes into
+//! groups, delay messages on a link, drop a fraction of the packets on a link,
+//! and kill and restart individual nodes. Since there is no consensus layer
+//! in the harness, the harness itself relays broadcast packets the same way
+//! consensus would, i.e., every node rebroadcasts each packet it has not
+//! seen before to all its peers except the one it received it from.
+//!
+//! On top of that the harness exposes invariant checks: eventual connectivity
+//! within each partition, delivery of broadcasts to every reachable node and
+//! the effectiveness of deduplication.
+
+use crossbeam_channel::{Receiver, Sender};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    common::{p2p_peer::RemotePeerId, P2PNodeId, PeerType},
+    connection::ConnChange,
+    consensus_ffi::{consensus::Regenesis, helpers::PacketType},
+    lock_or_die,
+    network::{
+        NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest, PacketDestination,
+    },
+    p2p::{connectivity::send_broadcast_message, maintenance::spawn, P2PNode},
+    read_or_die, spawn_or_die,
+    stats_export_service::StatsExportService,
+    test_utils::{
+        dummy_regenesis_blocks, generate_random_data, get_test_config, next_available_port,
+    },
+    write_or_die,
+};
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hasher,
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// The faults applied to messages travelling over a single directed link.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkFaults {
+    /// The fixed delay applied to every message sent over the link.
+    pub latency:          Duration,
+    /// The upper bound of the random delay added on top of `latency`.
+    pub jitter:           Duration,
+    /// The probability with which a network packet sent over the link is
+    /// dropped. Only packets are affected; requests and responses, e.g.,
+    /// handshakes and pings, always get through.
+    pub drop_probability: f64,
+}
+
+impl LinkFaults {
+    fn is_delaying(&self) -> bool { self.latency > Duration::ZERO || self.jitter > Duration::ZERO }
+}
+
+/// How many times a single payload was delivered to a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryCount {
+    /// Deliveries that were passed on to the upper layers.
+    pub unique:    u64,
+    /// Deliveries that were filtered out by deduplication.
+    pub duplicate: u64,
+}
+
+/// A packet passed on to the upper layers by one of the nodes.
+struct Delivery {
+    receiver:   P2PNodeId,
+    source:     RemotePeerId,
+    network_id: NetworkId,
+    payload:    Arc<[u8]>,
+}
+
+/// The fault configuration shared by all the nodes of a [`TestNetwork`].
+/// It is installed in the node's connection handler, and consulted by each
+/// connection when messages are received and sent.
+pub struct FaultInjector {
+    /// Partition group of each node. Nodes in different groups can't talk to
+    /// each other. Nodes that are not assigned a group can talk to everyone.
+    groups:     RwLock<HashMap<P2PNodeId, usize>>,
+    /// Faults of individual directed links, indexed by (sender, receiver).
+    links:      RwLock<HashMap<(P2PNodeId, P2PNodeId), LinkFaults>>,
+    /// Faults of links that don't have specific faults set.
+    default:    RwLock<LinkFaults>,
+    /// Delivery counts indexed by receiver and payload hash.
+    deliveries: Mutex<HashMap<(P2PNodeId, u64), DeliveryCount>>,
+    /// Number of packets dropped because of the configured link faults.
+    dropped:    Mutex<u64>,
+    relay:      Sender<Delivery>,
+}
+
+impl FaultInjector {
+    fn new(relay: Sender<Delivery>) -> Self {
+        Self {
+            groups: Default::default(),
+            links: Default::default(),
+            default: Default::default(),
+            deliveries: Default::default(),
+            dropped: Default::default(),
+            relay,
+        }
+    }
+
+    fn link_faults(&self, from: P2PNodeId, to: P2PNodeId) -> LinkFaults {
+        read_or_die!(self.links)
+            .get(&(from, to))
+            .copied()
+            .unwrap_or_else(|| *read_or_die!(self.default))
+    }
+
+    fn is_partitioned(&self, a: P2PNodeId, b: P2PNodeId) -> bool {
+        let groups = read_or_die!(self.groups);
+        match (groups.get(&a), groups.get(&b)) {
+            (Some(group_a), Some(group_b)) => group_a != group_b,
+            _ => false,
+        }
+    }
+
+    /// Decide whether a message received by `local` should be processed.
+    /// Returns `Ok(false)` if the message is to be dropped, and an I/O error
+    /// if the connection is to be closed because the sender is in another
+    /// partition. The remote id is taken from the handshake if the
+    /// connection has not been promoted yet.
+    pub fn admit_inbound(
+        &self,
+        local: P2PNodeId,
+        remote: Option<P2PNodeId>,
+        message: &NetworkMessage,
+    ) -> anyhow::Result<bool> {
+        let remote = match (remote, &message.payload) {
+            (Some(id), _) => id,
+            (None, NetworkPayload::NetworkRequest(NetworkRequest::Handshake(handshake), ..)) => {
+                handshake.remote_id
+            }
+            (None, _) => return Ok(true),
+        };
+
+        if self.is_partitioned(remote, local) {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                format!("peer {} is in another partition", remote),
+            )
+            .into());
+        }
+
+        if let NetworkPayload::NetworkPacket(..) = message.payload {
+            let faults = self.link_faults(remote, local);
+            if faults.drop_probability > 0.0 && thread_rng().gen_bool(faults.drop_probability) {
+                *lock_or_die!(self.dropped) += 1;
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// The delay to apply to a message sent from `local` to `remote`, if any.
+    pub fn outbound_delay(&self, local: P2PNodeId, remote: P2PNodeId) -> Option<Duration> {
+        let faults = self.link_faults(local, remote);
+        if !faults.is_delaying() {
+            return None;
+        }
+        let jitter = if faults.jitter > Duration::ZERO {
+            thread_rng().gen_range(Duration::ZERO, faults.jitter)
+        } else {
+            Duration::ZERO
+        };
+        Some(faults.latency + jitter)
+    }
+
+    /// Record a packet that passed through the deduplication stage of
+    /// `local`. Unique broadcasts are handed over to the harness for relaying.
+    pub fn record_packet(
+        &self,
+        local: P2PNodeId,
+        source: RemotePeerId,
+        packet: &NetworkPacket,
+        is_duplicate: bool,
+    ) {
+        let key = (local, payload_hash(&packet.message));
+        {
+            let mut deliveries = lock_or_die!(self.deliveries);
+            let count = deliveries.entry(key).or_default();
+            if is_duplicate {
+                count.duplicate += 1;
+            } else {
+                count.unique += 1;
+            }
+        }
+
+        if !is_duplicate && matches!(packet.destination, PacketDestination::Broadcast(..)) {
+            let _ = self.relay.send(Delivery {
+                receiver: local,
+                source,
+                network_id: packet.network_id,
+                payload: Arc::from(&packet.message[..]),
+            });
+        }
+    }
+
+    /// Get the delivery count of the given payload at the given node.
+    pub fn delivery_count(&self, node: P2PNodeId, payload: &[u8]) -> DeliveryCount {
+        lock_or_die!(self.deliveries)
+            .get(&(node, payload_hash(payload)))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the total number of packets dropped due to the link faults.
+    pub fn dropped_packets(&self) -> u64 { *lock_or_die!(self.dropped) }
+}
+
+fn payload_hash(payload: &[u8]) -> u64 {
+    let mut hasher = twox_hash::XxHash64::default();
+    hasher.write(payload);
+    hasher.finish()
+}
+
+/// A node of the [`TestNetwork`]. The node id, port and data directory are
+/// retained when the node is killed so that it can be restarted as the same
+/// peer.
+struct NodeSlot {
+    id:       P2PNodeId,
+    port:     u16,
+    data_dir: PathBuf,
+    node:     Option<Arc<P2PNode>>,
+}
+
+/// A set of in-process nodes connected over localhost, with a shared
+/// [`FaultInjector`].
+pub struct TestNetwork {
+    network_id:    NetworkId,
+    slots:         Arc<RwLock<Vec<NodeSlot>>>,
+    /// The links requested via the `connect*` methods. These are
+    /// re-established when partitions are healed and nodes restarted.
+    links:         HashSet<(usize, usize)>,
+    injector:      Arc<FaultInjector>,
+    relay_stopped: Arc<AtomicBool>,
+    relay_thread:  Option<JoinHandle<()>>,
+}
+
+impl TestNetwork {
+    /// Start `count` nodes on the given network. The nodes are not connected.
+    pub fn new(count: usize, network_id: u16) -> anyhow::Result<Self> {
+        let (relay_sender, relay_receiver) = crossbeam_channel::unbounded();
+        let injector = Arc::new(FaultInjector::new(relay_sender));
+
+        let mut slots = Vec::with_capacity(count);
+        for _ in 0..count {
+            let port = next_available_port();
+            let node = start_node(port, network_id, None, None, &injector)?;
+            slots.push(NodeSlot {
+                id: node.id(),
+                port,
+                data_dir: node.config.data_dir_path.clone(),
+                node: Some(node),
+            });
+        }
+        let slots = Arc::new(RwLock::new(slots));
+
+        let relay_stopped = Arc::new(AtomicBool::new(false));
+        let relay_thread =
+            spawn_relay_thread(Arc::clone(&slots), relay_receiver, Arc::clone(&relay_stopped));
+
+        Ok(Self {
+            network_id: NetworkId::from(network_id),
+            slots,
+            links: HashSet::new(),
+            injector,
+            relay_stopped,
+            relay_thread: Some(relay_thread),
+        })
+    }
+
+    /// The number of nodes, including the killed ones.
+    pub fn len(&self) -> usize { read_or_die!(self.slots).len() }
+
+    /// Whether the network has no nodes.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Get the node at the given index, unless it is killed.
+    pub fn node(&self, idx: usize) -> Option<Arc<P2PNode>> {
+        read_or_die!(self.slots)[idx].node.clone()
+    }
+
+    /// Get the id of the node at the given index.
+    pub fn node_id(&self, idx: usize) -> P2PNodeId { read_or_die!(self.slots)[idx].id }
+
+    /// Access the fault injector shared by the nodes.
+    pub fn injector(&self) -> &FaultInjector { &self.injector }
+
+    /// Connect the nodes at the given indices.
+    pub fn connect(&mut self, a: usize, b: usize) {
+        let link = (a.min(b), a.max(b));
+        self.links.insert(link);
+        self.dial(link.0, link.1);
+    }
+
+    /// Connect every node to every other node.
+    pub fn connect_mesh(&mut self) {
+        for a in 0..self.len() {
+            for b in a + 1..self.len() {
+                self.connect(a, b);
+            }
+        }
+    }
+
+    /// Connect the nodes in a ring.
+    pub fn connect_ring(&mut self) {
+        let count = self.len();
+        for a in 0..count {
+            self.connect(a, (a + 1) % count);
+        }
+    }
+
+    fn dial(&self, from: usize, to: usize) {
+        let slots = read_or_die!(self.slots);
+        if let (Some(source), Some(target)) = (&slots[from].node, &slots[to].node) {
+            source.register_conn_change(ConnChange::NewPeers(vec![target.self_peer]));
+        }
+    }
+
+    /// Split the nodes into the given groups. Connections between nodes in
+    /// different groups are closed on the next message, and new ones are
+    /// rejected during the handshake. Nodes that are not listed in any group
+    /// can talk to everyone.
+    pub fn partition(&self, groups: &[&[usize]]) {
+        let slots = read_or_die!(self.slots);
+        let mut assignment = write_or_die!(self.injector.groups);
+        assignment.clear();
+        for (group, members) in groups.iter().enumerate() {
+            for &idx in members.iter() {
+                assignment.insert(slots[idx].id, group);
+            }
+        }
+    }
+
+    /// Remove all partitions and re-establish the requested links.
+    pub fn heal(&self) {
+        write_or_die!(self.injector.groups).clear();
+        for &(a, b) in &self.links {
+            self.dial(a, b);
+        }
+    }
+
+    /// Set the faults of the directed link from `from` to `to`.
+    pub fn set_link_faults(&self, from: usize, to: usize, faults: LinkFaults) {
+        let (from, to) = (self.node_id(from), self.node_id(to));
+        write_or_die!(self.injector.links).insert((from, to), faults);
+    }
+
+    /// Set the faults of all the links without specific faults.
+    pub fn set_default_faults(&self, faults: LinkFaults) {
+        *write_or_die!(self.injector.default) = faults;
+    }
+
+    /// Remove all link faults.
+    pub fn clear_faults(&self) {
+        write_or_die!(self.injector.links).clear();
+        *write_or_die!(self.injector.default) = LinkFaults::default();
+    }
+
+    /// Stop the node at the given index. Its data directory is retained.
+    pub fn kill(&self, idx: usize) -> anyhow::Result<()> {
+        let node = write_or_die!(self.slots)[idx].node.take();
+        if let Some(node) = node {
+            // `P2PNode::close` would also stop the consensus queues shared by
+            // all the nodes in the process, so only the network is stopped.
+            node.stop_network();
+            node.join()?;
+        }
+        Ok(())
+    }
+
+    /// Restart a previously killed node with the same id, port and data
+    /// directory, and re-establish its links.
+    pub fn restart(&self, idx: usize) -> anyhow::Result<()> {
+        {
+            let mut slots = write_or_die!(self.slots);
+            let slot = &mut slots[idx];
+            anyhow::ensure!(slot.node.is_none(), "Node {} is still running.", idx);
+            let node = start_node(
+                slot.port,
+                self.network_id.id,
+                Some(slot.id),
+                Some(slot.data_dir.clone()),
+                &self.injector,
+            )?;
+            slot.node = Some(node);
+        }
+        // Dial from the restarted node, since the other nodes may have
+        // soft-banned its address while it was unreachable.
+        for &(a, b) in &self.links {
+            if a == idx {
+                self.dial(a, b);
+            } else if b == idx {
+                self.dial(b, a);
+            }
+        }
+        Ok(())
+    }
+
+    /// Broadcast a fresh packet simulating a block of the given size from the
+    /// node at the given index. Returns the payload of the packet.
+    pub fn broadcast(&self, from: usize, size: usize) -> anyhow::Result<Arc<[u8]>> {
+        self.broadcast_packet(from, PacketType::Block, size)
+    }
+
+    /// Broadcast a fresh packet of the given type and size from the node at
+    /// the given index. Returns the payload of the packet.
+    pub fn broadcast_packet(
+        &self,
+        from: usize,
+        packet_type: PacketType,
+        size: usize,
+    ) -> anyhow::Result<Arc<[u8]>> {
+        let node = self.node(from).ok_or_else(|| anyhow::anyhow!("Node {} is killed.", from))?;
+        let mut payload = Vec::with_capacity(1 + size);
+        payload.push(packet_type as u8);
+        payload.extend(generate_random_data(size));
+        let payload: Arc<[u8]> = Arc::from(payload);
+        send_broadcast_message(&node, vec![], self.network_id, Arc::clone(&payload));
+        Ok(payload)
+    }
+
+    /// The partition group of the node at the given index, if any.
+    fn group_of(&self, id: P2PNodeId) -> Option<usize> {
+        read_or_die!(self.injector.groups).get(&id).copied()
+    }
+
+    /// The sets of live nodes that are expected to be able to reach each
+    /// other, i.e., the live members of each partition group. Nodes without
+    /// a group belong to every set.
+    fn reachability_sets(&self) -> Vec<Vec<usize>> {
+        let slots = read_or_die!(self.slots);
+        let groups = read_or_die!(self.injector.groups);
+        let live = (0..slots.len()).filter(|&idx| slots[idx].node.is_some());
+        let mut sets: Vec<Vec<usize>> = Vec::new();
+        let group_count = groups.values().max().map_or(0, |max| max + 1);
+        if group_count == 0 {
+            sets.push(live.collect());
+        } else {
+            sets.resize(group_count, Vec::new());
+            for idx in live {
+                match groups.get(&slots[idx].id) {
+                    Some(&group) => sets[group].push(idx),
+                    None => sets.iter_mut().for_each(|set| set.push(idx)),
+                }
+            }
+        }
+        sets
+    }
+
+    /// Check whether the given live nodes form a connected graph through
+    /// their post-handshake connections.
+    fn is_connected(&self, members: &[usize]) -> bool {
+        let first = match members.first() {
+            Some(&first) => first,
+            None => return true,
+        };
+        let slots = read_or_die!(self.slots);
+        let by_id: HashMap<P2PNodeId, usize> =
+            members.iter().map(|&idx| (slots[idx].id, idx)).collect();
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from(vec![first]);
+        while let Some(idx) = queue.pop_front() {
+            if !visited.insert(idx) {
+                continue;
+            }
+            if let Some(node) = &slots[idx].node {
+                for stats in node.get_peer_stats(Some(PeerType::Node)) {
+                    if let Some(&peer) = by_id.get(&stats.self_id) {
+                        queue.push_back(peer);
+                    }
+                }
+            }
+        }
+        visited.len() == members.len()
+    }
+
+    /// Wait until the live nodes of every partition group form a connected
+    /// graph.
+    pub fn await_connectivity(&self, timeout: Duration) -> anyhow::Result<()> {
+        await_condition(timeout, || {
+            self.reachability_sets().iter().all(|members| self.is_connected(members))
+        })
+        .map_err(|_| anyhow::anyhow!("The nodes did not get connected within {:?}.", timeout))
+    }
+
+    /// Wait until the given broadcast payload from the node at index `from`
+    /// is delivered to every live node that can be reached from it.
+    pub fn await_delivery(
+        &self,
+        from: usize,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        let sender = self.node_id(from);
+        let expected = match self.group_of(sender) {
+            Some(group) => self.reachability_sets().swap_remove(group),
+            None => self.reachability_sets().concat(),
+        };
+        let expected = expected
+            .into_iter()
+            .filter(|&idx| idx != from)
+            .map(|idx| self.node_id(idx))
+            .collect::<HashSet<_>>();
+
+        let missing = || {
+            expected
+                .iter()
+                .filter(|&&id| self.injector.delivery_count(id, payload).unique == 0)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        await_condition(timeout, || missing().is_empty()).map_err(|_| {
+            anyhow::anyhow!(
+                "The broadcast was not delivered to {:?} within {:?}.",
+                missing(),
+                timeout
+            )
+        })
+    }
+
+    /// Check that no node passed the given payload on to the upper layers
+    /// more than once, and that no node outside the sender's partition
+    /// received it at all. Returns the total number of deliveries that were
+    /// filtered out by deduplication.
+    pub fn check_dedup(&self, from: usize, payload: &[u8]) -> anyhow::Result<u64> {
+        let sender_group = self.group_of(self.node_id(from));
+        let mut duplicates = 0;
+        for idx in 0..self.len() {
+            let id = self.node_id(idx);
+            let count = self.injector.delivery_count(id, payload);
+            anyhow::ensure!(
+                count.unique <= 1,
+                "Node {} passed the same broadcast on {} times.",
+                idx,
+                count.unique
+            );
+            let other_group = matches!(
+                (sender_group, self.group_of(id)),
+                (Some(a), Some(b)) if a != b
+            );
+            anyhow::ensure!(
+                !other_group || count.unique == 0,
+                "Node {} received a broadcast across a partition.",
+                idx
+            );
+            duplicates += count.duplicate;
+        }
+        Ok(duplicates)
+    }
+
+    /// Stop all the nodes and delete their data directories.
+    pub fn shutdown(mut self) -> anyhow::Result<()> { self.stop() }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        self.relay_stopped.store(true, Ordering::Release);
+        if let Some(relay_thread) = self.relay_thread.take() {
+            if relay_thread.join().is_err() {
+                error!("The relay thread of the test network panicked.");
+            }
+        }
+        for idx in 0..self.len() {
+            self.kill(idx)?;
+        }
+        for slot in write_or_die!(self.slots).drain(..) {
+            std::fs::remove_dir_all(&slot.data_dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestNetwork {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            error!("Could not stop the test network: {}", e);
+        }
+    }
+}
+
+/// Create a node with test settings, install the fault injector and start its
+/// poll loop. If no data directory is provided a fresh temporary one is used.
+fn start_node(
+    port: u16,
+    network_id: u16,
+    id: Option<P2PNodeId>,
+    data_dir: Option<PathBuf>,
+    injector: &Arc<FaultInjector>,
+) -> anyhow::Result<Arc<P2PNode>> {
+    let mut config = get_test_config(port, vec![network_id]);
+    if let Some(data_dir) = data_dir {
+        std::fs::remove_dir_all(&config.common.data_dir)?;
+        config.common.data_dir = data_dir.clone();
+        config.common.config_dir = data_dir;
+    }
+    config.cli.poll_interval = 1;
+    config.connection.housekeeping_interval = 1;
+    let regenesis_arc = Arc::new(Regenesis::from_blocks(dummy_regenesis_blocks()));
+    let stats = Arc::new(StatsExportService::new()?);
+    let (node, server, poll) = P2PNode::new(id, &config, PeerType::Node, stats, regenesis_arc)?;
+    *write_or_die!(node.connection_handler.fault_injector) = Some(Arc::clone(injector));
+    spawn(&node, server, poll, None);
+    Ok(node)
+}
+
+/// Spawn the thread that plays the role of consensus in relaying broadcasts:
+/// each unique broadcast is rebroadcast by the receiving node to all its
+/// peers except the one it came from.
+fn spawn_relay_thread(
+    slots: Arc<RwLock<Vec<NodeSlot>>>,
+    deliveries: Receiver<Delivery>,
+    stopped: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    spawn_or_die!("test network relay", move || {
+        while !stopped.load(Ordering::Acquire) {
+            let delivery = match deliveries.recv_timeout(Duration::from_millis(10)) {
+                Ok(delivery) => delivery,
+                Err(_) => continue,
+            };
+            let node = read_or_die!(slots)
+                .iter()
+                .find(|slot| slot.id == delivery.receiver)
+                .and_then(|slot| slot.node.clone());
+            if let Some(node) = node {
+                send_broadcast_message(
+                    &node,
+                    vec![delivery.source],
+                    delivery.network_id,
+                    delivery.payload,
+                );
+            }
+        }
+    })
+}
+
+/// Poll the condition until it holds or the timeout expires.
+fn await_condition(timeout: Duration, condition: impl Fn() -> bool) -> Result<(), ()> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if condition() {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    if condition() {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
@@ -25,6 +25,7 @@ pub mod types {
     use std::convert::{TryFrom, TryInto};
 
     include!(concat!(env!("OUT_DIR"), "/concordium.v2.rs"));
+    include!(concat!(env!("OUT_DIR"), "/concordium.v2.serde.rs"));
 
     /// Convert an account address to a pointer to the content. The length of
     /// the content is checked to be 32 bytes.
@@ -198,6 +199,163 @@ pub mod types {
         fn from(value: ContractAddress) -> Self { Self::new(value.index, value.subindex) }
     }
 
+    #[cfg(feature = "network_dump")]
+    impl TryFrom<DumpFilter> for crate::dumper::DumpFilter {
+        type Error = tonic::Status;
+
+        fn try_from(value: DumpFilter) -> Result<Self, Self::Error> {
+            use crate::{consensus_ffi::helpers::PacketType, dumper::DumpDirection};
+
+            let peer_ids = value
+                .peer_ids
+                .into_iter()
+                .map(|peer_id| peer_id.value.parse())
+                .collect::<anyhow::Result<_>>()
+                .map_err(|e| tonic::Status::invalid_argument(format!("Invalid peer id: {}", e)))?;
+            let ips = value
+                .ip_addresses
+                .into_iter()
+                .map(|ip| ip.value.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| {
+                    tonic::Status::invalid_argument(format!("Invalid IP address: {}", e))
+                })?;
+            let direction = match dump_filter::Direction::from_i32(value.direction) {
+                Some(dump_filter::Direction::Both) => None,
+                Some(dump_filter::Direction::Inbound) => Some(DumpDirection::Inbound),
+                Some(dump_filter::Direction::Outbound) => Some(DumpDirection::Outbound),
+                None => return Err(tonic::Status::invalid_argument("Unknown dump direction.")),
+            };
+            let packet_types = value
+                .packet_types
+                .into_iter()
+                .map(|packet_type| match dump_filter::PacketType::from_i32(packet_type) {
+                    Some(dump_filter::PacketType::Block) => Ok(PacketType::Block),
+                    Some(dump_filter::PacketType::Transaction) => Ok(PacketType::Transaction),
+                    Some(dump_filter::PacketType::FinalizationRecord) => {
+                        Ok(PacketType::FinalizationRecord)
+                    }
+                    Some(dump_filter::PacketType::FinalizationMessage) => {
+                        Ok(PacketType::FinalizationMessage)
+                    }
+                    Some(dump_filter::PacketType::CatchUpStatus) => Ok(PacketType::CatchUpStatus),
+                    None => Err(tonic::Status::invalid_argument("Unknown packet type.")),
+                })
+                .collect::<Result<_, _>>()?;
+            let network_ids = value
+                .network_ids
+                .into_iter()
+                .map(|network_id| {
+                    u16::try_from(network_id).map(crate::network::NetworkId::from).map_err(|_| {
+                        tonic::Status::invalid_argument(format!(
+                            "Invalid network id: {}",
+                            network_id
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Self {
+                peer_ids,
+                ips,
+                direction,
+                packet_types,
+                network_ids,
+                max_duration: value
+                    .max_duration
+                    .map(|duration| std::time::Duration::from_millis(duration.value)),
+                max_bytes: value.max_bytes,
+            })
+        }
+    }
+
+    impl From<crate::configuration::NetworkLimits> for NetworkLimits {
+        fn from(value: crate::configuration::NetworkLimits) -> Self {
+            Self {
+                desired_nodes:                Some(value.desired_nodes_count.into()),
+                max_allowed_nodes:            Some(value.max_allowed_nodes.into()),
+                relay_broadcast_percentage:   Some(value.relay_broadcast_percentage),
+                max_latency:                  value.max_latency,
+                housekeeping_interval:        Some(value.housekeeping_interval),
+                drop_rebroadcast_probability: value.drop_rebroadcast_probability,
+            }
+        }
+    }
+
+    impl SetNetworkLimitsRequest {
+        /// Replace the given limits by the ones that are set or cleared in the
+        /// request. The result is not checked for consistency. Bootstrappers do
+        /// not rebroadcast messages, so they reject the drop probability.
+        pub(crate) fn apply_to(
+            self,
+            mut limits: crate::configuration::NetworkLimits,
+            peer_type: crate::common::PeerType,
+        ) -> Result<crate::configuration::NetworkLimits, tonic::Status> {
+            let request = self.limits.unwrap_or_default();
+            if self.clear_max_latency && request.max_latency.is_some() {
+                return Err(tonic::Status::invalid_argument(
+                    "The maximum latency cannot be both set and cleared.",
+                ));
+            }
+            if self.clear_drop_rebroadcast_probability
+                && request.drop_rebroadcast_probability.is_some()
+            {
+                return Err(tonic::Status::invalid_argument(
+                    "The drop rebroadcast probability cannot be both set and cleared.",
+                ));
+            }
+            if peer_type != crate::common::PeerType::Node
+                && request.drop_rebroadcast_probability.is_some()
+            {
+                return Err(tonic::Status::invalid_argument(
+                    "The drop rebroadcast probability can only be set on nodes.",
+                ));
+            }
+            if let Some(desired) = request.desired_nodes {
+                limits.desired_nodes_count = desired.try_into().map_err(|_| {
+                    tonic::Status::invalid_argument("The desired number of nodes is too large.")
+                })?;
+            }
+            if let Some(max) = request.max_allowed_nodes {
+                limits.max_allowed_nodes = max.try_into().map_err(|_| {
+                    tonic::Status::invalid_argument("The maximum number of nodes is too large.")
+                })?;
+            }
+            if let Some(relay) = request.relay_broadcast_percentage {
+                limits.relay_broadcast_percentage = relay;
+            }
+            if let Some(latency) = request.max_latency {
+                limits.max_latency = Some(latency);
+            } else if self.clear_max_latency {
+                limits.max_latency = None;
+            }
+            if let Some(interval) = request.housekeeping_interval {
+                limits.housekeeping_interval = interval;
+            }
+            if let Some(probability) = request.drop_rebroadcast_probability {
+                limits.drop_rebroadcast_probability = Some(probability);
+            } else if self.clear_drop_rebroadcast_probability {
+                limits.drop_rebroadcast_probability = None;
+            }
+            Ok(limits)
+        }
+    }
+
+    impl From<crate::consensus_ffi::helpers::ConsensusIsInBakingCommitteeResponse>
+        for baker_status::CommitteeStatus
+    {
+        fn from(
+            value: crate::consensus_ffi::helpers::ConsensusIsInBakingCommitteeResponse,
+        ) -> Self {
+            use crate::consensus_ffi::helpers::ConsensusIsInBakingCommitteeResponse::*;
+            match value {
+                ActiveInCommittee => Self::ActiveInCommittee,
+                NotInCommittee => Self::NotInCommittee,
+                AddedButNotActiveInCommittee => Self::AddedButNotActiveInCommittee,
+                AddedButWrongKeys => Self::AddedButWrongKeys,
+            }
+        }
+    }
+
     impl TryFrom<Memo> for concordium_base::transactions::Memo {
         type Error = tonic::Status;
 
@@ -436,22 +594,36 @@ pub mod types {
         }
     }
 
+    impl TryFrom<AccountTransaction>
+        for concordium_base::transactions::AccountTransaction<
+            concordium_base::transactions::EncodedPayload,
+        >
+    {
+        type Error = tonic::Status;
+
+        fn try_from(value: AccountTransaction) -> Result<Self, Self::Error> {
+            let pat = PreAccountTransaction {
+                header:  value.header,
+                payload: value.payload,
+            };
+            let (header, payload) = pat.try_into()?;
+            let signature = value.signature.require()?.try_into()?;
+            Ok(Self {
+                signature,
+                header,
+                payload,
+            })
+        }
+    }
+
     impl SendBlockItemRequest {
         /// Return the Versioned block item serialized in the V0 format.
         pub(crate) fn get_v0_format(self) -> Result<Vec<u8>, tonic::Status> {
             match self.block_item.require()? {
                 send_block_item_request::BlockItem::AccountTransaction(at) => {
-                    let pat = PreAccountTransaction {
-                        header:  at.header,
-                        payload: at.payload,
-                    };
-                    let (header, payload) = pat.try_into()?;
-                    let signature = at.signature.require()?.try_into()?;
-                    let at = concordium_base::transactions::AccountTransaction {
-                        signature,
-                        header,
-                        payload,
-                    };
+                    let at: concordium_base::transactions::AccountTransaction<
+                        concordium_base::transactions::EncodedPayload,
+                    > = at.try_into()?;
                     Ok(concordium_base::common::to_bytes(&Versioned::new(
                         0.into(),
                         concordium_base::transactions::BlockItem::AccountTransaction(at),
@@ -623,6 +795,28 @@ struct ServiceConfig {
     get_account_transaction_sign_hash: bool,
     #[serde(default)]
     get_block_items: bool,
+    #[serde(default)]
+    get_finalized_blocks_from: bool,
+    #[serde(default)]
+    get_finalized_transaction_events: bool,
+    #[serde(default)]
+    dry_run_transaction: bool,
+    #[serde(default)]
+    send_block_items: bool,
+    #[serde(default)]
+    wait_for_block_item_finalization: bool,
+    #[serde(default)]
+    get_network_limits: bool,
+    #[serde(default)]
+    set_network_limits: bool,
+    #[serde(default)]
+    set_baker_credentials: bool,
+    #[serde(default)]
+    start_baker: bool,
+    #[serde(default)]
+    stop_baker: bool,
+    #[serde(default)]
+    get_baker_status: bool,
 }
 
 impl ServiceConfig {
@@ -678,6 +872,17 @@ impl ServiceConfig {
             send_block_item: true,
             get_account_transaction_sign_hash: true,
             get_block_items: true,
+            get_finalized_blocks_from: true,
+            get_finalized_transaction_events: true,
+            dry_run_transaction: true,
+            send_block_items: true,
+            wait_for_block_item_finalization: true,
+            get_network_limits: true,
+            set_network_limits: true,
+            set_baker_credentials: false,
+            start_baker: true,
+            stop_baker: true,
+            get_baker_status: true,
         }
     }
 
@@ -761,12 +966,20 @@ impl<D: prost::Message + Default> tonic::codec::Decoder for RawDecoder<D> {
     }
 }
 
+mod cache;
+mod reload;
+mod rest;
+mod subscriptions;
+
 /// The implementation of the GRPC2 server.
 pub mod server {
     use crate::{
-        configuration::GRPC2Config,
+        configuration::{GRPC2Config, SEND_BLOCK_ITEMS_BACKOFF},
         consensus_ffi::{
-            consensus::{ConsensusContainer, ConsensusType, CALLBACK_QUEUE},
+            consensus::{
+                ConsensusContainer, ConsensusType, BLOCKS_BAKED, CALLBACK_QUEUE,
+                FINALIZATION_MESSAGES_SENT,
+            },
             ffi::NotificationHandlers,
             helpers::{
                 ConsensusFfiResponse, ConsensusIsInBakingCommitteeResponse, ContractStateResponse,
@@ -776,29 +989,136 @@ pub mod server {
         },
         health,
         p2p::P2PNode,
+        stats_export_service::StatsExportService,
     };
     use anyhow::Context;
     use byteorder::WriteBytesExt;
     use futures::{FutureExt, StreamExt};
+    use prost::Message;
     use std::{
         io::Write,
         net::SocketAddr,
         sync::{Arc, Mutex},
     };
-    use tonic::{async_trait, transport::ServerTlsConfig};
-
-    use super::*;
+    use tonic::async_trait;
+
+    pub use super::reload::{check_files, ReloadableConfig};
+    use super::{
+        cache::{CacheKey, CachedResponse, QueryCache},
+        reload,
+        subscriptions::{
+            transaction_events, BlockItemStatusUpdates, FinalizedHeights, Notifications,
+            TransactionEventFilter,
+        },
+        *,
+    };
 
     /// An updatable list of listeners for events generated by the node.
     /// These are specifically for events that are generated naturally during
     /// consensus operations. Currently that is "block arrived" and "block
     /// finalized".
-    type Clients = Arc<Mutex<Vec<tokio::sync::mpsc::Sender<Result<Arc<[u8]>, tonic::Status>>>>>;
+    type Clients = Arc<Mutex<Vec<Subscriber>>>;
+
+    /// A listener for the events generated by the node.
+    struct Subscriber {
+        sender:     tokio::sync::mpsc::Sender<Result<Arc<[u8]>, tonic::Status>>,
+        /// Whether the subscriber is told about the events it missed because
+        /// its channel was full. Subscribers that only use the events to learn
+        /// about progress, and look up what they missed, are not.
+        report_lag: bool,
+        /// The number of events missed since the channel was last full.
+        skipped:    u64,
+    }
+
+    impl Subscriber {
+        /// A client that is told when it misses events.
+        fn client(sender: tokio::sync::mpsc::Sender<Result<Arc<[u8]>, tonic::Status>>) -> Self {
+            Subscriber {
+                sender,
+                report_lag: true,
+                skipped: 0,
+            }
+        }
+
+        /// A subscriber that tolerates missing events.
+        fn progress(sender: tokio::sync::mpsc::Sender<Result<Arc<[u8]>, tonic::Status>>) -> Self {
+            Subscriber {
+                sender,
+                report_lag: false,
+                skipped: 0,
+            }
+        }
+
+        /// Send an event to the subscriber, without waiting. Returns whether
+        /// the subscriber should be retained, and whether the event was
+        /// dropped.
+        ///
+        /// A client that misses events because its channel is full is sent an
+        /// error with the number of skipped events as soon as there is room
+        /// for it in the channel, which ends its stream. This way a client can
+        /// tell a gap from a period without events.
+        fn send(&mut self, event: &Arc<[u8]>) -> (bool, bool) {
+            use tokio::sync::mpsc::error::TrySendError;
+            if self.skipped > 0 {
+                let lagged = tonic::Status::data_loss(format!(
+                    "The client lagged behind and {} items were skipped.",
+                    self.skipped + 1
+                ));
+                return match self.sender.try_send(Err(lagged)) {
+                    Ok(()) | Err(TrySendError::Closed(_)) => (false, true),
+                    Err(TrySendError::Full(_)) => {
+                        self.skipped += 1;
+                        (true, true)
+                    }
+                };
+            }
+            match self.sender.try_send(Ok(event.clone())) {
+                Ok(()) => (true, false),
+                Err(TrySendError::Full(_)) => {
+                    if self.report_lag {
+                        self.skipped = 1;
+                    }
+                    (true, self.report_lag)
+                }
+                Err(TrySendError::Closed(_)) => (false, false),
+            }
+        }
+    }
+
+    /// Relay the events generated by consensus to the subscribers, recording
+    /// the number of events dropped for clients that lag behind.
+    async fn relay_blocks(
+        mut events: futures::channel::mpsc::UnboundedReceiver<Arc<[u8]>>,
+        subscribers: Clients,
+        stats: Arc<StatsExportService>,
+        record_dropped: fn(&StatsExportService, u64),
+    ) {
+        while let Some(event) = events.next().await {
+            match subscribers.lock() {
+                Ok(mut subscribers) => {
+                    let mut dropped = 0;
+                    subscribers.retain_mut(|subscriber| {
+                        let (retain, was_dropped) = subscriber.send(&event);
+                        if was_dropped {
+                            dropped += 1;
+                        }
+                        retain
+                    });
+                    if dropped > 0 {
+                        record_dropped(&stats, dropped);
+                    }
+                }
+                Err(e) => {
+                    error!("Could not acquire lock to the list of receivers: {}.", e)
+                }
+            }
+        }
+    }
 
     /// The type that implements the service that responds to queries.
     struct RpcServerImpl {
-        /// Configuration of enabled endpoints.
-        service_config: ServiceConfig,
+        /// Configuration of enabled endpoints and TLS, which can be reloaded.
+        config: Arc<ReloadableConfig>,
         /// Reference to the node to support network and node status related
         /// queries.
         node: Arc<P2PNode>,
@@ -808,8 +1128,16 @@ pub mod server {
         blocks_channels: Clients,
         /// The list of active clients listening for new finalized blocks.
         finalized_blocks_channels: Clients,
+        /// The number of blocks buffered for each client of the block streams.
+        stream_capacity: usize,
+        /// The cache of responses to queries about finalized blocks, if
+        /// enabled.
+        query_cache: Option<Arc<QueryCache>>,
     }
 
+    /// The type of the streams of responses produced by consensus queries.
+    type QueryStream = futures::channel::mpsc::Receiver<Result<Vec<u8>, tonic::Status>>;
+
     /// An administrative structure that collects objects needed to manage the
     /// the GRPC2 server.
     pub struct GRPC2Server {
@@ -826,6 +1154,13 @@ pub mod server {
         /// relaying finalized blocks.
         blocks_relay:           tokio::task::JoinHandle<()>,
         finalized_blocks_relay: tokio::task::JoinHandle<()>,
+        /// A handle to the REST gateway task, if the gateway is enabled.
+        rest_gateway:           Option<tokio::task::JoinHandle<()>>,
+        /// The configuration that can be reloaded while the server is running.
+        config:                 Arc<ReloadableConfig>,
+        /// A handle to the task that reloads the configuration when its files
+        /// change, if there are any files.
+        config_watch:           Option<tokio::task::JoinHandle<()>>,
     }
 
     impl GRPC2Server {
@@ -844,117 +1179,89 @@ pub mod server {
                 // parser should already make sure that when the address is defined, so is the
                 // port.
                 let listen_port = config.listen_port.context("Missing GRPC port")?;
+                anyhow::ensure!(
+                    config.stream_capacity > 0,
+                    "The capacity of the block streams must be positive."
+                );
 
                 log::info!("Starting GRPC V2 server listening on {listen_addr}:{listen_port}");
 
-                let service_config = if let Some(ref source) = config.endpoint_config {
-                    ServiceConfig::from_file(source)?
+                let reloadable = Arc::new(ReloadableConfig::new(config)?);
+                let config_watch = if reloadable.has_files() {
+                    Some(tokio::spawn(reload::watch(Arc::clone(&reloadable))))
                 } else {
-                    ServiceConfig::new_all_enabled()
+                    None
                 };
-                debug!("GRPC endpoints enabled: {:#?}", service_config);
-
-                let identity = match (&config.x509_cert, &config.cert_private_key) {
-                    (None, None) => None,
-                    (None, Some(_)) => {
-                        anyhow::bail!("Private key supplied, but not the certificate.")
-                    }
-                    (Some(_), None) => {
-                        anyhow::bail!("Certificate supplied, but not the private key.")
-                    }
-                    (Some(cert_path), Some(key_path)) => {
-                        let cert =
-                            std::fs::read(cert_path).context("Unable to read certificate.")?;
-                        let key = std::fs::read(key_path).context("Unable to read key.")?;
-                        let identity = tonic::transport::Identity::from_pem(cert, key);
-                        Some(identity)
-                    }
-                };
-                let server = RpcServerImpl {
-                    service_config,
+                let server = Arc::new(RpcServerImpl {
+                    config: Arc::clone(&reloadable),
                     node: Arc::clone(node),
                     consensus: consensus.clone(),
                     blocks_channels: Arc::new(Mutex::new(Vec::new())),
                     finalized_blocks_channels: Arc::new(Mutex::new(Vec::new())),
-                };
+                    stream_capacity: config.stream_capacity,
+                    query_cache: if config.query_cache_size > 0 {
+                        Some(Arc::new(QueryCache::new(config.query_cache_size)))
+                    } else {
+                        None
+                    },
+                });
 
                 let NotificationHandlers {
-                    mut blocks,
-                    mut finalized_blocks,
+                    blocks,
+                    finalized_blocks,
                 } = notification_handlers;
 
-                let blocks_channel = server.blocks_channels.clone();
-                let blocks_relay = tokio::spawn(async move {
-                    while let Some(v) = blocks.next().await {
-                        match blocks_channel.lock() {
-                            Ok(mut senders) => senders.retain(|sender| {
-                                if let Err(e) = sender.try_send(Ok(v.clone())) {
-                                    match e {
-                                        tokio::sync::mpsc::error::TrySendError::Full(_) => {
-                                            // if the client is too slow they will just not get all
-                                            // the blocks. If they miss some they can query.
-                                            // Retaining old blocks would complicate the server and
-                                            // go beyond the simple semantics that the current API
-                                            // has.
-                                            // We still retain the client for the next block though.
-                                            true
-                                        }
-                                        tokio::sync::mpsc::error::TrySendError::Closed(_) => false,
-                                    }
-                                } else {
-                                    true
-                                }
-                            }),
-                            Err(e) => {
-                                error!("Could not acquire lock to the list of receivers: {}.", e)
-                            }
-                        }
-                    }
-                });
+                let blocks_relay = tokio::spawn(relay_blocks(
+                    blocks,
+                    server.blocks_channels.clone(),
+                    node.stats.clone(),
+                    StatsExportService::grpc_blocks_dropped_add,
+                ));
+                let finalized_blocks_relay = tokio::spawn(relay_blocks(
+                    finalized_blocks,
+                    server.finalized_blocks_channels.clone(),
+                    node.stats.clone(),
+                    StatsExportService::grpc_finalized_blocks_dropped_add,
+                ));
 
-                let finalized_blocks_channel = server.finalized_blocks_channels.clone();
-                let finalized_blocks_relay = tokio::spawn(async move {
-                    while let Some(v) = finalized_blocks.next().await {
-                        match finalized_blocks_channel.lock() {
-                            Ok(mut senders) => senders.retain(|sender| {
-                                if let Err(e) = sender.try_send(Ok(v.clone())) {
-                                    match e {
-                                        tokio::sync::mpsc::error::TrySendError::Full(_) => {
-                                            // if the client is too slow they will just not get all
-                                            // the blocks. If they miss some they can query.
-                                            // Retaining old blocks would complicate the server and
-                                            // go beyond
-                                            // the simple semantics that the current API has.
-                                            // We still retain the client for the next block though.
-                                            true
-                                        }
-                                        tokio::sync::mpsc::error::TrySendError::Closed(_) => false,
-                                    }
-                                } else {
-                                    true
-                                }
-                            }),
-                            Err(e) => {
-                                error!("Could not acquire lock to the list of receivers: {}.", e)
+                let rest_gateway = if let Some(rest_port) = config.rest_port {
+                    let rest_addr = std::net::SocketAddr::new(listen_addr, rest_port);
+                    log::info!("Starting GRPC V2 REST gateway listening on {rest_addr}");
+                    let listener = std::net::TcpListener::bind(rest_addr)
+                        .and_then(|listener| {
+                            listener.set_nonblocking(true)?;
+                            tokio::net::TcpListener::from_std(listener)
+                        })
+                        .context("Unable to bind the REST gateway.")?;
+                    let server = Arc::clone(&server);
+                    let reloadable = Arc::clone(&reloadable);
+                    let error_sender = error_sender.clone();
+                    Some(tokio::spawn(async move {
+                        if let Err(err) = rest::serve(server, listener, reloadable).await {
+                            error!("A runtime error occurred in the REST gateway: {}", err);
+                            if error_sender.send(()).is_err() {
+                                error!(
+                                    "An error occurred while trying to signal the main node \
+                                     thread."
+                                )
                             }
                         }
-                    }
-                });
-                let service = service::queries_server::QueriesServer::new(server);
+                    }))
+                } else {
+                    None
+                };
+
+                let service = service::queries_server::QueriesServer::from_arc(server);
                 let log_layer = tower_http::trace::TraceLayer::new_for_grpc();
                 let mut builder = tonic::transport::Server::builder().layer(log_layer);
-                if let Some(identity) = identity {
-                    builder = builder
-                        .tls_config(ServerTlsConfig::new().identity(identity))
-                        .context("Unable to configure TLS.")?;
-                } else {
-                    // if TLS is not enabled and we want grpc-web we need to explicitly
-                    // enable http1 support.
-                    // If TLS is enabled this is not necessary because TLS supports protocol
-                    // negotiation.
-                    if config.enable_grpc_web {
-                        builder = builder.accept_http1(true);
-                    }
+                // TLS is handled when accepting connections, see `tls_incoming` below, so
+                // that new connections use the current certificate.
+                // If TLS is not enabled and we want grpc-web we need to explicitly
+                // enable http1 support.
+                // If TLS is enabled this is not necessary because TLS supports protocol
+                // negotiation.
+                if !reloadable.has_tls() && config.enable_grpc_web {
+                    builder = builder.accept_http1(true);
                 }
 
                 let (shutdown_sender, shutdown_receiver) = tokio::sync::oneshot::channel::<()>();
@@ -983,13 +1290,27 @@ pub mod server {
                         .add_service(reflection_service)
                 };
 
+                let listen_addr = std::net::SocketAddr::new(listen_addr, listen_port);
+                let tls_listener = if reloadable.has_tls() {
+                    let listener = std::net::TcpListener::bind(listen_addr)
+                        .and_then(|listener| {
+                            listener.set_nonblocking(true)?;
+                            tokio::net::TcpListener::from_std(listener)
+                        })
+                        .context("Unable to bind the GRPC2 server.")?;
+                    Some(listener)
+                } else {
+                    None
+                };
                 let task = tokio::spawn(async move {
-                    let result = router
-                        .serve_with_shutdown(
-                            std::net::SocketAddr::new(listen_addr, listen_port),
-                            shutdown_receiver.map(|_| ()),
-                        )
-                        .await;
+                    let shutdown = shutdown_receiver.map(|_| ());
+                    let result = match tls_listener {
+                        Some(listener) => {
+                            let incoming = reload::tls_incoming(listener, reloadable);
+                            router.serve_with_incoming_shutdown(incoming, shutdown).await
+                        }
+                        None => router.serve_with_shutdown(listen_addr, shutdown).await,
+                    };
                     if let Err(ref err) = result {
                         // Log an error and notify main thread that an error occured.
                         error!("A runtime error occurred in the GRPC2 server: {}", err);
@@ -1004,12 +1325,19 @@ pub mod server {
                     shutdown_sender,
                     blocks_relay,
                     finalized_blocks_relay,
+                    rest_gateway,
+                    config: reloadable,
+                    config_watch,
                 }))
             } else {
                 Ok(None)
             }
         }
 
+        /// The configuration of the server that can be reloaded while it is
+        /// running.
+        pub fn reloadable_config(&self) -> Arc<ReloadableConfig> { Arc::clone(&self.config) }
+
         /// Query whether the server task thread is still running.
         pub fn is_finished(&self) -> bool { self.task.is_finished() }
 
@@ -1023,6 +1351,12 @@ pub mod server {
             }
             self.blocks_relay.abort();
             self.finalized_blocks_relay.abort();
+            if let Some(rest_gateway) = self.rest_gateway {
+                rest_gateway.abort();
+            }
+            if let Some(config_watch) = self.config_watch {
+                config_watch.abort();
+            }
             // Force the rpc server to shut down in at most 10 seconds.
             let timeout_duration = std::time::Duration::from_secs(10);
             match tokio::time::timeout(timeout_duration, self.task).await {
@@ -1042,6 +1376,340 @@ pub mod server {
         }
     }
 
+    impl RpcServerImpl {
+        /// The key of the cached response to a query about the given block.
+        /// Only queries that name the block by its hash are cached, since the
+        /// other ones refer to different blocks over time.
+        fn cache_key(
+            &self,
+            method: &'static str,
+            block: &types::BlockHashInput,
+            request: &impl prost::Message,
+        ) -> Option<CacheKey> {
+            self.query_cache.as_ref()?;
+            match block.block_hash_input {
+                Some(types::block_hash_input::BlockHashInput::Given(ref hash)) => {
+                    let block = hash.value.as_slice().try_into().ok()?;
+                    Some(CacheKey::new(method, block, request.encode_to_vec()))
+                }
+                _ => None,
+            }
+        }
+
+        /// Look up a cached response, recording whether it was found.
+        fn lookup(&self, key: &CacheKey) -> Option<CachedResponse> {
+            let response = self.query_cache.as_ref()?.get(key);
+            if response.is_some() {
+                self.node.stats.grpc_query_cache_hits_inc();
+            } else {
+                self.node.stats.grpc_query_cache_misses_inc();
+            }
+            response
+        }
+
+        /// Whether the block with the given hash is finalized, in which case
+        /// the responses to queries about it never change.
+        fn is_finalized(&self, block: [u8; 32]) -> bool {
+            let input = types::BlockHashInput {
+                block_hash_input: Some(types::block_hash_input::BlockHashInput::Given(
+                    types::BlockHash {
+                        value: block.to_vec(),
+                    },
+                )),
+            };
+            match self.consensus.get_block_info_v2(&input) {
+                Ok((_, info)) => {
+                    types::BlockInfo::decode(info.as_slice()).map_or(false, |info| info.finalized)
+                }
+                Err(_) => false,
+            }
+        }
+
+        /// Store a response to a query about a finalized block.
+        fn store(&self, key: CacheKey, response: Vec<Vec<u8>>) {
+            if let Some(ref cache) = self.query_cache {
+                cache.insert(key, response);
+                self.node.stats.set_grpc_query_cache_size(cache.size() as i64);
+            }
+        }
+
+        /// Answer a query about a block with a single response, from the cache
+        /// if possible. The query returns the hash of the block and the
+        /// response.
+        fn cached_query(
+            &self,
+            method: &'static str,
+            block: &types::BlockHashInput,
+            request: &impl prost::Message,
+            query: impl FnOnce() -> Result<([u8; 32], Vec<u8>), tonic::Status>,
+        ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
+            let key = self.cache_key(method, block, request);
+            if let Some(key) = key.as_ref() {
+                if let Some(response) = self.lookup(key).and_then(|cached| cached.first().cloned())
+                {
+                    let mut response = tonic::Response::new(response);
+                    add_hash(&mut response, key.block())?;
+                    return Ok(response);
+                }
+            }
+            let (hash, response) = query()?;
+            if let Some(key) = key {
+                if self.is_finalized(hash) {
+                    self.store(key, vec![response.clone()]);
+                }
+            }
+            let mut response = tonic::Response::new(response);
+            add_hash(&mut response, hash)?;
+            Ok(response)
+        }
+
+        /// Answer a query about a block with a stream of responses, from the
+        /// cache if possible. The query is given the sender to write the
+        /// responses to, and returns the hash of the block.
+        ///
+        /// The responses to a query about a finalized block are forwarded to
+        /// the client and collected at the same time, and are only stored if
+        /// the stream completes without errors.
+        fn cached_stream(
+            &self,
+            method: &'static str,
+            block: &types::BlockHashInput,
+            request: &impl prost::Message,
+            capacity: usize,
+            query: impl FnOnce(
+                futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
+            ) -> Result<[u8; 32], tonic::Status>,
+        ) -> Result<tonic::Response<QueryStream>, tonic::Status> {
+            let key = self.cache_key(method, block, request);
+            if let Some(key) = key.as_ref() {
+                if let Some(cached) = self.lookup(key) {
+                    let (mut sender, receiver) =
+                        futures::channel::mpsc::channel(cached.len().max(1));
+                    for item in cached.iter() {
+                        // the channel has room for all the cached items
+                        if sender.try_send(Ok(item.clone())).is_err() {
+                            return Err(tonic::Status::internal("Could not replay the response."));
+                        }
+                    }
+                    let mut response = tonic::Response::new(receiver);
+                    add_hash(&mut response, key.block())?;
+                    return Ok(response);
+                }
+            }
+            let (sender, mut receiver) = futures::channel::mpsc::channel(capacity);
+            let hash = query(sender)?;
+            let key = match key {
+                Some(key) if self.is_finalized(hash) => key,
+                _ => {
+                    let mut response = tonic::Response::new(receiver);
+                    add_hash(&mut response, hash)?;
+                    return Ok(response);
+                }
+            };
+            let (mut client, client_receiver) = futures::channel::mpsc::channel(capacity);
+            let cache = self.query_cache.clone();
+            let stats = self.node.stats.clone();
+            tokio::spawn(async move {
+                use futures::SinkExt;
+                let mut items = Vec::new();
+                let mut complete = true;
+                while let Some(item) = receiver.next().await {
+                    match item {
+                        Ok(ref value) => items.push(value.clone()),
+                        Err(_) => complete = false,
+                    }
+                    if client.send(item).await.is_err() {
+                        // the client went away, so the response might not be
+                        // read to the end
+                        complete = false;
+                        break;
+                    }
+                }
+                if let (true, Some(cache)) = (complete, cache) {
+                    cache.insert(key, items);
+                    stats.set_grpc_query_cache_size(cache.size() as i64);
+                }
+            });
+            let mut response = tonic::Response::new(client_receiver);
+            add_hash(&mut response, hash)?;
+            Ok(response)
+        }
+
+        /// Subscribe to the given notifications only to learn about progress,
+        /// so that missed notifications are not reported.
+        fn subscribe_progress(&self, channels: &Clients) -> Result<Notifications, tonic::Status> {
+            let (sender, receiver) = tokio::sync::mpsc::channel(self.stream_capacity);
+            match channels.lock() {
+                Ok(mut fbs) => {
+                    fbs.push(Subscriber::progress(sender));
+                }
+                Err(e) => {
+                    error!("Could not acquire lock: {}", e);
+                    return Err(tonic::Status::internal("Could not enqueue request."));
+                }
+            }
+            Ok(receiver)
+        }
+
+        /// Subscribe to the notifications about finalized blocks and produce
+        /// the finalized heights from the given one, or from the next
+        /// finalized block if no height is given.
+        fn subscribe_finalized_heights(
+            &self,
+            start: Option<u64>,
+        ) -> Result<FinalizedHeights, tonic::Status> {
+            let receiver = self.subscribe_progress(&self.finalized_blocks_channels)?;
+            FinalizedHeights::new(self.consensus.clone(), receiver, start)
+        }
+
+        /// Subscribe to the notifications about arrived and finalized blocks
+        /// and produce the changes in the status of the given block
+        /// item.
+        fn subscribe_block_item_status(
+            &self,
+            hash: crate::grpc2::types::TransactionHash,
+        ) -> Result<BlockItemStatusUpdates, tonic::Status> {
+            let blocks = self.subscribe_progress(&self.blocks_channels)?;
+            let finalized = self.subscribe_progress(&self.finalized_blocks_channels)?;
+            Ok(BlockItemStatusUpdates::new(self.consensus.clone(), hash, blocks, finalized))
+        }
+
+        /// The status of the node as a baker, if it is configured with baker
+        /// keys.
+        fn baker_consensus_info(&self) -> types::node_info::BakerConsensusInfo {
+            let (in_baking_committee, _, bid) = self.consensus.in_baking_committee();
+            let baker_id = types::BakerId {
+                value: bid,
+            };
+
+            let baker_status = match in_baking_committee {
+                ConsensusIsInBakingCommitteeResponse::ActiveInCommittee => {
+                    if self.consensus.in_finalization_committee() {
+                        // The node is configured with baker keys and is an active
+                        // member of
+                        // the finalization committee.
+                        types::node_info::baker_consensus_info::Status::ActiveFinalizerCommitteeInfo(
+                            types::node_info::baker_consensus_info::ActiveFinalizerCommitteeInfo {},
+                        )
+                    } else {
+                        // The node is configured with baker keys and is an active
+                        // member of the baking
+                        // committee.
+                        types::node_info::baker_consensus_info::Status::ActiveBakerCommitteeInfo(
+                            types::node_info::baker_consensus_info::ActiveBakerCommitteeInfo {},
+                        )
+                    }
+                }
+                ConsensusIsInBakingCommitteeResponse::NotInCommittee => {
+                    // The node is configured with baker keys but is not in the baking
+                    // committee.
+                    types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
+                            types::node_info::baker_consensus_info::PassiveCommitteeInfo::NotInCommittee.into()
+                        )
+                }
+                ConsensusIsInBakingCommitteeResponse::AddedButNotActiveInCommittee => {
+                    // The node is configured with baker keys and it is a baker, but not
+                    // for the current epoch.
+                    types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
+                            types::node_info::baker_consensus_info::PassiveCommitteeInfo::AddedButNotActiveInCommittee.into()
+                        )
+                }
+                ConsensusIsInBakingCommitteeResponse::AddedButWrongKeys => {
+                    // The node is configured with baker keys however they do not match
+                    // expected baker keys for the associated account.
+                    types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
+                            types::node_info::baker_consensus_info::PassiveCommitteeInfo::AddedButWrongKeys.into()
+                        )
+                }
+            };
+            types::node_info::BakerConsensusInfo {
+                baker_id: Some(baker_id),
+                status:   Some(baker_status),
+            }
+        }
+    }
+
+    /// Submit a block item to consensus and, if it is accepted, to the
+    /// outbound queue so that it is sent to peers.
+    fn send_block_item(
+        node: &P2PNode,
+        consensus: &ConsensusContainer,
+        request: crate::grpc2::types::SendBlockItemRequest,
+    ) -> Result<crate::grpc2::types::TransactionHash, tonic::Status> {
+        use ConsensusFfiResponse::*;
+        if node.is_network_stopped() {
+            return Err(tonic::Status::failed_precondition(
+                "The network is stopped due to unrecognized protocol update.",
+            ));
+        }
+
+        let transaction_bytes = request.get_v0_format()?;
+        if transaction_bytes.len() > crate::configuration::PROTOCOL_MAX_TRANSACTION_SIZE {
+            warn!("Received a transaction that exceeds maximum transaction size.");
+            return Err(tonic::Status::invalid_argument(
+                "Transaction size exceeds maximum allowed size.",
+            ));
+        }
+        let (transaction_hash, consensus_result) = consensus.send_transaction(&transaction_bytes);
+
+        let result = if consensus_result == Success {
+            let mut payload = Vec::with_capacity(1 + transaction_bytes.len());
+            payload.write_u8(PacketType::Transaction as u8)?;
+            payload.write_all(&transaction_bytes)?;
+
+            CALLBACK_QUEUE.send_out_message(ConsensusMessage::new(
+                MessageType::Outbound(None),
+                PacketType::Transaction,
+                Arc::from(payload),
+                vec![],
+                None,
+            ))
+        } else {
+            Err(consensus_result.into())
+        };
+
+        let mk_err_response = |code, error| Err(tonic::Status::new(code, error));
+        let mk_err_invalid_argument_response =
+            |error| mk_err_response(tonic::Code::InvalidArgument, error);
+
+        match (result, consensus_result) {
+            (Ok(_), Success) => {
+                let transaction_hash = match transaction_hash {
+                    Some(h) => h,
+                    None => {
+                        error!("Block item hash not present, but transaction is accepted.");
+                        return Err(tonic::Status::internal(
+                            "Block item hash not present, but transaction is accepted.",
+                        ));
+                    }
+                };
+                Ok(crate::grpc2::types::TransactionHash {
+                    value: transaction_hash.to_vec(),
+                })
+            }
+            (Err(e), Success) => {
+                warn!("Couldn't put a transaction in the outbound queue due to {:?}", e);
+                Err(tonic::Status::new(
+                    tonic::Code::Internal,
+                    format!("Couldn't put a transaction in the outbound queue due to {:?}", e),
+                ))
+            }
+            // the wildcard is always Err as only 'Success' responses from the consensus are
+            // being retransmitted. In other words Ok(_) implies consensus_result == Success
+            (_, DuplicateEntry) => {
+                mk_err_response(tonic::Code::AlreadyExists, DuplicateEntry.to_string())
+            }
+            (_, ConsensusShutDown) => {
+                warn!(
+                    "Consensus didn't accept a transaction via RPC due to {:?}",
+                    ConsensusShutDown.to_string()
+                );
+                mk_err_invalid_argument_response(ConsensusShutDown.to_string())
+            }
+            (_, consensus_error) => mk_err_invalid_argument_response(consensus_error.to_string()),
+        }
+    }
+
     #[async_trait]
     impl service::queries_server::Queries for RpcServerImpl {
         /// Return type for the 'GetAccountList' method.
@@ -1071,9 +1739,15 @@ pub mod server {
         /// Return type for the 'Blocks' method.
         type GetBlocksStream =
             tokio_stream::wrappers::ReceiverStream<Result<Arc<[u8]>, tonic::Status>>;
+        /// Return type for the 'GetFinalizedBlocksFrom' method.
+        type GetFinalizedBlocksFromStream =
+            tokio_stream::wrappers::ReceiverStream<Result<Vec<u8>, tonic::Status>>;
         /// Return type for the 'FinalizedBlocks' method.
         type GetFinalizedBlocksStream =
             tokio_stream::wrappers::ReceiverStream<Result<Arc<[u8]>, tonic::Status>>;
+        /// Return type for the 'GetFinalizedTransactionEvents' method.
+        type GetFinalizedTransactionEventsStream =
+            tokio_stream::wrappers::ReceiverStream<Result<Vec<u8>, tonic::Status>>;
         /// Return type for the 'GetIdentityProviders' method.
         type GetIdentityProvidersStream =
             futures::channel::mpsc::Receiver<Result<Vec<u8>, tonic::Status>>;
@@ -1098,18 +1772,25 @@ pub mod server {
         /// Return type for the 'GetPoolDelegators' method.
         type GetPoolDelegatorsStream =
             futures::channel::mpsc::Receiver<Result<Vec<u8>, tonic::Status>>;
+        /// Return type for the 'SendBlockItems' method.
+        type SendBlockItemsStream = tokio_stream::wrappers::ReceiverStream<
+            Result<crate::grpc2::types::SendBlockItemsResponse, tonic::Status>,
+        >;
+        /// Return type for the 'WaitForBlockItemFinalization' method.
+        type WaitForBlockItemFinalizationStream =
+            tokio_stream::wrappers::ReceiverStream<Result<Vec<u8>, tonic::Status>>;
 
         async fn get_blocks(
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<Self::GetBlocksStream>, tonic::Status> {
-            if !self.service_config.get_blocks {
+            if !self.config.service_config().get_blocks {
                 return Err(tonic::Status::unimplemented("`GetBlocks` is not enabled."));
             }
-            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            let (sender, receiver) = tokio::sync::mpsc::channel(self.stream_capacity);
             match self.blocks_channels.lock() {
                 Ok(mut fbs) => {
-                    fbs.push(sender);
+                    fbs.push(Subscriber::client(sender));
                 }
                 Err(e) => {
                     error!("Could not acquire lock: {}", e);
@@ -1123,13 +1804,13 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<Self::GetFinalizedBlocksStream>, tonic::Status> {
-            if !self.service_config.get_finalized_blocks {
+            if !self.config.service_config().get_finalized_blocks {
                 return Err(tonic::Status::unimplemented("`GetFinalizedBlocks` is not enabled."));
             }
-            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            let (sender, receiver) = tokio::sync::mpsc::channel(self.stream_capacity);
             match self.finalized_blocks_channels.lock() {
                 Ok(mut fbs) => {
-                    fbs.push(sender);
+                    fbs.push(Subscriber::client(sender));
                 }
                 Err(e) => {
                     error!("Could not acquire lock: {}", e);
@@ -1139,11 +1820,93 @@ pub mod server {
             Ok(tonic::Response::new(tokio_stream::wrappers::ReceiverStream::new(receiver)))
         }
 
+        async fn get_finalized_blocks_from(
+            &self,
+            request: tonic::Request<crate::grpc2::types::AbsoluteBlockHeight>,
+        ) -> Result<tonic::Response<Self::GetFinalizedBlocksFromStream>, tonic::Status> {
+            if !self.config.service_config().get_finalized_blocks_from {
+                return Err(tonic::Status::unimplemented(
+                    "`GetFinalizedBlocksFrom` is not enabled.",
+                ));
+            }
+            let mut heights = self.subscribe_finalized_heights(Some(request.get_ref().value))?;
+            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            tokio::spawn(async move {
+                loop {
+                    let next = tokio::select! {
+                        // the client is gone
+                        _ = sender.closed() => break,
+                        next = heights.next_block() => next,
+                    };
+                    let block = match next {
+                        Ok(Some(block)) => block,
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = sender.send(Err(e)).await;
+                            break;
+                        }
+                    };
+                    if sender.send(Ok(prost::Message::encode_to_vec(&block))).await.is_err() {
+                        // the client is gone
+                        break;
+                    }
+                }
+            });
+            Ok(tonic::Response::new(tokio_stream::wrappers::ReceiverStream::new(receiver)))
+        }
+
+        async fn get_finalized_transaction_events(
+            &self,
+            request: tonic::Request<crate::grpc2::types::FinalizedTransactionEventsRequest>,
+        ) -> Result<tonic::Response<Self::GetFinalizedTransactionEventsStream>, tonic::Status>
+        {
+            if !self.config.service_config().get_finalized_transaction_events {
+                return Err(tonic::Status::unimplemented(
+                    "`GetFinalizedTransactionEvents` is not enabled.",
+                ));
+            }
+            let request = request.into_inner();
+            let filter = TransactionEventFilter::try_from(&request)?;
+            let mut heights =
+                self.subscribe_finalized_heights(request.start_height.map(|height| height.value))?;
+            let consensus = self.consensus.clone();
+            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            tokio::spawn(async move {
+                loop {
+                    let next = tokio::select! {
+                        // the client is gone
+                        _ = sender.closed() => break,
+                        next = heights.next_block() => next,
+                    };
+                    let events = match next {
+                        Ok(Some(block)) => transaction_events(&consensus, &block, &filter).await,
+                        Ok(None) => break,
+                        Err(e) => Err(e),
+                    };
+                    match events {
+                        Ok(events) => {
+                            for event in events {
+                                if sender.send(Ok(event)).await.is_err() {
+                                    // the client is gone
+                                    return;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let _ = sender.send(Err(e)).await;
+                            break;
+                        }
+                    }
+                }
+            });
+            Ok(tonic::Response::new(tokio_stream::wrappers::ReceiverStream::new(receiver)))
+        }
+
         async fn get_account_info(
             &self,
             request: tonic::Request<crate::grpc2::types::AccountInfoRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_account_info {
+            if !self.config.service_config().get_account_info {
                 return Err(tonic::Status::unimplemented("`GetAccountInfo` is not enabled."));
             }
             let request = request.get_ref();
@@ -1160,7 +1923,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetAccountListStream>, tonic::Status> {
-            if !self.service_config.get_account_list {
+            if !self.config.service_config().get_account_list {
                 return Err(tonic::Status::unimplemented("`GetAccountList` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1174,7 +1937,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetModuleListStream>, tonic::Status> {
-            if !self.service_config.get_module_list {
+            if !self.config.service_config().get_module_list {
                 return Err(tonic::Status::unimplemented("`GetModuleList` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1188,23 +1951,22 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::ModuleSourceRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_module_source {
+            if !self.config.service_config().get_module_source {
                 return Err(tonic::Status::unimplemented("`GetModuleSource` is not enabled."));
             }
             let request = request.get_ref();
             let block_hash = request.block_hash.as_ref().require()?;
             let module_ref = request.module_ref.as_ref().require()?;
-            let (hash, response) = self.consensus.get_module_source_v2(block_hash, module_ref)?;
-            let mut response = tonic::Response::new(response);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            self.cached_query("GetModuleSource", block_hash, request, || {
+                self.consensus.get_module_source_v2(block_hash, module_ref)
+            })
         }
 
         async fn get_instance_list(
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetInstanceListStream>, tonic::Status> {
-            if !self.service_config.get_instance_list {
+            if !self.config.service_config().get_instance_list {
                 return Err(tonic::Status::unimplemented("`GetInstanceList` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1218,7 +1980,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::InstanceInfoRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_instance_info {
+            if !self.config.service_config().get_instance_info {
                 return Err(tonic::Status::unimplemented("`GetInstanceInfo` is not enabled."));
             }
             let request = request.get_ref();
@@ -1235,7 +1997,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::InstanceInfoRequest>,
         ) -> Result<tonic::Response<Self::GetInstanceStateStream>, tonic::Status> {
-            if !self.service_config.get_instance_state {
+            if !self.config.service_config().get_instance_state {
                 return Err(tonic::Status::unimplemented("`GetInstanceState` is not enabled."));
             }
             let request = request.get_ref();
@@ -1300,7 +2062,7 @@ pub mod server {
             &self,
             request: tonic::Request<types::InstanceStateLookupRequest>,
         ) -> Result<tonic::Response<types::InstanceStateValueAtKey>, tonic::Status> {
-            if !self.service_config.instance_state_lookup {
+            if !self.config.service_config().instance_state_lookup {
                 return Err(tonic::Status::unimplemented("`InstanceStateLookup` is not enabled."));
             }
             let request = request.get_ref();
@@ -1342,7 +2104,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::AccountAddress>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_next_account_sequence_number {
+            if !self.config.service_config().get_next_account_sequence_number {
                 return Err(tonic::Status::unimplemented(
                     "`GetNextAccountSequenceNumber` is not enabled.",
                 ));
@@ -1355,7 +2117,7 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_consensus_info {
+            if !self.config.service_config().get_consensus_info {
                 return Err(tonic::Status::unimplemented("`GetConsensusInfo` is not enabled."));
             }
             let response = self.consensus.get_consensus_info_v2()?;
@@ -1366,7 +2128,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::AncestorsRequest>,
         ) -> Result<tonic::Response<Self::GetAncestorsStream>, tonic::Status> {
-            if !self.service_config.get_ancestors {
+            if !self.config.service_config().get_ancestors {
                 return Err(tonic::Status::unimplemented("`GetAncestors` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1383,18 +2145,56 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::TransactionHash>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_block_item_status {
+            if !self.config.service_config().get_block_item_status {
                 return Err(tonic::Status::unimplemented("`GetBlockItemStatus` is not enabled."));
             }
             let response = self.consensus.get_block_item_status_v2(request.get_ref())?;
             Ok(tonic::Response::new(response))
         }
 
+        async fn wait_for_block_item_finalization(
+            &self,
+            request: tonic::Request<crate::grpc2::types::TransactionHash>,
+        ) -> Result<tonic::Response<Self::WaitForBlockItemFinalizationStream>, tonic::Status>
+        {
+            if !self.config.service_config().wait_for_block_item_finalization {
+                return Err(tonic::Status::unimplemented(
+                    "`WaitForBlockItemFinalization` is not enabled.",
+                ));
+            }
+            let mut updates = self.subscribe_block_item_status(request.into_inner())?;
+            // report an unknown block item right away instead of in the stream
+            let first = updates.next().await?;
+            let (sender, receiver) = tokio::sync::mpsc::channel(10);
+            tokio::spawn(async move {
+                let mut next = first;
+                while let Some(status) = next {
+                    if sender.send(Ok(status)).await.is_err() {
+                        // the client is gone
+                        return;
+                    }
+                    let update = tokio::select! {
+                        // the client is gone
+                        _ = sender.closed() => return,
+                        update = updates.next() => update,
+                    };
+                    next = match update {
+                        Ok(next) => next,
+                        Err(e) => {
+                            let _ = sender.send(Err(e)).await;
+                            return;
+                        }
+                    };
+                }
+            });
+            Ok(tonic::Response::new(tokio_stream::wrappers::ReceiverStream::new(receiver)))
+        }
+
         async fn invoke_instance(
             &self,
             request: tonic::Request<crate::grpc2::types::InvokeInstanceRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.invoke_instance {
+            if !self.config.service_config().invoke_instance {
                 return Err(tonic::Status::unimplemented("`InvokeInstance` is not enabled."));
             }
             if request
@@ -1415,12 +2215,25 @@ pub mod server {
             }
         }
 
+        async fn dry_run_transaction(
+            &self,
+            request: tonic::Request<crate::grpc2::types::DryRunTransactionRequest>,
+        ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
+            if !self.config.service_config().dry_run_transaction {
+                return Err(tonic::Status::unimplemented("`DryRunTransaction` is not enabled."));
+            }
+            let (hash, response) = self.consensus.dry_run_transaction_v2(request.into_inner())?;
+            let mut response = tonic::Response::new(response);
+            add_hash(&mut response, hash)?;
+            Ok(response)
+        }
+
         async fn get_cryptographic_parameters(
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<crate::grpc2::types::CryptographicParameters>, tonic::Status>
         {
-            if !self.service_config.get_cryptographic_parameters {
+            if !self.config.service_config().get_cryptographic_parameters {
                 return Err(tonic::Status::unimplemented(
                     "`GetCryptographicParameters` is not enabled.",
                 ));
@@ -1436,20 +2249,20 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_block_info {
+            if !self.config.service_config().get_block_info {
                 return Err(tonic::Status::unimplemented("`GetBlockInfo` is not enabled."));
             }
-            let (hash, response) = self.consensus.get_block_info_v2(request.get_ref())?;
-            let mut response = tonic::Response::new(response);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            let request = request.get_ref();
+            self.cached_query("GetBlockInfo", request, request, || {
+                self.consensus.get_block_info_v2(request)
+            })
         }
 
         async fn get_baker_list(
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetBakerListStream>, tonic::Status> {
-            if !self.service_config.get_baker_list {
+            if !self.config.service_config().get_baker_list {
                 return Err(tonic::Status::unimplemented("`GetBakerList` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1463,7 +2276,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::PoolInfoRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_pool_info {
+            if !self.config.service_config().get_pool_info {
                 return Err(tonic::Status::unimplemented("`GetPoolInfo` is not enabled."));
             }
             let (hash, response) = self.consensus.get_pool_info_v2(request.get_ref())?;
@@ -1476,7 +2289,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_passive_delegation_info {
+            if !self.config.service_config().get_passive_delegation_info {
                 return Err(tonic::Status::unimplemented(
                     "`GetPassiveDelegationInfo` is not enabled.",
                 ));
@@ -1492,7 +2305,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlocksAtHeightRequest>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_blocks_at_height {
+            if !self.config.service_config().get_blocks_at_height {
                 return Err(tonic::Status::unimplemented("`GetBlocksAtHeight` is not enabled."));
             }
             let data = self.consensus.get_blocks_at_height_v2(request.get_ref())?;
@@ -1504,7 +2317,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_tokenomics_info {
+            if !self.config.service_config().get_tokenomics_info {
                 return Err(tonic::Status::unimplemented("`GetTokenomicsInfo` is not enabled."));
             }
             let (hash, response) = self.consensus.get_tokenomics_info_v2(request.get_ref())?;
@@ -1517,7 +2330,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::GetPoolDelegatorsRequest>,
         ) -> Result<tonic::Response<Self::GetPoolDelegatorsStream>, tonic::Status> {
-            if !self.service_config.get_pool_delegators {
+            if !self.config.service_config().get_pool_delegators {
                 return Err(tonic::Status::unimplemented("`GetPoolDelegators` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1532,7 +2345,7 @@ pub mod server {
             request: tonic::Request<crate::grpc2::types::GetPoolDelegatorsRequest>,
         ) -> Result<tonic::Response<Self::GetPoolDelegatorsRewardPeriodStream>, tonic::Status>
         {
-            if !self.service_config.get_pool_delegators_reward_period {
+            if !self.config.service_config().get_pool_delegators_reward_period {
                 return Err(tonic::Status::unimplemented(
                     "`GetPoolDelegatorsRewardPeriod` is not enabled.",
                 ));
@@ -1549,7 +2362,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetPassiveDelegatorsStream>, tonic::Status> {
-            if !self.service_config.get_passive_delegators {
+            if !self.config.service_config().get_passive_delegators {
                 return Err(tonic::Status::unimplemented("`GetPassiveDelegators` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(100);
@@ -1564,7 +2377,7 @@ pub mod server {
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetPassiveDelegatorsRewardPeriodStream>, tonic::Status>
         {
-            if !self.service_config.get_passive_delegators_reward_period {
+            if !self.config.service_config().get_passive_delegators_reward_period {
                 return Err(tonic::Status::unimplemented(
                     "`GetPassiveDelegatorsRewardPeriod` is not enabled.",
                 ));
@@ -1582,7 +2395,7 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_branches {
+            if !self.config.service_config().get_branches {
                 return Err(tonic::Status::unimplemented("`GetBranches` is not enabled."));
             }
             Ok(tonic::Response::new(self.consensus.get_branches_v2()?))
@@ -1592,7 +2405,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_election_info {
+            if !self.config.service_config().get_election_info {
                 return Err(tonic::Status::unimplemented("`GetElectionInfo` is not enabled."));
             }
             let (hash, response) = self.consensus.get_election_info_v2(request.get_ref())?;
@@ -1605,7 +2418,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetIdentityProvidersStream>, tonic::Status> {
-            if !self.service_config.get_identity_providers {
+            if !self.config.service_config().get_identity_providers {
                 return Err(tonic::Status::unimplemented("`GetIdentityProviders` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(10);
@@ -1619,7 +2432,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetAnonymityRevokersStream>, tonic::Status> {
-            if !self.service_config.get_anonymity_revokers {
+            if !self.config.service_config().get_anonymity_revokers {
                 return Err(tonic::Status::unimplemented("`GetAnonymityRevokers` is not enabled."));
             }
             let (sender, receiver) = futures::channel::mpsc::channel(10);
@@ -1634,7 +2447,7 @@ pub mod server {
             request: tonic::Request<crate::grpc2::types::AccountAddress>,
         ) -> Result<tonic::Response<Self::GetAccountNonFinalizedTransactionsStream>, tonic::Status>
         {
-            if !self.service_config.get_account_non_finalized_transactions {
+            if !self.config.service_config().get_account_non_finalized_transactions {
                 return Err(tonic::Status::unimplemented(
                     "`GetAccountNonFinalizedTransactions` is not enabled.",
                 ));
@@ -1649,39 +2462,37 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetBlockTransactionEventsStream>, tonic::Status> {
-            if !self.service_config.get_block_transaction_events {
+            if !self.config.service_config().get_block_transaction_events {
                 return Err(tonic::Status::unimplemented(
                     "`GetBlockTransactionEvents` is not enabled.",
                 ));
             }
-            let (sender, receiver) = futures::channel::mpsc::channel(10);
-            let hash = self.consensus.get_block_transaction_events_v2(request.get_ref(), sender)?;
-            let mut response = tonic::Response::new(receiver);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            let request = request.get_ref();
+            self.cached_stream("GetBlockTransactionEvents", request, request, 10, |sender| {
+                self.consensus.get_block_transaction_events_v2(request, sender)
+            })
         }
 
         async fn get_block_special_events(
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetBlockSpecialEventsStream>, tonic::Status> {
-            if !self.service_config.get_block_special_events {
+            if !self.config.service_config().get_block_special_events {
                 return Err(tonic::Status::unimplemented(
                     "`GetBlockSpecialEvents` is not enabled.",
                 ));
             }
-            let (sender, receiver) = futures::channel::mpsc::channel(10);
-            let hash = self.consensus.get_block_special_events_v2(request.get_ref(), sender)?;
-            let mut response = tonic::Response::new(receiver);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            let request = request.get_ref();
+            self.cached_stream("GetBlockSpecialEvents", request, request, 10, |sender| {
+                self.consensus.get_block_special_events_v2(request, sender)
+            })
         }
 
         async fn get_block_pending_updates(
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetBlockPendingUpdatesStream>, tonic::Status> {
-            if !self.service_config.get_block_pending_updates {
+            if !self.config.service_config().get_block_pending_updates {
                 return Err(tonic::Status::unimplemented(
                     "`GetBlockPendingUpdates` is not enabled.",
                 ));
@@ -1697,7 +2508,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_next_update_sequence_numbers {
+            if !self.config.service_config().get_next_update_sequence_numbers {
                 return Err(tonic::Status::unimplemented(
                     "`GetNextUpdateSequenceNumber` is not enabled.",
                 ));
@@ -1713,7 +2524,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_block_chain_parameters {
+            if !self.config.service_config().get_block_chain_parameters {
                 return Err(tonic::Status::unimplemented(
                     "`GetBlockChainParameters` is not enabled.",
                 ));
@@ -1729,23 +2540,22 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Vec<u8>>, tonic::Status> {
-            if !self.service_config.get_block_finalization_summary {
+            if !self.config.service_config().get_block_finalization_summary {
                 return Err(tonic::Status::unimplemented(
                     "`GetBlockFinalizationSummary` is not enabled.",
                 ));
             }
-            let (hash, response) =
-                self.consensus.get_block_finalization_summary_v2(request.get_ref())?;
-            let mut response = tonic::Response::new(response);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            let request = request.get_ref();
+            self.cached_query("GetBlockFinalizationSummary", request, request, || {
+                self.consensus.get_block_finalization_summary_v2(request)
+            })
         }
 
         async fn shutdown(
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.shutdown {
+            if !self.config.service_config().shutdown {
                 return Err(tonic::Status::unimplemented("`Shutdown` is not enabled."));
             }
             match self.node.close() {
@@ -1758,7 +2568,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::IpSocketAddress>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.peer_connect {
+            if !self.config.service_config().peer_connect {
                 return Err(tonic::Status::unimplemented("`PeerConnect` is not enabled."));
             }
             if self.node.is_network_stopped() {
@@ -1785,7 +2595,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::IpSocketAddress>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.peer_disconnect {
+            if !self.config.service_config().peer_disconnect {
                 return Err(tonic::Status::unimplemented("`PeerDisconnect` is not enabled."));
             }
             if self.node.is_network_stopped() {
@@ -1811,7 +2621,7 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<crate::grpc2::types::BannedPeers>, tonic::Status> {
-            if !self.service_config.get_banned_peers {
+            if !self.config.service_config().get_banned_peers {
                 return Err(tonic::Status::unimplemented("`GetBannedPeers` is not enabled."));
             }
             if let Ok(banned_peers) = self.node.get_banlist() {
@@ -1840,7 +2650,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::PeerToBan>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.ban_peer {
+            if !self.config.service_config().ban_peer {
                 return Err(tonic::Status::unimplemented("`BanPeer` is not enabled."));
             }
             let ip = request.into_inner().ip_address.require()?;
@@ -1860,7 +2670,7 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BannedPeer>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.unban_peer {
+            if !self.config.service_config().unban_peer {
                 return Err(tonic::Status::unimplemented("`UnbanPeer` is not enabled."));
             }
             match request.into_inner().ip_address.require()?.value.parse::<std::net::IpAddr>() {
@@ -1882,16 +2692,21 @@ pub mod server {
         #[cfg(feature = "network_dump")]
         async fn dump_start(
             &self,
-            request: tonic::Request<crate::grpc2::types::DumpRequest>,
+            request: tonic::Request<crate::grpc2::types::DumpStartRequest>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.dump_start {
+            if !self.config.service_config().dump_start {
                 return Err(tonic::Status::unimplemented("`DumpStart` is not enabled."));
             }
-            let file_path = request.get_ref().file.to_owned();
+            let request = request.into_inner();
+            let file_path = request.file;
             if file_path.is_empty() {
                 Err(tonic::Status::invalid_argument("The supplied path must be non-empty"))
             } else {
-                match self.node.activate_dump(&file_path, request.get_ref().raw) {
+                let filter = match request.filter {
+                    Some(filter) => filter.try_into()?,
+                    None => crate::dumper::DumpFilter::default(),
+                };
+                match self.node.activate_dump(&file_path, request.raw, filter) {
                     Ok(_) => Ok(tonic::Response::new(crate::grpc2::types::Empty {})),
                     Err(e) => {
                         Err(tonic::Status::internal(format!("Could not start network dump {}", e)))
@@ -1903,9 +2718,9 @@ pub mod server {
         #[cfg(not(feature = "network_dump"))]
         async fn dump_start(
             &self,
-            _request: tonic::Request<crate::grpc2::types::DumpRequest>,
+            _request: tonic::Request<crate::grpc2::types::DumpStartRequest>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.dump_start {
+            if !self.config.service_config().dump_start {
                 return Err(tonic::Status::unimplemented("`DumpStart` is not enabled."));
             }
             Err(tonic::Status::failed_precondition("Feature \"network_dump\" is not active."))
@@ -1916,7 +2731,7 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.dump_stop {
+            if !self.config.service_config().dump_stop {
                 return Err(tonic::Status::unimplemented("`DumpStop` is not enabled."));
             }
             match self.node.stop_dump() {
@@ -1932,17 +2747,190 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
-            if !self.service_config.dump_stop {
+            if !self.config.service_config().dump_stop {
                 return Err(tonic::Status::unimplemented("`DumpStop` is not enabled."));
             }
             Err(tonic::Status::failed_precondition("Feature \"network_dump\" is not active."))
         }
 
+        async fn get_network_limits(
+            &self,
+            _request: tonic::Request<crate::grpc2::types::Empty>,
+        ) -> Result<tonic::Response<crate::grpc2::types::NetworkLimits>, tonic::Status> {
+            if !self.config.service_config().get_network_limits {
+                return Err(tonic::Status::unimplemented("`GetNetworkLimits` is not enabled."));
+            }
+            Ok(tonic::Response::new(self.node.config.limits().into()))
+        }
+
+        async fn set_network_limits(
+            &self,
+            request: tonic::Request<crate::grpc2::types::SetNetworkLimitsRequest>,
+        ) -> Result<tonic::Response<crate::grpc2::types::NetworkLimits>, tonic::Status> {
+            if !self.config.service_config().set_network_limits {
+                return Err(tonic::Status::unimplemented("`SetNetworkLimits` is not enabled."));
+            }
+            let origin = match request.remote_addr() {
+                Some(addr) => format!("GRPC request from {}", addr),
+                None => "GRPC request".to_owned(),
+            };
+            let limits = request
+                .into_inner()
+                .apply_to(self.node.config.limits(), self.node.self_peer.peer_type)?;
+            self.node
+                .config
+                .set_limits(limits, &origin)
+                .map_err(|e| tonic::Status::invalid_argument(format!("{:#}", e)))?;
+            Ok(tonic::Response::new(limits.into()))
+        }
+
+        async fn set_baker_credentials(
+            &self,
+            request: tonic::Request<crate::grpc2::types::SetBakerCredentialsRequest>,
+        ) -> Result<tonic::Response<crate::grpc2::types::SetBakerCredentialsResponse>, tonic::Status>
+        {
+            if !self.config.service_config().set_baker_credentials {
+                return Err(tonic::Status::unimplemented("`SetBakerCredentials` is not enabled."));
+            }
+            if !self.consensus.is_active() {
+                return Err(tonic::Status::failed_precondition(
+                    "The node was started without baker credentials.",
+                ));
+            }
+            let request = request.into_inner();
+            let start = match (request.epoch, request.genesis_index) {
+                (Some(epoch), Some(genesis_index)) => Some((genesis_index.value, epoch)),
+                (Some(_), None) => {
+                    return Err(tonic::Status::invalid_argument(
+                        "The genesis index of the epoch is missing.",
+                    ))
+                }
+                (None, _) => None,
+            };
+            let data = tokio::fs::read(&request.path).await.map_err(|e| {
+                tonic::Status::invalid_argument(format!(
+                    "Cannot read the baker credentials file: {}",
+                    e
+                ))
+            })?;
+            let credentials = match request.password {
+                Some(password) => {
+                    crate::plugins::consensus::decrypt_baker_credentials(&data, || Ok(password))
+                        .map_err(|e| tonic::Status::invalid_argument(format!("{:#}", e)))?
+                }
+                None => data,
+            };
+            let baker_id = crate::p2p::baker_proof::check_baker_credentials(&credentials)
+                .map_err(|e| tonic::Status::invalid_argument(format!("{:#}", e)))?;
+            // Both reading the best block and switching the credentials call into
+            // consensus, which may block.
+            let consensus = self.consensus.clone();
+            let best_block_epoch =
+                tokio::task::spawn_blocking(move || consensus.best_block_epoch())
+                    .await
+                    .map_err(|e| tonic::Status::internal(format!("{}", e)))?;
+            let result = match start {
+                Some((genesis_index, epoch)) if (genesis_index, epoch.value) > best_block_epoch => {
+                    crate::plugins::consensus::schedule_baker_credentials(
+                        self.node.clone(),
+                        self.consensus.clone(),
+                        credentials,
+                        genesis_index,
+                        epoch.value,
+                    );
+                    types::set_baker_credentials_response::Result::Scheduled(epoch)
+                }
+                _ => {
+                    let node = self.node.clone();
+                    let consensus = self.consensus.clone();
+                    tokio::task::spawn_blocking(move || {
+                        crate::plugins::consensus::switch_baker_credentials(
+                            &node,
+                            &consensus,
+                            &credentials,
+                        )
+                    })
+                    .await
+                    .map_err(|e| tonic::Status::internal(format!("{}", e)))?
+                    .map_err(|e| tonic::Status::invalid_argument(format!("{:#}", e)))?;
+                    types::set_baker_credentials_response::Result::Active(
+                        self.baker_consensus_info(),
+                    )
+                }
+            };
+            Ok(tonic::Response::new(types::SetBakerCredentialsResponse {
+                baker_id: Some(types::BakerId {
+                    value: baker_id,
+                }),
+                result:   Some(result),
+            }))
+        }
+
+        async fn start_baker(
+            &self,
+            _request: tonic::Request<crate::grpc2::types::Empty>,
+        ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
+            if !self.config.service_config().start_baker {
+                return Err(tonic::Status::unimplemented("`StartBaker` is not enabled."));
+            }
+            if !self.consensus.is_active() {
+                return Err(tonic::Status::failed_precondition(
+                    "The node was started without baker credentials.",
+                ));
+            }
+            self.consensus.start_baker();
+            Ok(tonic::Response::new(crate::grpc2::types::Empty {}))
+        }
+
+        async fn stop_baker(
+            &self,
+            _request: tonic::Request<crate::grpc2::types::Empty>,
+        ) -> Result<tonic::Response<crate::grpc2::types::Empty>, tonic::Status> {
+            if !self.config.service_config().stop_baker {
+                return Err(tonic::Status::unimplemented("`StopBaker` is not enabled."));
+            }
+            if !self.consensus.is_active() {
+                return Err(tonic::Status::failed_precondition(
+                    "The node was started without baker credentials.",
+                ));
+            }
+            self.consensus.stop_baker();
+            Ok(tonic::Response::new(crate::grpc2::types::Empty {}))
+        }
+
+        async fn get_baker_status(
+            &self,
+            _request: tonic::Request<crate::grpc2::types::Empty>,
+        ) -> Result<tonic::Response<crate::grpc2::types::BakerStatus>, tonic::Status> {
+            if !self.config.service_config().get_baker_status {
+                return Err(tonic::Status::unimplemented("`GetBakerStatus` is not enabled."));
+            }
+            if !self.consensus.is_active() {
+                return Err(tonic::Status::failed_precondition(
+                    "The node was started without baker credentials.",
+                ));
+            }
+            let (in_baking_committee, _, baker_id) = self.consensus.in_baking_committee();
+            let mut status = types::BakerStatus {
+                baking: self.consensus.is_baking(),
+                baker_id: Some(types::BakerId {
+                    value: baker_id,
+                }),
+                finalizer: self.consensus.in_finalization_committee(),
+                blocks_baked: BLOCKS_BAKED.load(std::sync::atomic::Ordering::Relaxed),
+                finalization_messages_sent: FINALIZATION_MESSAGES_SENT
+                    .load(std::sync::atomic::Ordering::Relaxed),
+                ..Default::default()
+            };
+            status.set_committee_status(in_baking_committee.into());
+            Ok(tonic::Response::new(status))
+        }
+
         async fn get_peers_info(
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<crate::grpc2::types::PeersInfo>, tonic::Status> {
-            if !self.service_config.get_peers_info {
+            if !self.config.service_config().get_peers_info {
                 return Err(tonic::Status::unimplemented("`GetPeersInfo` is not enabled."));
             }
             // we do a clone so we can release the lock quickly.
@@ -2013,7 +3001,7 @@ pub mod server {
             &self,
             _request: tonic::Request<crate::grpc2::types::Empty>,
         ) -> Result<tonic::Response<types::NodeInfo>, tonic::Status> {
-            if !self.service_config.get_node_info {
+            if !self.config.service_config().get_node_info {
                 return Err(tonic::Status::unimplemented("`GetNodeInfo` is not enabled."));
             }
             let peer_version = self.node.get_version();
@@ -2066,56 +3054,7 @@ pub mod server {
                         // protocol on the chain.
                         types::node_info::node::ConsensusStatus::NotRunning(types::Empty {})
                     } else if matches!(self.consensus.consensus_type, ConsensusType::Active) {
-                        let (in_baking_committee, _, bid) = self.consensus.in_baking_committee();
-                        let baker_id = types::BakerId {
-                            value: bid,
-                        };
-
-                        let baker_status = match in_baking_committee {
-                            ConsensusIsInBakingCommitteeResponse::ActiveInCommittee => {
-                                if self.consensus.in_finalization_committee() {
-                                    // The node is configured with baker keys and is an active
-                                    // member of
-                                    // the finalization committee.
-                                    types::node_info::baker_consensus_info::Status::ActiveFinalizerCommitteeInfo(
-                                            types::node_info::baker_consensus_info::ActiveFinalizerCommitteeInfo{})
-                                } else {
-                                    // The node is configured with baker keys and is an active
-                                    // member of the baking
-                                    // committee.
-                                    types::node_info::baker_consensus_info::Status::ActiveBakerCommitteeInfo(
-                                            types::node_info::baker_consensus_info::ActiveBakerCommitteeInfo{})
-                                }
-                            }
-                            ConsensusIsInBakingCommitteeResponse::NotInCommittee => {
-                                // The node is configured with baker keys but is not in the baking
-                                // committee.
-                                types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
-                                        types::node_info::baker_consensus_info::PassiveCommitteeInfo::NotInCommittee.into()
-                                    )
-                            }
-                            ConsensusIsInBakingCommitteeResponse::AddedButNotActiveInCommittee => {
-                                // The node is configured with baker keys and it is a baker, but not
-                                // for the current epoch.
-                                types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
-                                        types::node_info::baker_consensus_info::PassiveCommitteeInfo::AddedButNotActiveInCommittee.into()
-                                    )
-                            }
-                            ConsensusIsInBakingCommitteeResponse::AddedButWrongKeys => {
-                                // The node is configured with baker keys however they do not match
-                                // expected baker keys for the associated account.
-                                types::node_info::baker_consensus_info::Status::PassiveCommitteeInfo(
-                                        types::node_info::baker_consensus_info::PassiveCommitteeInfo::AddedButWrongKeys.into()
-                                    )
-                            }
-                        };
-                        // construct the baker status
-                        types::node_info::node::ConsensusStatus::Active(
-                            types::node_info::BakerConsensusInfo {
-                                baker_id: Some(baker_id),
-                                status:   Some(baker_status),
-                            },
-                        )
+                        types::node_info::node::ConsensusStatus::Active(self.baker_consensus_info())
                     } else {
                         // The node is not configured with baker keys and is participating in
                         // the consensus passively.
@@ -2141,85 +3080,59 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::SendBlockItemRequest>,
         ) -> Result<tonic::Response<crate::grpc2::types::TransactionHash>, tonic::Status> {
-            use ConsensusFfiResponse::*;
-            if !self.service_config.send_block_item {
+            if !self.config.service_config().send_block_item {
                 return Err(tonic::Status::unimplemented("`SendBlockItem` is not enabled."));
             }
+            let hash = send_block_item(&self.node, &self.consensus, request.into_inner())?;
+            Ok(tonic::Response::new(hash))
+        }
 
-            if self.node.is_network_stopped() {
-                return Err(tonic::Status::failed_precondition(
-                    "The network is stopped due to unrecognized protocol update.",
-                ));
-            }
-
-            let transaction_bytes = request.into_inner().get_v0_format()?;
-            if transaction_bytes.len() > crate::configuration::PROTOCOL_MAX_TRANSACTION_SIZE {
-                warn!("Received a transaction that exceeds maximum transaction size.");
-                return Err(tonic::Status::invalid_argument(
-                    "Transaction size exceeds maximum allowed size.",
-                ));
-            }
-            let (transaction_hash, consensus_result) =
-                self.consensus.send_transaction(&transaction_bytes);
-
-            let result = if consensus_result == Success {
-                let mut payload = Vec::with_capacity(1 + transaction_bytes.len());
-                payload.write_u8(PacketType::Transaction as u8)?;
-                payload.write_all(&transaction_bytes)?;
-
-                CALLBACK_QUEUE.send_out_message(ConsensusMessage::new(
-                    MessageType::Outbound(None),
-                    PacketType::Transaction,
-                    Arc::from(payload),
-                    vec![],
-                    None,
-                ))
-            } else {
-                Err(consensus_result.into())
-            };
-
-            let mk_err_response = |code, error| Err(tonic::Status::new(code, error));
-            let mk_err_invalid_argument_response =
-                |error| mk_err_response(tonic::Code::InvalidArgument, error);
-
-            match (result, consensus_result) {
-                (Ok(_), Success) => {
-                    let transaction_hash = match transaction_hash {
-                        Some(h) => h,
-                        None => {
-                            error!("Block item hash not present, but transaction is accepted.");
-                            return Err(tonic::Status::internal(
-                                "Block item hash not present, but transaction is accepted.",
-                            ));
+        async fn send_block_items(
+            &self,
+            request: tonic::Request<tonic::Streaming<crate::grpc2::types::SendBlockItemRequest>>,
+        ) -> Result<tonic::Response<Self::SendBlockItemsStream>, tonic::Status> {
+            use crate::grpc2::types::{send_block_items_response, SendBlockItemsResponse};
+            if !self.config.service_config().send_block_items {
+                return Err(tonic::Status::unimplemented("`SendBlockItems` is not enabled."));
+            }
+            let mut items = request.into_inner();
+            let node = self.node.clone();
+            let consensus = self.consensus.clone();
+            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            tokio::spawn(async move {
+                loop {
+                    let item = match items.message().await {
+                        Ok(Some(item)) => item,
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = sender.send(Err(e)).await;
+                            break;
                         }
                     };
-                    Ok(tonic::Response::new(crate::grpc2::types::TransactionHash {
-                        value: transaction_hash.to_vec(),
-                    }))
-                }
-                (Err(e), Success) => {
-                    warn!("Couldn't put a transaction in the outbound queue due to {:?}", e);
-                    Err(tonic::Status::new(
-                        tonic::Code::Internal,
-                        format!("Couldn't put a transaction in the outbound queue due to {:?}", e),
-                    ))
-                }
-                // the wildcard is always Err as only 'Success' responses from the consensus are
-                // being retransmitted. In other words Ok(_) implies consensus_result == Success
-                (_, DuplicateEntry) => {
-                    mk_err_response(tonic::Code::AlreadyExists, DuplicateEntry.to_string())
-                }
-                (_, ConsensusShutDown) => {
-                    warn!(
-                        "Consensus didn't accept a transaction via RPC due to {:?}",
-                        ConsensusShutDown.to_string()
-                    );
-                    mk_err_invalid_argument_response(ConsensusShutDown.to_string())
-                }
-                (_, consensus_error) => {
-                    mk_err_invalid_argument_response(consensus_error.to_string())
+                    // The next item is only read once there is room for it in the outbound
+                    // queue, so a large batch slows the client down instead of failing.
+                    while CALLBACK_QUEUE.is_outbound_low_priority_full() {
+                        tokio::time::sleep(SEND_BLOCK_ITEMS_BACKOFF).await;
+                    }
+                    let result = match send_block_item(&node, &consensus, item) {
+                        Ok(hash) => send_block_items_response::Result::TransactionHash(hash),
+                        Err(e) => send_block_items_response::Result::Error(
+                            crate::grpc2::types::SendBlockItemError {
+                                code:    e.code() as i32,
+                                message: e.message().to_string(),
+                            },
+                        ),
+                    };
+                    let response = SendBlockItemsResponse {
+                        result: Some(result),
+                    };
+                    if sender.send(Ok(response)).await.is_err() {
+                        // the client is gone
+                        break;
+                    }
                 }
-            }
+            });
+            Ok(tonic::Response::new(tokio_stream::wrappers::ReceiverStream::new(receiver)))
         }
 
         async fn get_account_transaction_sign_hash(
@@ -2227,7 +3140,7 @@ pub mod server {
             request: tonic::Request<crate::grpc2::types::PreAccountTransaction>,
         ) -> Result<tonic::Response<crate::grpc2::types::AccountTransactionSignHash>, tonic::Status>
         {
-            if !self.service_config.get_account_transaction_sign_hash {
+            if !self.config.service_config().get_account_transaction_sign_hash {
                 return Err(tonic::Status::unimplemented(
                     "`GetAccountTransactionSignHash` is not enabled.",
                 ));
@@ -2245,14 +3158,53 @@ pub mod server {
             &self,
             request: tonic::Request<crate::grpc2::types::BlockHashInput>,
         ) -> Result<tonic::Response<Self::GetBlockItemsStream>, tonic::Status> {
-            if !self.service_config.get_block_items {
+            if !self.config.service_config().get_block_items {
                 return Err(tonic::Status::unimplemented("`GetBlockItems` is not enabled."));
             }
-            let (sender, receiver) = futures::channel::mpsc::channel(100);
-            let hash = self.consensus.get_block_items_v2(request.get_ref(), sender)?;
-            let mut response = tonic::Response::new(receiver);
-            add_hash(&mut response, hash)?;
-            Ok(response)
+            let request = request.get_ref();
+            self.cached_stream("GetBlockItems", request, request, 100, |sender| {
+                self.consensus.get_block_items_v2(request, sender)
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn lagging_client_is_told_and_removed() {
+            let event: Arc<[u8]> = Arc::from(vec![1u8]);
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+            let mut client = Subscriber::client(sender);
+            assert_eq!(client.send(&event), (true, false));
+            // the channel is full, so the next two events are dropped
+            assert_eq!(client.send(&event), (true, true));
+            assert_eq!(client.send(&event), (true, true));
+            assert!(matches!(receiver.try_recv(), Ok(Ok(_))));
+            // once there is room the client learns about the three missed events
+            assert_eq!(client.send(&event), (false, true));
+            match receiver.try_recv() {
+                Ok(Err(status)) => {
+                    assert_eq!(status.code(), tonic::Code::DataLoss);
+                    assert!(status.message().contains("3 items"));
+                }
+                _ => panic!("Expected a lag notification."),
+            }
+        }
+
+        #[test]
+        fn progress_subscriber_skips_silently() {
+            let event: Arc<[u8]> = Arc::from(vec![1u8]);
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+            let mut subscriber = Subscriber::progress(sender);
+            assert_eq!(subscriber.send(&event), (true, false));
+            assert_eq!(subscriber.send(&event), (true, false));
+            assert!(matches!(receiver.try_recv(), Ok(Ok(_))));
+            assert_eq!(subscriber.send(&event), (true, false));
+            assert!(matches!(receiver.try_recv(), Ok(Ok(_))));
+            drop(receiver);
+            assert_eq!(subscriber.send(&event), (false, false));
         }
     }
 }
@@ -0,0 +1,158 @@
+//! A bounded cache for the responses to queries about finalized blocks.
+//!
+//! The answer to a query about a finalized block never changes, so it can be
+//! served from memory instead of going through consensus again. Responses are
+//! keyed by the method, the hash of the block and the encoded request, and are
+//! only stored once the block is known to be finalized. When the total size of
+//! the cached responses exceeds the limit, the least recently used ones are
+//! evicted.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+/// The size attributed to an entry in addition to its data, accounting for
+/// the bookkeeping of the cache.
+const ENTRY_OVERHEAD: usize = 128;
+
+/// Identifies the response to a query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    method:  &'static str,
+    block:   [u8; 32],
+    request: Vec<u8>,
+}
+
+impl CacheKey {
+    pub fn new(method: &'static str, block: [u8; 32], request: Vec<u8>) -> Self {
+        CacheKey {
+            method,
+            block,
+            request,
+        }
+    }
+
+    /// The hash of the block the query is about.
+    pub fn block(&self) -> [u8; 32] { self.block }
+}
+
+/// A cached response, with one element for unary methods, and one element per
+/// message for streaming methods.
+pub(crate) type CachedResponse = Arc<[Vec<u8>]>;
+
+struct Entry {
+    response:  CachedResponse,
+    size:      usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<CacheKey, Entry>,
+    /// The keys of the entries by the time they were last used.
+    recency: BTreeMap<u64, CacheKey>,
+    /// Incremented whenever an entry is used.
+    clock:   u64,
+    size:    usize,
+}
+
+/// The cache of query responses.
+pub(crate) struct QueryCache {
+    max_size: usize,
+    entries:  Mutex<Entries>,
+}
+
+impl QueryCache {
+    /// Create a cache that keeps responses of at most the given total size, in
+    /// bytes.
+    pub fn new(max_size: usize) -> Self {
+        QueryCache {
+            max_size,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Look up a response, marking it as recently used.
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().ok()?;
+        entries.clock += 1;
+        let now = entries.clock;
+        let entry = entries.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.last_used, now);
+        let response = entry.response.clone();
+        entries.recency.remove(&previous);
+        entries.recency.insert(now, key.clone());
+        Some(response)
+    }
+
+    /// Store a response, evicting the least recently used ones if needed.
+    /// Responses that are larger than the whole cache are not stored.
+    pub fn insert(&self, key: CacheKey, response: Vec<Vec<u8>>) {
+        let size = ENTRY_OVERHEAD
+            + key.request.len()
+            + response.iter().map(|message| message.len()).sum::<usize>();
+        if size > self.max_size {
+            return;
+        }
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        if entries.entries.contains_key(&key) {
+            return;
+        }
+        while entries.size + size > self.max_size {
+            let oldest = match entries.recency.keys().next() {
+                Some(&oldest) => oldest,
+                None => break,
+            };
+            if let Some(evicted) =
+                entries.recency.remove(&oldest).and_then(|key| entries.entries.remove(&key))
+            {
+                entries.size -= evicted.size;
+            }
+        }
+        entries.clock += 1;
+        let now = entries.clock;
+        entries.size += size;
+        entries.recency.insert(now, key.clone());
+        entries.entries.insert(key, Entry {
+            response: response.into(),
+            size,
+            last_used: now,
+        });
+    }
+
+    /// The total size of the cached responses, in bytes.
+    pub fn size(&self) -> usize { self.entries.lock().map_or(0, |entries| entries.size) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(block: u8) -> CacheKey { CacheKey::new("GetBlockInfo", [block; 32], Vec::new()) }
+
+    #[test]
+    fn least_recently_used_responses_are_evicted() {
+        let cache = QueryCache::new(3 * (ENTRY_OVERHEAD + 100));
+        for block in 0..3 {
+            cache.insert(key(block), vec![vec![block; 100]]);
+        }
+        assert_eq!(cache.size(), 3 * (ENTRY_OVERHEAD + 100));
+        // using the first response makes the second one the oldest
+        assert_eq!(cache.get(&key(0)).unwrap()[0], vec![0; 100]);
+        cache.insert(key(3), vec![vec![3; 60], vec![3; 40]]);
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(0)).is_some());
+        assert!(cache.get(&key(2)).is_some());
+        assert_eq!(cache.get(&key(3)).unwrap().len(), 2);
+        assert_eq!(cache.size(), 3 * (ENTRY_OVERHEAD + 100));
+
+        // responses that don't fit at all are not stored
+        cache.insert(key(4), vec![vec![4; 7 * 100]]);
+        assert!(cache.get(&key(4)).is_none());
+        assert!(cache.get(&key(3)).is_some());
+    }
+}
@@ -0,0 +1,241 @@
+//! The parts of the GRPC V2 server configuration that can be reloaded while
+//! the server is running: the endpoint configuration and the TLS certificate
+//! and private key.
+//!
+//! The files are reloaded when they change, see [watch], and on request, see
+//! [ReloadableConfig::reload]. Requests read the endpoint configuration when
+//! they are handled, and connections use the certificate that is current when
+//! they are accepted. Existing connections are not affected by a new
+//! certificate.
+
+use super::ServiceConfig;
+use crate::{configuration::GRPC2Config, read_or_die, write_or_die};
+use anyhow::Context;
+use futures::Stream;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio_rustls::{rustls, server::TlsStream, TlsAcceptor};
+
+/// How often the files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The TLS configurations built from the certificate and the private key. The
+/// GRPC server and the REST gateway negotiate different protocols.
+#[derive(Clone)]
+struct TlsAcceptors {
+    grpc: TlsAcceptor,
+    rest: TlsAcceptor,
+}
+
+/// The reloadable configuration of the GRPC V2 server.
+pub struct ReloadableConfig {
+    /// The file with the endpoint configuration, if any. Without it all
+    /// endpoints are enabled.
+    endpoint_config: Option<PathBuf>,
+    /// The files with the certificate and the private key, if TLS is enabled.
+    tls_files:       Option<(PathBuf, PathBuf)>,
+    service_config:  RwLock<Arc<ServiceConfig>>,
+    tls:             RwLock<Option<TlsAcceptors>>,
+}
+
+impl ReloadableConfig {
+    /// Load the configuration from the files given in the server
+    /// configuration.
+    pub(crate) fn new(config: &GRPC2Config) -> anyhow::Result<Self> {
+        let tls_files = tls_files(config)?;
+        let service_config = load_service_config(config.endpoint_config.as_deref())?;
+        debug!("GRPC endpoints enabled: {:#?}", service_config);
+        let tls = match tls_files {
+            Some((ref cert_path, ref key_path)) => Some(load_tls(cert_path, key_path)?),
+            None => None,
+        };
+        Ok(ReloadableConfig {
+            endpoint_config: config.endpoint_config.clone(),
+            tls_files,
+            service_config: RwLock::new(Arc::new(service_config)),
+            tls: RwLock::new(tls),
+        })
+    }
+
+    /// The current endpoint configuration.
+    pub(crate) fn service_config(&self) -> Arc<ServiceConfig> {
+        Arc::clone(&read_or_die!(self.service_config))
+    }
+
+    /// Whether the server uses TLS.
+    pub(crate) fn has_tls(&self) -> bool { self.tls_files.is_some() }
+
+    /// The current TLS configuration of the GRPC server, if TLS is enabled.
+    fn grpc_tls(&self) -> Option<TlsAcceptor> {
+        read_or_die!(self.tls).as_ref().map(|tls| tls.grpc.clone())
+    }
+
+    /// The current TLS configuration of the REST gateway, if TLS is enabled.
+    pub(crate) fn rest_tls(&self) -> Option<TlsAcceptor> {
+        read_or_die!(self.tls).as_ref().map(|tls| tls.rest.clone())
+    }
+
+    /// Whether there are any files to reload.
+    pub(crate) fn has_files(&self) -> bool {
+        self.endpoint_config.is_some() || self.tls_files.is_some()
+    }
+
+    /// Load the endpoint configuration and the certificate and private key
+    /// again. If any of them cannot be loaded, the current configuration is
+    /// kept in its entirety.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let service_config = load_service_config(self.endpoint_config.as_deref())?;
+        let tls = match self.tls_files {
+            Some((ref cert_path, ref key_path)) => Some(load_tls(cert_path, key_path)?),
+            None => None,
+        };
+        debug!("GRPC endpoints enabled: {:#?}", service_config);
+        *write_or_die!(self.service_config) = Arc::new(service_config);
+        *write_or_die!(self.tls) = tls;
+        info!("Reloaded the GRPC V2 endpoint configuration and TLS certificate.");
+        Ok(())
+    }
+
+    /// The modification times of the files, to detect changes.
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        let modified =
+            |path: &PathBuf| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        self.endpoint_config
+            .iter()
+            .chain(self.tls_files.iter().flat_map(|(cert, key)| vec![cert, key]))
+            .map(modified)
+            .collect()
+    }
+}
+
+/// Load the endpoint configuration and the certificate and private key given
+/// in the server configuration, without starting the server, and return the
+/// errors that occur.
+pub fn check_files(config: &GRPC2Config) -> Vec<anyhow::Error> {
+    let mut errors = Vec::new();
+    if let Err(e) = load_service_config(config.endpoint_config.as_deref()) {
+        errors.push(e);
+    }
+    match tls_files(config) {
+        Ok(Some((cert_path, key_path))) => {
+            if let Err(e) = load_tls(&cert_path, &key_path) {
+                errors.push(e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => errors.push(e),
+    }
+    errors
+}
+
+/// The files with the certificate and the private key, if TLS is enabled.
+fn tls_files(config: &GRPC2Config) -> anyhow::Result<Option<(PathBuf, PathBuf)>> {
+    match (&config.x509_cert, &config.cert_private_key) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => anyhow::bail!("Private key supplied, but not the certificate."),
+        (Some(_), None) => anyhow::bail!("Certificate supplied, but not the private key."),
+        (Some(cert_path), Some(key_path)) => Ok(Some((cert_path.clone(), key_path.clone()))),
+    }
+}
+
+fn load_service_config(source: Option<&Path>) -> anyhow::Result<ServiceConfig> {
+    match source {
+        Some(source) => ServiceConfig::from_file(source),
+        None => Ok(ServiceConfig::new_all_enabled()),
+    }
+}
+
+fn load_tls(cert_path: &Path, key_path: &Path) -> anyhow::Result<TlsAcceptors> {
+    let cert = std::fs::read(cert_path).context("Unable to read certificate.")?;
+    let key = std::fs::read(key_path).context("Unable to read key.")?;
+    Ok(TlsAcceptors {
+        grpc: tls_acceptor(&cert, &key, b"h2").context("Unable to configure TLS.")?,
+        rest: tls_acceptor(&cert, &key, b"http/1.1")
+            .context("Unable to configure TLS for the REST gateway.")?,
+    })
+}
+
+/// Build a TLS configuration from the PEM encoded certificate chain and
+/// private key, negotiating the given protocol.
+fn tls_acceptor(cert: &[u8], key: &[u8], protocol: &[u8]) -> anyhow::Result<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut &cert[..])?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect::<Vec<_>>();
+    let key = rustls_pemfile::read_all(&mut &key[..])?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("No private key found."))?;
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![protocol.to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Reload the configuration whenever one of its files changes. If reloading
+/// fails, it is retried at every check until it succeeds, so that a
+/// certificate and key that are not replaced at the same time are eventually
+/// picked up.
+pub(crate) async fn watch(config: Arc<ReloadableConfig>) {
+    let mut last_loaded = config.modification_times();
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let modified = config.modification_times();
+        if modified != last_loaded {
+            match config.reload() {
+                Ok(()) => last_loaded = modified,
+                Err(e) => error!("Could not reload the GRPC V2 configuration: {:#}", e),
+            }
+        }
+    }
+}
+
+/// The TLS connections accepted on the listener, using the TLS configuration
+/// that is current when the connection is accepted. Handshakes are done
+/// concurrently, so that a slow client does not hold up the others. The
+/// listener is closed when the stream is dropped.
+pub(crate) fn tls_incoming(
+    listener: tokio::net::TcpListener,
+    config: Arc<ReloadableConfig>,
+) -> impl Stream<Item = std::io::Result<TlsStream<tokio::net::TcpStream>>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(16);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                },
+                _ = sender.closed() => return,
+            };
+            let acceptor = match config.grpc_tls() {
+                Some(acceptor) => acceptor,
+                None => return,
+            };
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = sender.send(Ok(stream)).await;
+                    }
+                    Err(e) => debug!("TLS handshake with GRPC client {} failed: {}", peer, e),
+                }
+            });
+        }
+    });
+    tokio_stream::wrappers::ReceiverStream::new(receiver)
+}
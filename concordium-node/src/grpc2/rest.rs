@@ -0,0 +1,544 @@
+//! A gateway that serves the GRPC V2 queries as HTTP routes with JSON requests
+//! and responses.
+//!
+//! Every method of the `Queries` service is available at `/v2/{Method}`, e.g.,
+//! `/v2/GetConsensusInfo`, and takes the JSON encoding of its request message
+//! as the body. Methods about a block are also available at
+//! `/v2/blocks/{block}/{Method}`, where the block is either `best`,
+//! `last-final`, or a hex encoded block hash. For such routes the block is the
+//! request if the method takes just a block, and otherwise it is used as the
+//! `blockHash` field of the request. Responses use the canonical JSON encoding
+//! of the protobuf messages, and streaming responses are sent as
+//! newline-delimited JSON.
+//!
+//! Requests are answered by calling the same service implementation as the
+//! GRPC server, so the same endpoints are enabled.
+
+use super::{reload::ReloadableConfig, service::queries_server::Queries, types};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use hyper::{body::HttpBody, header, Body, Method, StatusCode};
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{convert::Infallible, sync::Arc};
+
+/// The maximum size of a request body. Requests are small, apart from
+/// transactions, and the largest transactions are well below this limit even
+/// when encoded as JSON.
+const MAX_REQUEST_SIZE: usize = 1 << 20;
+
+/// Serve the gateway on connections from the given listener, using TLS if it
+/// is configured. This only returns if accepting connections fails.
+pub(crate) async fn serve<T: Queries>(
+    service: Arc<T>,
+    listener: tokio::net::TcpListener,
+    config: Arc<ReloadableConfig>,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let service = Arc::clone(&service);
+        let tls = config.rest_tls();
+        tokio::spawn(async move {
+            let handler =
+                hyper::service::service_fn(move |request| handle(Arc::clone(&service), request));
+            let mut http = hyper::server::conn::Http::new();
+            http.http1_only(true);
+            let result = match tls {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => http.serve_connection(stream, handler).await,
+                    Err(e) => {
+                        debug!("TLS handshake with REST client {} failed: {}", peer, e);
+                        return;
+                    }
+                },
+                None => http.serve_connection(stream, handler).await,
+            };
+            if let Err(e) = result {
+                debug!("Connection to REST client {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle<T: Queries>(
+    service: Arc<T>,
+    request: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, Infallible> {
+    if request.method() != Method::GET && request.method() != Method::POST {
+        return Ok(error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &tonic::Status::unimplemented("Only GET and POST requests are supported."),
+        ));
+    }
+    match respond(&*service, request).await {
+        Ok(response) => Ok(response),
+        Err(status) => Ok(error_response(http_status(status.code()), &status)),
+    }
+}
+
+async fn respond<T: Queries>(
+    service: &T,
+    request: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, tonic::Status> {
+    let (method, block) = parse_path(request.uri().path())?;
+    let method = method.to_owned();
+    let body = read_body(request.into_body()).await?;
+    let reply = dispatch(service, &method, Input {
+        body,
+        block,
+    })
+    .await?;
+    Ok(reply.into_response())
+}
+
+/// Split the path of a request into the name of the method and the block it
+/// is about, if any.
+fn parse_path(path: &str) -> Result<(&str, Option<types::BlockHashInput>), tonic::Status> {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["v2", method] => Ok((*method, None)),
+        ["v2", "blocks", block, method] => Ok((*method, Some(parse_block(block)?))),
+        _ => Err(tonic::Status::not_found(format!("No route for `{}`.", path))),
+    }
+}
+
+fn parse_block(block: &str) -> Result<types::BlockHashInput, tonic::Status> {
+    use types::block_hash_input::BlockHashInput::*;
+    let input = match block {
+        "best" => Best(types::Empty::default()),
+        "last-final" => LastFinal(types::Empty::default()),
+        hash => match hex::decode(hash) {
+            Ok(value) if value.len() == 32 => Given(types::BlockHash {
+                value,
+            }),
+            _ => {
+                return Err(tonic::Status::invalid_argument(format!(
+                    "`{}` is not `best`, `last-final` or a block hash.",
+                    block
+                )))
+            }
+        },
+    };
+    Ok(types::BlockHashInput {
+        block_hash_input: Some(input),
+    })
+}
+
+/// Read the JSON body of a request. An empty body stands for an empty object.
+async fn read_body(mut body: Body) -> Result<Value, tonic::Status> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| {
+            tonic::Status::invalid_argument(format!("Could not read the request: {}", e))
+        })?;
+        if bytes.len() + chunk.len() > MAX_REQUEST_SIZE {
+            return Err(tonic::Status::invalid_argument("The request is too large."));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(json!({}));
+    }
+    serde_json::from_slice(&bytes)
+        .map_err(|e| tonic::Status::invalid_argument(format!("Invalid JSON: {}", e)))
+}
+
+/// The input of a method, from the path and the body of the request.
+struct Input {
+    body:  Value,
+    block: Option<types::BlockHashInput>,
+}
+
+impl Input {
+    /// The request of a method that takes just a block.
+    fn block(self) -> Result<tonic::Request<types::BlockHashInput>, tonic::Status> {
+        match self.block {
+            Some(block) => Ok(tonic::Request::new(block)),
+            None => self.request(),
+        }
+    }
+
+    /// The request of any other method. The block in the path, if any, is
+    /// used as the `blockHash` field.
+    fn request<M: DeserializeOwned>(self) -> Result<tonic::Request<M>, tonic::Status> {
+        let mut body = self.body;
+        if let Some(block) = self.block {
+            match body {
+                Value::Object(ref mut fields) => {
+                    fields.insert("blockHash".into(), to_json(&block)?);
+                }
+                _ => {
+                    return Err(tonic::Status::invalid_argument(
+                        "The request must be a JSON object.",
+                    ))
+                }
+            }
+        }
+        serde_json::from_value(body)
+            .map(tonic::Request::new)
+            .map_err(|e| tonic::Status::invalid_argument(format!("Invalid request: {}", e)))
+    }
+}
+
+/// Call the method with the given name.
+async fn dispatch<T: Queries>(
+    service: &T,
+    method: &str,
+    input: Input,
+) -> Result<Reply, tonic::Status> {
+    match method {
+        "GetFinalizedBlocks" => encoded_stream::<types::FinalizedBlockInfo, _, _>(
+            service.get_finalized_blocks(input.request()?).await?,
+        ),
+        "GetBlocks" => encoded_stream::<types::ArrivedBlockInfo, _, _>(
+            service.get_blocks(input.request()?).await?,
+        ),
+        "GetAccountInfo" => {
+            encoded::<types::AccountInfo>(service.get_account_info(input.request()?).await?)
+        }
+        "GetAccountList" => encoded_stream::<types::AccountAddress, _, _>(
+            service.get_account_list(input.block()?).await?,
+        ),
+        "GetModuleList" => {
+            encoded_stream::<types::ModuleRef, _, _>(service.get_module_list(input.block()?).await?)
+        }
+        "GetModuleSource" => encoded::<types::VersionedModuleSource>(
+            service.get_module_source(input.request()?).await?,
+        ),
+        "GetInstanceList" => encoded_stream::<types::ContractAddress, _, _>(
+            service.get_instance_list(input.block()?).await?,
+        ),
+        "GetInstanceInfo" => {
+            encoded::<types::InstanceInfo>(service.get_instance_info(input.request()?).await?)
+        }
+        "GetInstanceState" => message_stream(service.get_instance_state(input.request()?).await?),
+        "InstanceStateLookup" => message(service.instance_state_lookup(input.request()?).await?),
+        "GetNextAccountSequenceNumber" => encoded::<types::NextAccountSequenceNumber>(
+            service.get_next_account_sequence_number(input.request()?).await?,
+        ),
+        "GetConsensusInfo" => {
+            encoded::<types::ConsensusInfo>(service.get_consensus_info(input.request()?).await?)
+        }
+        "GetAncestors" => {
+            encoded_stream::<types::BlockHash, _, _>(service.get_ancestors(input.request()?).await?)
+        }
+        "GetBlockItemStatus" => encoded::<types::BlockItemStatus>(
+            service.get_block_item_status(input.request()?).await?,
+        ),
+        "GetCryptographicParameters" => {
+            message(service.get_cryptographic_parameters(input.block()?).await?)
+        }
+        "GetBlockInfo" => {
+            encoded::<types::BlockInfo>(service.get_block_info(input.block()?).await?)
+        }
+        "GetBakerList" => {
+            encoded_stream::<types::BakerId, _, _>(service.get_baker_list(input.block()?).await?)
+        }
+        "GetPoolInfo" => {
+            encoded::<types::PoolInfoResponse>(service.get_pool_info(input.request()?).await?)
+        }
+        "GetPassiveDelegationInfo" => encoded::<types::PassiveDelegationInfo>(
+            service.get_passive_delegation_info(input.block()?).await?,
+        ),
+        "GetBlocksAtHeight" => encoded::<types::BlocksAtHeightResponse>(
+            service.get_blocks_at_height(input.request()?).await?,
+        ),
+        "GetTokenomicsInfo" => {
+            encoded::<types::TokenomicsInfo>(service.get_tokenomics_info(input.block()?).await?)
+        }
+        "InvokeInstance" => encoded::<types::InvokeInstanceResponse>(
+            service.invoke_instance(input.request()?).await?,
+        ),
+        "GetPoolDelegators" => encoded_stream::<types::DelegatorInfo, _, _>(
+            service.get_pool_delegators(input.request()?).await?,
+        ),
+        "GetPoolDelegatorsRewardPeriod" => {
+            encoded_stream::<types::DelegatorRewardPeriodInfo, _, _>(
+                service.get_pool_delegators_reward_period(input.request()?).await?,
+            )
+        }
+        "GetPassiveDelegators" => encoded_stream::<types::DelegatorInfo, _, _>(
+            service.get_passive_delegators(input.block()?).await?,
+        ),
+        "GetPassiveDelegatorsRewardPeriod" => {
+            encoded_stream::<types::DelegatorRewardPeriodInfo, _, _>(
+                service.get_passive_delegators_reward_period(input.block()?).await?,
+            )
+        }
+        "GetBranches" => encoded::<types::Branch>(service.get_branches(input.request()?).await?),
+        "GetElectionInfo" => {
+            encoded::<types::ElectionInfo>(service.get_election_info(input.block()?).await?)
+        }
+        "GetIdentityProviders" => encoded_stream::<types::IpInfo, _, _>(
+            service.get_identity_providers(input.block()?).await?,
+        ),
+        "GetAnonymityRevokers" => encoded_stream::<types::ArInfo, _, _>(
+            service.get_anonymity_revokers(input.block()?).await?,
+        ),
+        "GetAccountNonFinalizedTransactions" => encoded_stream::<types::TransactionHash, _, _>(
+            service.get_account_non_finalized_transactions(input.request()?).await?,
+        ),
+        "GetBlockTransactionEvents" => encoded_stream::<types::BlockItemSummary, _, _>(
+            service.get_block_transaction_events(input.block()?).await?,
+        ),
+        "GetBlockSpecialEvents" => encoded_stream::<types::BlockSpecialEvent, _, _>(
+            service.get_block_special_events(input.block()?).await?,
+        ),
+        "GetBlockPendingUpdates" => encoded_stream::<types::PendingUpdate, _, _>(
+            service.get_block_pending_updates(input.block()?).await?,
+        ),
+        "GetNextUpdateSequenceNumbers" => encoded::<types::NextUpdateSequenceNumbers>(
+            service.get_next_update_sequence_numbers(input.block()?).await?,
+        ),
+        "GetBlockChainParameters" => encoded::<types::ChainParameters>(
+            service.get_block_chain_parameters(input.block()?).await?,
+        ),
+        "GetBlockFinalizationSummary" => encoded::<types::BlockFinalizationSummary>(
+            service.get_block_finalization_summary(input.block()?).await?,
+        ),
+        "Shutdown" => message(service.shutdown(input.request()?).await?),
+        "SendBlockItem" => message(service.send_block_item(input.request()?).await?),
+        "GetPeersInfo" => message(service.get_peers_info(input.request()?).await?),
+        "PeerConnect" => message(service.peer_connect(input.request()?).await?),
+        "PeerDisconnect" => message(service.peer_disconnect(input.request()?).await?),
+        "GetBannedPeers" => message(service.get_banned_peers(input.request()?).await?),
+        "BanPeer" => message(service.ban_peer(input.request()?).await?),
+        "UnbanPeer" => message(service.unban_peer(input.request()?).await?),
+        "DumpStart" => message(service.dump_start(input.request()?).await?),
+        "DumpStop" => message(service.dump_stop(input.request()?).await?),
+        "GetNodeInfo" => message(service.get_node_info(input.request()?).await?),
+        "GetNetworkLimits" => message(service.get_network_limits(input.request()?).await?),
+        "SetNetworkLimits" => message(service.set_network_limits(input.request()?).await?),
+        "SetBakerCredentials" => message(service.set_baker_credentials(input.request()?).await?),
+        "StartBaker" => message(service.start_baker(input.request()?).await?),
+        "StopBaker" => message(service.stop_baker(input.request()?).await?),
+        "GetBakerStatus" => message(service.get_baker_status(input.request()?).await?),
+        "GetAccountTransactionSignHash" => {
+            message(service.get_account_transaction_sign_hash(input.request()?).await?)
+        }
+        "GetBlockItems" => {
+            encoded_stream::<types::BlockItem, _, _>(service.get_block_items(input.block()?).await?)
+        }
+        "GetFinalizedBlocksFrom" => encoded_stream::<types::FinalizedBlockInfo, _, _>(
+            service.get_finalized_blocks_from(input.request()?).await?,
+        ),
+        "GetFinalizedTransactionEvents" => {
+            encoded_stream::<types::FinalizedTransactionEvent, _, _>(
+                service.get_finalized_transaction_events(input.request()?).await?,
+            )
+        }
+        "DryRunTransaction" => encoded::<types::DryRunTransactionResponse>(
+            service.dry_run_transaction(input.request()?).await?,
+        ),
+        "WaitForBlockItemFinalization" => encoded_stream::<types::BlockItemStatus, _, _>(
+            service.wait_for_block_item_finalization(input.request()?).await?,
+        ),
+        // `SendBlockItems` streams its requests, which does not map to a single
+        // HTTP request. `SendBlockItem` can be used instead.
+        _ => Err(tonic::Status::not_found(format!("Unknown method `{}`.", method))),
+    }
+}
+
+/// The response to a request, along with the hash of the block it is about,
+/// if any.
+struct Reply {
+    block_hash: Option<String>,
+    body:       ReplyBody,
+}
+
+enum ReplyBody {
+    Single(Value),
+    Stream(BoxStream<'static, Result<Value, tonic::Status>>),
+}
+
+impl Reply {
+    fn new<A>(response: &tonic::Response<A>, body: ReplyBody) -> Self {
+        let block_hash = response
+            .metadata()
+            .get("blockhash")
+            .and_then(|hash| hash.to_str().ok())
+            .map(String::from);
+        Reply {
+            block_hash,
+            body,
+        }
+    }
+
+    fn into_response(self) -> hyper::Response<Body> {
+        let mut builder = hyper::Response::builder();
+        if let Some(hash) = self.block_hash {
+            builder = builder.header("blockhash", hash);
+        }
+        let response = match self.body {
+            ReplyBody::Single(value) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(value.to_string())),
+            ReplyBody::Stream(values) => {
+                builder.header(header::CONTENT_TYPE, "application/x-ndjson").body(ndjson(values))
+            }
+        };
+        response.unwrap_or_else(|e| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &tonic::Status::internal(format!("Could not construct the response: {}", e)),
+            )
+        })
+    }
+}
+
+fn to_json<M: Serialize>(message: &M) -> Result<Value, tonic::Status> {
+    serde_json::to_value(message)
+        .map_err(|e| tonic::Status::internal(format!("Could not encode the response: {}", e)))
+}
+
+fn decode_json<M: Message + Default + Serialize>(bytes: &[u8]) -> Result<Value, tonic::Status> {
+    let message = M::decode(bytes)
+        .map_err(|e| tonic::Status::internal(format!("Could not decode the response: {}", e)))?;
+    to_json(&message)
+}
+
+/// A single response that consensus produced in encoded form.
+fn encoded<M: Message + Default + Serialize>(
+    response: tonic::Response<Vec<u8>>,
+) -> Result<Reply, tonic::Status> {
+    let value = decode_json::<M>(response.get_ref())?;
+    Ok(Reply::new(&response, ReplyBody::Single(value)))
+}
+
+/// A single response message.
+fn message<M: Serialize>(response: tonic::Response<M>) -> Result<Reply, tonic::Status> {
+    let value = to_json(response.get_ref())?;
+    Ok(Reply::new(&response, ReplyBody::Single(value)))
+}
+
+/// A stream of responses that were produced in encoded form.
+fn encoded_stream<M, S, B>(response: tonic::Response<S>) -> Result<Reply, tonic::Status>
+where
+    M: Message + Default + Serialize + 'static,
+    S: Stream<Item = Result<B, tonic::Status>> + Send + 'static,
+    B: AsRef<[u8]>, {
+    let mut reply = Reply::new(&response, ReplyBody::Single(Value::Null));
+    let values = response.into_inner().map(|item| decode_json::<M>(item?.as_ref()));
+    reply.body = ReplyBody::Stream(values.boxed());
+    Ok(reply)
+}
+
+/// A stream of response messages.
+fn message_stream<M, S>(response: tonic::Response<S>) -> Result<Reply, tonic::Status>
+where
+    M: Serialize + 'static,
+    S: Stream<Item = Result<M, tonic::Status>> + Send + 'static, {
+    let mut reply = Reply::new(&response, ReplyBody::Single(Value::Null));
+    let values = response.into_inner().map(|item| to_json(&item?));
+    reply.body = ReplyBody::Stream(values.boxed());
+    Ok(reply)
+}
+
+/// Encode a stream of values as newline-delimited JSON. An error ends the
+/// stream, and is sent as a final object with an `error` field.
+fn ndjson(values: BoxStream<'static, Result<Value, tonic::Status>>) -> Body {
+    let lines = values.scan(false, |ended, item| {
+        if *ended {
+            return futures::future::ready(None);
+        }
+        let value = item.unwrap_or_else(|status| {
+            *ended = true;
+            json!({ "error": error_json(&status) })
+        });
+        let mut line = value.to_string().into_bytes();
+        line.push(b'\n');
+        futures::future::ready(Some(Ok::<_, Infallible>(line)))
+    });
+    Body::wrap_stream(lines)
+}
+
+fn error_json(status: &tonic::Status) -> Value {
+    json!({
+        "code": status.code() as i32,
+        "message": status.message(),
+    })
+}
+
+fn error_response(code: StatusCode, status: &tonic::Status) -> hyper::Response<Body> {
+    let mut response = hyper::Response::new(Body::from(error_json(status).to_string()));
+    *response.status_mut() = code;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
+    response
+}
+
+/// The HTTP status that corresponds to a GRPC status code.
+fn http_status(code: tonic::Code) -> StatusCode {
+    use tonic::Code;
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::OutOfRange | Code::FailedPrecondition => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Cancelled | Code::Unknown | Code::Internal | Code::DataLoss => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_name_the_method_and_block() {
+        let (method, block) = parse_path("/v2/GetConsensusInfo").unwrap();
+        assert_eq!(method, "GetConsensusInfo");
+        assert!(block.is_none());
+
+        let hash = "ab".repeat(32);
+        let (method, block) = parse_path(&format!("/v2/blocks/{}/GetBlockInfo/", hash)).unwrap();
+        assert_eq!(method, "GetBlockInfo");
+        assert!(matches!(
+            block.unwrap().block_hash_input,
+            Some(types::block_hash_input::BlockHashInput::Given(types::BlockHash { value }))
+                if value == vec![0xab; 32]
+        ));
+
+        let (_, block) = parse_path("/v2/blocks/last-final/GetAccountList").unwrap();
+        assert!(matches!(
+            block.unwrap().block_hash_input,
+            Some(types::block_hash_input::BlockHashInput::LastFinal(_))
+        ));
+
+        assert_eq!(
+            parse_path("/v2/blocks/ab/GetBlockInfo").unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        assert_eq!(parse_path("/v1/GetBlockInfo").unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn the_block_in_the_path_completes_the_request() {
+        let input = Input {
+            body:  json!({}),
+            block: Some(parse_block("best").unwrap()),
+        };
+        let request = input.request::<types::AccountInfoRequest>().unwrap().into_inner();
+        assert!(matches!(
+            request.block_hash.and_then(|block| block.block_hash_input),
+            Some(types::block_hash_input::BlockHashInput::Best(_))
+        ));
+
+        // methods without a block reject it
+        let input = Input {
+            body:  json!({}),
+            block: Some(parse_block("best").unwrap()),
+        };
+        assert!(input.request::<types::Empty>().is_err());
+    }
+}
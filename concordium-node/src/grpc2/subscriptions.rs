@@ -0,0 +1,513 @@
+//! Subscriptions to finalized blocks and transaction events that are driven by
+//! block height.
+//!
+//! Unlike `GetFinalizedBlocks`, which forwards the notifications from consensus
+//! as they arrive and drops them for clients that can't keep up, these
+//! subscriptions only use the notifications to learn how far finalization has
+//! progressed, and look up every finalized block in turn. A client can hence
+//! start at any height in the past, and a slow client receives every block,
+//! just later.
+//!
+//! The status of a block item is followed the same way: the notifications
+//! only trigger a new look-up of the status.
+
+use super::types;
+use crate::consensus_ffi::consensus::ConsensusContainer;
+use futures::StreamExt;
+use prost::Message;
+use std::{collections::HashSet, convert::TryFrom, sync::Arc};
+
+/// The notifications about finalized blocks, as produced by consensus.
+pub(crate) type Notifications = tokio::sync::mpsc::Receiver<Result<Arc<[u8]>, tonic::Status>>;
+
+/// The height of the last finalized block.
+fn last_finalized_height(consensus: &ConsensusContainer) -> Result<u64, tonic::Status> {
+    let info = types::ConsensusInfo::decode(&consensus.get_consensus_info_v2()?[..])
+        .map_err(|e| tonic::Status::internal(format!("Invalid consensus info: {}", e)))?;
+    Ok(info.last_finalized_block_height.map_or(0, |height| height.value))
+}
+
+/// The hash of the finalized block at the given height.
+fn finalized_block_at(
+    consensus: &ConsensusContainer,
+    height: u64,
+) -> Result<types::BlockHash, tonic::Status> {
+    use types::blocks_at_height_request::{Absolute, BlocksAtHeight};
+    let request = types::BlocksAtHeightRequest {
+        blocks_at_height: Some(BlocksAtHeight::Absolute(Absolute {
+            height: Some(types::AbsoluteBlockHeight {
+                value: height,
+            }),
+        })),
+    };
+    let response =
+        types::BlocksAtHeightResponse::decode(&consensus.get_blocks_at_height_v2(&request)?[..])
+            .map_err(|e| tonic::Status::internal(format!("Invalid blocks at height: {}", e)))?;
+    // there is exactly one block at a finalized height
+    response
+        .blocks
+        .into_iter()
+        .next()
+        .ok_or_else(|| tonic::Status::internal(format!("No finalized block at height {}.", height)))
+}
+
+/// Produces the heights of finalized blocks in order and without gaps,
+/// starting either at the given height or at the first block finalized after
+/// the subscription.
+pub(crate) struct FinalizedHeights {
+    consensus:     ConsensusContainer,
+    notifications: Notifications,
+    /// The next height to produce; it is only known once a block is finalized
+    /// if no start height was given.
+    next:          Option<u64>,
+    /// The height up to which blocks are known to be finalized.
+    finalized:     u64,
+}
+
+impl FinalizedHeights {
+    /// Start producing heights. The notifications must be subscribed to before
+    /// this is called, so that no finalized block is missed.
+    pub fn new(
+        consensus: ConsensusContainer,
+        notifications: Notifications,
+        start: Option<u64>,
+    ) -> Result<Self, tonic::Status> {
+        let finalized = if start.is_some() {
+            last_finalized_height(&consensus)?
+        } else {
+            0
+        };
+        Ok(FinalizedHeights {
+            consensus,
+            notifications,
+            next: start,
+            finalized,
+        })
+    }
+
+    /// Get the next finalized height, waiting for it to be finalized if
+    /// needed. Returns `None` when the notifications end, i.e., when the
+    /// server is shutting down.
+    pub async fn next(&mut self) -> Result<Option<u64>, tonic::Status> {
+        loop {
+            if let Some(next) = self.next {
+                if next <= self.finalized {
+                    self.next = Some(next + 1);
+                    return Ok(Some(next));
+                }
+            }
+            let notification = match self.notifications.recv().await {
+                Some(notification) => notification?,
+                None => return Ok(None),
+            };
+            let height = types::FinalizedBlockInfo::decode(&notification[..])
+                .map_err(|e| tonic::Status::internal(format!("Invalid finalized block: {}", e)))?
+                .height
+                .map_or(0, |height| height.value);
+            // notifications are dropped if the subscriber falls behind, so the
+            // latest one might be missing
+            self.finalized = std::cmp::max(height, last_finalized_height(&self.consensus)?);
+            if self.next.is_none() {
+                self.next = Some(height);
+            }
+        }
+    }
+
+    /// Get the next finalized block.
+    pub async fn next_block(&mut self) -> Result<Option<types::FinalizedBlockInfo>, tonic::Status> {
+        match self.next().await? {
+            Some(height) => Ok(Some(types::FinalizedBlockInfo {
+                hash:   Some(finalized_block_at(&self.consensus, height)?),
+                height: Some(types::AbsoluteBlockHeight {
+                    value: height,
+                }),
+            })),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Produces the changes in the status of a block item, until it is finalized.
+pub(crate) struct BlockItemStatusUpdates {
+    consensus: ConsensusContainer,
+    hash:      types::TransactionHash,
+    /// Notifications about arrived blocks, which might commit the item.
+    blocks:    Notifications,
+    /// Notifications about finalized blocks, which might finalize the item.
+    finalized: Notifications,
+    /// The last status that was produced.
+    last:      Option<Vec<u8>>,
+    /// Whether the finalized status has been produced.
+    done:      bool,
+}
+
+impl BlockItemStatusUpdates {
+    /// Start following the status. The notifications must be subscribed to
+    /// before this is called, so that no change is missed.
+    pub fn new(
+        consensus: ConsensusContainer,
+        hash: types::TransactionHash,
+        blocks: Notifications,
+        finalized: Notifications,
+    ) -> Self {
+        BlockItemStatusUpdates {
+            consensus,
+            hash,
+            blocks,
+            finalized,
+            last: None,
+            done: false,
+        }
+    }
+
+    /// Get the next status of the block item, encoded as a `BlockItemStatus`,
+    /// waiting for it to change if needed. The first call returns the current
+    /// status. Returns `None` once the finalized status has been produced, or
+    /// when the notifications end, i.e., when the server is shutting down.
+    pub async fn next(&mut self) -> Result<Option<Vec<u8>>, tonic::Status> {
+        if self.done {
+            return Ok(None);
+        }
+        loop {
+            let status = self.consensus.get_block_item_status_v2(&self.hash)?;
+            if self.last.as_ref() != Some(&status) {
+                let decoded = types::BlockItemStatus::decode(&status[..]).map_err(|e| {
+                    tonic::Status::internal(format!("Invalid block item status: {}", e))
+                })?;
+                self.done =
+                    matches!(decoded.status, Some(types::block_item_status::Status::Finalized(_)));
+                self.last = Some(status.clone());
+                return Ok(Some(status));
+            }
+            // the notifications themselves are not needed, only the fact that
+            // something happened
+            let notification = tokio::select! {
+                notification = self.blocks.recv() => notification,
+                notification = self.finalized.recv() => notification,
+            };
+            match notification {
+                Some(notification) => {
+                    notification?;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The kinds of transaction events that can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum EventType {
+    AccountCreation,
+    ChainUpdate,
+    Rejected,
+    ModuleDeployed,
+    ContractInitialized,
+    ContractUpdated,
+    Transfer,
+    Other,
+}
+
+impl TryFrom<i32> for EventType {
+    type Error = tonic::Status;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        use types::TransactionEventType as T;
+        match T::from_i32(value) {
+            Some(T::AccountCreation) => Ok(EventType::AccountCreation),
+            Some(T::ChainUpdate) => Ok(EventType::ChainUpdate),
+            Some(T::Rejected) => Ok(EventType::Rejected),
+            Some(T::ModuleDeployed) => Ok(EventType::ModuleDeployed),
+            Some(T::ContractInitialized) => Ok(EventType::ContractInitialized),
+            Some(T::ContractUpdated) => Ok(EventType::ContractUpdated),
+            Some(T::Transfer) => Ok(EventType::Transfer),
+            Some(T::Other) => Ok(EventType::Other),
+            None => Err(tonic::Status::invalid_argument("Unknown transaction event type.")),
+        }
+    }
+}
+
+/// The accounts and contracts a transaction affects, and the kind of its
+/// outcome.
+#[derive(Debug)]
+struct Involvement<'a> {
+    event_type: EventType,
+    accounts:   Vec<&'a [u8]>,
+    contracts:  Vec<(u64, u64)>,
+}
+
+fn contract(address: &Option<types::ContractAddress>) -> Option<(u64, u64)> {
+    address.as_ref().map(|address| (address.index, address.subindex))
+}
+
+fn account(address: &Option<types::AccountAddress>) -> Option<&[u8]> {
+    address.as_ref().map(|address| &address.value[..])
+}
+
+/// The number of leading bytes shared by all the aliases of an account.
+const ACCOUNT_ALIAS_PREFIX_LEN: usize = 29;
+
+/// The part of an account address that identifies the account, regardless of
+/// which of its aliases is used.
+fn alias_prefix(address: &[u8]) -> &[u8] {
+    &address[..std::cmp::min(address.len(), ACCOUNT_ALIAS_PREFIX_LEN)]
+}
+
+impl<'a> Involvement<'a> {
+    fn of(summary: &'a types::BlockItemSummary) -> Self {
+        use types::{
+            account_transaction_effects::Effect, block_item_summary::Details,
+            contract_trace_element::Element,
+        };
+        let mut involvement = Involvement {
+            event_type: EventType::Other,
+            accounts:   Vec::new(),
+            contracts:  Vec::new(),
+        };
+        match &summary.details {
+            Some(Details::AccountCreation(details)) => {
+                involvement.event_type = EventType::AccountCreation;
+                involvement.accounts.extend(account(&details.address));
+            }
+            Some(Details::Update(_)) => involvement.event_type = EventType::ChainUpdate,
+            Some(Details::AccountTransaction(details)) => {
+                involvement.accounts.extend(account(&details.sender));
+                match details.effects.as_ref().and_then(|effects| effects.effect.as_ref()) {
+                    Some(Effect::None(_)) => involvement.event_type = EventType::Rejected,
+                    Some(Effect::ModuleDeployed(_)) => {
+                        involvement.event_type = EventType::ModuleDeployed
+                    }
+                    Some(Effect::ContractInitialized(event)) => {
+                        involvement.event_type = EventType::ContractInitialized;
+                        involvement.contracts.extend(contract(&event.address));
+                    }
+                    Some(Effect::ContractUpdateIssued(update)) => {
+                        involvement.event_type = EventType::ContractUpdated;
+                        for element in update.effects.iter().filter_map(|e| e.element.as_ref()) {
+                            match element {
+                                Element::Updated(event) => {
+                                    involvement.contracts.extend(contract(&event.address))
+                                }
+                                Element::Transferred(transfer) => {
+                                    involvement.contracts.extend(contract(&transfer.sender));
+                                    involvement.accounts.extend(account(&transfer.receiver));
+                                }
+                                Element::Interrupted(event) => {
+                                    involvement.contracts.extend(contract(&event.address))
+                                }
+                                Element::Resumed(event) => {
+                                    involvement.contracts.extend(contract(&event.address))
+                                }
+                                Element::Upgraded(event) => {
+                                    involvement.contracts.extend(contract(&event.address))
+                                }
+                            }
+                        }
+                    }
+                    Some(Effect::AccountTransfer(transfer)) => {
+                        involvement.event_type = EventType::Transfer;
+                        involvement.accounts.extend(account(&transfer.receiver));
+                    }
+                    Some(Effect::TransferredWithSchedule(transfer)) => {
+                        involvement.event_type = EventType::Transfer;
+                        involvement.accounts.extend(account(&transfer.receiver));
+                    }
+                    Some(Effect::EncryptedAmountTransferred(transfer)) => {
+                        involvement.event_type = EventType::Transfer;
+                        involvement.accounts.extend(
+                            transfer.added.as_ref().and_then(|added| account(&added.receiver)),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+        involvement
+    }
+}
+
+/// Selects the transactions whose events are sent to a subscriber. Empty
+/// sets impose no restrictions; a transaction matches if it involves any of
+/// the accounts or contracts, and its outcome is of one of the event types.
+/// An account is involved whichever of its aliases the transaction uses.
+#[derive(Debug, Default)]
+pub(crate) struct TransactionEventFilter {
+    /// The alias prefixes of the accounts.
+    accounts:    HashSet<Vec<u8>>,
+    contracts:   HashSet<(u64, u64)>,
+    event_types: HashSet<EventType>,
+}
+
+impl TransactionEventFilter {
+    fn matches(&self, summary: &types::BlockItemSummary) -> bool {
+        let involvement = Involvement::of(summary);
+        if !self.event_types.is_empty() && !self.event_types.contains(&involvement.event_type) {
+            return false;
+        }
+        if self.accounts.is_empty() && self.contracts.is_empty() {
+            return true;
+        }
+        involvement.accounts.iter().any(|account| self.accounts.contains(alias_prefix(account)))
+            || involvement.contracts.iter().any(|contract| self.contracts.contains(contract))
+    }
+}
+
+impl TryFrom<&types::FinalizedTransactionEventsRequest> for TransactionEventFilter {
+    type Error = tonic::Status;
+
+    fn try_from(request: &types::FinalizedTransactionEventsRequest) -> Result<Self, Self::Error> {
+        Ok(TransactionEventFilter {
+            accounts:    request
+                .accounts
+                .iter()
+                .map(|account| alias_prefix(&account.value).to_vec())
+                .collect(),
+            contracts:   request
+                .contracts
+                .iter()
+                .map(|contract| (contract.index, contract.subindex))
+                .collect(),
+            event_types: request
+                .event_types
+                .iter()
+                .map(|&event_type| EventType::try_from(event_type))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Get the summaries of the transactions in the given finalized block that
+/// match the filter, wrapped as events ready to be sent to the subscriber.
+pub(crate) async fn transaction_events(
+    consensus: &ConsensusContainer,
+    block: &types::FinalizedBlockInfo,
+    filter: &TransactionEventFilter,
+) -> Result<Vec<Vec<u8>>, tonic::Status> {
+    let block_hash = block.hash.clone();
+    let request = types::BlockHashInput {
+        block_hash_input: block_hash.clone().map(types::block_hash_input::BlockHashInput::Given),
+    };
+    let (sender, mut receiver) = futures::channel::mpsc::channel(10);
+    consensus.get_block_transaction_events_v2(&request, sender)?;
+    let mut events = Vec::new();
+    while let Some(summary) = receiver.next().await {
+        let summary = types::BlockItemSummary::decode(&summary?[..])
+            .map_err(|e| tonic::Status::internal(format!("Invalid transaction summary: {}", e)))?;
+        if filter.matches(&summary) {
+            let event = types::FinalizedTransactionEvent {
+                block_hash:   block_hash.clone(),
+                block_height: block.height.clone(),
+                summary:      Some(summary),
+            };
+            events.push(event.encode_to_vec());
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{
+        account_transaction_effects::{AccountTransfer, ContractUpdateIssued, Effect},
+        block_item_summary::Details,
+        contract_trace_element::{Element, Transferred},
+        AccountTransactionDetails, AccountTransactionEffects, ContractTraceElement,
+    };
+
+    fn address(byte: u8) -> Option<types::AccountAddress> {
+        Some(types::AccountAddress {
+            value: vec![byte; 32],
+        })
+    }
+
+    fn accounts(bytes: &[u8]) -> HashSet<Vec<u8>> {
+        bytes.iter().map(|&byte| alias_prefix(&[byte; 32]).to_vec()).collect()
+    }
+
+    fn summary(sender: u8, effect: Effect) -> types::BlockItemSummary {
+        types::BlockItemSummary {
+            details: Some(Details::AccountTransaction(AccountTransactionDetails {
+                sender: address(sender),
+                effects: Some(AccountTransactionEffects {
+                    effect: Some(effect),
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transaction_event_filter() {
+        let transfer = summary(
+            1,
+            Effect::AccountTransfer(AccountTransfer {
+                receiver: address(2),
+                ..Default::default()
+            }),
+        );
+        let contract_payout = summary(
+            3,
+            Effect::ContractUpdateIssued(ContractUpdateIssued {
+                effects: vec![ContractTraceElement {
+                    element: Some(Element::Transferred(Transferred {
+                        sender: Some(types::ContractAddress {
+                            index:    7,
+                            subindex: 0,
+                        }),
+                        receiver: address(4),
+                        ..Default::default()
+                    })),
+                }],
+            }),
+        );
+
+        let everything = TransactionEventFilter::default();
+        assert!(everything.matches(&transfer) && everything.matches(&contract_payout));
+
+        let receiver = TransactionEventFilter {
+            accounts: accounts(&[2]),
+            ..Default::default()
+        };
+        assert!(receiver.matches(&transfer));
+        assert!(!receiver.matches(&contract_payout));
+
+        let payee = TransactionEventFilter {
+            accounts: accounts(&[4]),
+            ..Default::default()
+        };
+        assert!(payee.matches(&contract_payout));
+
+        let instance = TransactionEventFilter {
+            contracts: vec![(7, 0)].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(!instance.matches(&transfer));
+        assert!(instance.matches(&contract_payout));
+
+        let transfers_of_sender = TransactionEventFilter {
+            accounts: accounts(&[1, 3]),
+            event_types: vec![EventType::Transfer].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(transfers_of_sender.matches(&transfer));
+        assert!(!transfers_of_sender.matches(&contract_payout));
+
+        // a transfer to an alias of the account
+        let mut alias = vec![2; 32];
+        alias[31] = 9;
+        let to_alias = summary(
+            1,
+            Effect::AccountTransfer(AccountTransfer {
+                receiver: Some(types::AccountAddress {
+                    value: alias,
+                }),
+                ..Default::default()
+            }),
+        );
+        assert!(receiver.matches(&to_alias));
+        assert!(!payee.matches(&to_alias));
+    }
+}
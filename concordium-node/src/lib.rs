@@ -23,6 +23,8 @@ pub mod network;
 pub mod p2p;
 pub mod plugins;
 
+#[cfg(feature = "network_dump")]
+pub mod capture;
 #[cfg(feature = "network_dump")]
 pub mod dumper;
 pub mod rpc;
@@ -34,6 +36,9 @@ pub mod consensus_ffi;
 #[cfg(any(test, bench, feature = "test_utils"))]
 pub mod test_utils;
 
+#[cfg(any(test, bench, feature = "test_utils"))]
+pub mod fault_injection;
+
 pub mod flatbuffers_shim;
 
 #[cfg(target_os = "macos")]
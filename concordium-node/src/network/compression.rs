@@ -0,0 +1,258 @@
+//! Compression of serialized network messages.
+//!
+//! Compression is negotiated per connection as part of the handshake
+//! capabilities. A compressed message is sent in place of the serialized
+//! `NetworkMessage` and is prefixed with a header that can't be mistaken for
+//! the size prefix of a valid flatbuffers message, so compressed and plain
+//! messages can be mixed on the same connection.
+
+use crate::{
+    configuration::PROTOCOL_MAX_MESSAGE_SIZE, network::Capabilities,
+    stats_export_service::StatsExportService,
+};
+use anyhow::{bail, ensure, Context};
+use std::{convert::TryFrom, fmt, sync::Arc, time::Instant};
+
+/// The first bytes of a compressed message. Read as the little-endian size
+/// prefix of a flatbuffers message, they exceed `PROTOCOL_MAX_MESSAGE_SIZE`.
+const COMPRESSED_MESSAGE_MAGIC: [u8; 4] = *b"\xffCMP";
+
+/// The header of a compressed message consists of the magic bytes, the
+/// algorithm tag and the big-endian length of the uncompressed message.
+const ALGORITHM_OFFSET: usize = COMPRESSED_MESSAGE_MAGIC.len();
+const LENGTH_OFFSET: usize = ALGORITHM_OFFSET + 1;
+const COMPRESSED_HEADER_SIZE: usize = LENGTH_OFFSET + 4;
+
+/// The zstd compression level; the default level offers a good trade-off
+/// between speed and compression ratio.
+const ZSTD_LEVEL: i32 = 3;
+
+/// The supported compression algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    Zstd,
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// The algorithms in order of preference.
+    const PREFERENCE: [CompressionAlgorithm; 2] =
+        [CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4];
+
+    /// Choose the algorithm to use on a connection with the given negotiated
+    /// capabilities, if any.
+    pub fn negotiate(capabilities: Capabilities) -> Option<Self> {
+        Self::PREFERENCE
+            .iter()
+            .copied()
+            .find(|algorithm| capabilities.contains(algorithm.capability()))
+    }
+
+    /// The capability a peer advertises if it supports the algorithm.
+    pub fn capability(self) -> Capabilities {
+        match self {
+            CompressionAlgorithm::Zstd => Capabilities::COMPRESSION_ZSTD,
+            CompressionAlgorithm::Lz4 => Capabilities::COMPRESSION_LZ4,
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            CompressionAlgorithm::Zstd => 0,
+            CompressionAlgorithm::Lz4 => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> anyhow::Result<Self> {
+        match tag {
+            0 => Ok(CompressionAlgorithm::Zstd),
+            1 => Ok(CompressionAlgorithm::Lz4),
+            _ => bail!("Unknown compression algorithm: {}", tag),
+        }
+    }
+}
+
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionAlgorithm::Zstd => write!(f, "zstd"),
+            CompressionAlgorithm::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+/// Check whether a message received from the network is compressed.
+pub fn is_compressed(message: &[u8]) -> bool { message.starts_with(&COMPRESSED_MESSAGE_MAGIC) }
+
+/// Compress a serialized network message, prefixing it with the header.
+pub fn compress(algorithm: CompressionAlgorithm, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let len = u32::try_from(message.len()).context("The message is too large to compress.")?;
+    let compressed = match algorithm {
+        CompressionAlgorithm::Zstd => zstd::bulk::compress(message, ZSTD_LEVEL)?,
+        CompressionAlgorithm::Lz4 => lz4_flex::block::compress(message),
+    };
+    let mut output = Vec::with_capacity(COMPRESSED_HEADER_SIZE + compressed.len());
+    output.extend_from_slice(&COMPRESSED_MESSAGE_MAGIC);
+    output.push(algorithm.tag());
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(&compressed);
+    Ok(output)
+}
+
+/// Decompress a message received from the network. The declared size of the
+/// uncompressed message is bounded by `PROTOCOL_MAX_MESSAGE_SIZE`, and the
+/// decompressed output is never allowed to exceed it.
+pub fn decompress(message: &[u8]) -> anyhow::Result<(CompressionAlgorithm, Vec<u8>)> {
+    ensure!(
+        is_compressed(message) && message.len() >= COMPRESSED_HEADER_SIZE,
+        "Malformed compressed message header."
+    );
+    let algorithm = CompressionAlgorithm::from_tag(message[ALGORITHM_OFFSET])?;
+    let mut len_bytes = [0u8; 4];
+    len_bytes.copy_from_slice(&message[LENGTH_OFFSET..COMPRESSED_HEADER_SIZE]);
+    let len = u32::from_be_bytes(len_bytes);
+    ensure!(
+        len <= PROTOCOL_MAX_MESSAGE_SIZE,
+        "The declared size of a compressed message ({}B) exceeds the maximum ({}B).",
+        len,
+        PROTOCOL_MAX_MESSAGE_SIZE
+    );
+    let len = len as usize;
+    let payload = &message[COMPRESSED_HEADER_SIZE..];
+    let decompressed = match algorithm {
+        CompressionAlgorithm::Zstd => zstd::bulk::decompress(payload, len)?,
+        CompressionAlgorithm::Lz4 => lz4_flex::block::decompress(payload, len)?,
+    };
+    ensure!(
+        decompressed.len() == len,
+        "The size of a decompressed message ({}B) doesn't match the declared one ({}B).",
+        decompressed.len(),
+        len
+    );
+    Ok((algorithm, decompressed))
+}
+
+/// A serialized network message to be sent over a number of connections. The
+/// compressed forms of the message are computed lazily, at most once per
+/// algorithm, and only if some of the connections negotiated compression.
+pub struct OutgoingMessage {
+    plain:      Arc<[u8]>,
+    /// Whether the message is large enough to be worth compressing.
+    compress:   bool,
+    compressed: Vec<(CompressionAlgorithm, Arc<[u8]>)>,
+}
+
+impl OutgoingMessage {
+    /// Wrap a serialized message; it is only compressed if it is at least
+    /// `threshold` bytes long.
+    pub fn new(message: &[u8], threshold: usize) -> Self {
+        Self {
+            plain:      Arc::from(message),
+            compress:   message.len() >= threshold,
+            compressed: Vec::new(),
+        }
+    }
+
+    /// The form of the message to send over a connection with the given
+    /// negotiated capabilities. The plain message is returned if compression
+    /// wasn't negotiated, fails, or doesn't make the message smaller.
+    pub fn for_capabilities(
+        &mut self,
+        capabilities: Capabilities,
+        stats: &StatsExportService,
+    ) -> Arc<[u8]> {
+        if !self.compress {
+            return Arc::clone(&self.plain);
+        }
+        let algorithm = match CompressionAlgorithm::negotiate(capabilities) {
+            Some(algorithm) => algorithm,
+            None => return Arc::clone(&self.plain),
+        };
+        let message =
+            if let Some((_, message)) = self.compressed.iter().find(|(alg, _)| *alg == algorithm) {
+                Arc::clone(message)
+            } else {
+                let start = Instant::now();
+                let result = compress(algorithm, &self.plain);
+                stats.compression_time_add(start.elapsed().as_secs_f64());
+                let message = match result {
+                    Ok(compressed) if compressed.len() < self.plain.len() => Arc::from(compressed),
+                    Ok(_) => Arc::clone(&self.plain),
+                    Err(e) => {
+                        warn!("Couldn't compress a message with {}: {}", algorithm, e);
+                        Arc::clone(&self.plain)
+                    }
+                };
+                self.compressed.push((algorithm, Arc::clone(&message)));
+                message
+            };
+        stats.compression_bytes_saved_sent_add((self.plain.len() - message.len()) as u64);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressible_message() -> Vec<u8> { (0..64 * 1024u32).map(|i| (i % 251) as u8).collect() }
+
+    #[test]
+    fn compression_roundtrip() -> anyhow::Result<()> {
+        let message = compressible_message();
+        for algorithm in CompressionAlgorithm::PREFERENCE.iter().copied() {
+            let compressed = compress(algorithm, &message)?;
+            assert!(is_compressed(&compressed));
+            assert!(compressed.len() < message.len());
+            assert_eq!(decompress(&compressed)?, (algorithm, message.clone()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_messages_are_not_flatbuffers_messages() {
+        let size_prefix = u32::from_le_bytes(COMPRESSED_MESSAGE_MAGIC);
+        assert!(size_prefix > PROTOCOL_MAX_MESSAGE_SIZE);
+    }
+
+    #[test]
+    fn decompression_is_bounded() -> anyhow::Result<()> {
+        let message = compressible_message();
+        for algorithm in CompressionAlgorithm::PREFERENCE.iter().copied() {
+            let mut compressed = compress(algorithm, &message)?;
+            // a declared size above the maximum is rejected upfront
+            compressed[LENGTH_OFFSET..COMPRESSED_HEADER_SIZE]
+                .copy_from_slice(&(PROTOCOL_MAX_MESSAGE_SIZE + 1).to_be_bytes());
+            assert!(decompress(&compressed).is_err());
+            // and so is output that exceeds the declared size
+            compressed[LENGTH_OFFSET..COMPRESSED_HEADER_SIZE]
+                .copy_from_slice(&(message.len() as u32 - 1).to_be_bytes());
+            assert!(decompress(&compressed).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn outgoing_message_forms() -> anyhow::Result<()> {
+        let stats = StatsExportService::new()?;
+        let message = compressible_message();
+
+        let mut small = OutgoingMessage::new(&message, message.len() + 1);
+        let all = Capabilities::COMPRESSION_ZSTD.union(Capabilities::COMPRESSION_LZ4);
+        assert_eq!(&small.for_capabilities(all, &stats)[..], &message[..]);
+
+        let mut large = OutgoingMessage::new(&message, message.len());
+        assert_eq!(&large.for_capabilities(Capabilities::empty(), &stats)[..], &message[..]);
+        let zstd = large.for_capabilities(all, &stats);
+        assert_eq!(decompress(&zstd)?.0, CompressionAlgorithm::Zstd);
+        let lz4 = large.for_capabilities(Capabilities::COMPRESSION_LZ4, &stats);
+        assert_eq!(decompress(&lz4)?.0, CompressionAlgorithm::Lz4);
+        // each form is only computed once
+        assert!(Arc::ptr_eq(&zstd, &large.for_capabilities(all, &stats)));
+        assert_eq!(
+            stats.get_compression_bytes_saved_sent(),
+            2 * (message.len() - zstd.len()) as u64 + (message.len() - lz4.len()) as u64
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 //! Network-related objects.
 
 pub mod buckets;
+pub mod compression;
 pub mod serialization;
 
 pub use self::buckets::Buckets;
@@ -8,10 +9,11 @@ use crate::common::{
     p2p_peer::{P2PPeer, RemotePeerId},
     P2PNodeId,
 };
+use anyhow::bail;
 use concordium_base::hashes::BlockHash;
 use nohash_hasher::BuildNoHashHasher;
 use semver::Version;
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt, str::FromStr};
 
 /// Wire protocol version number. Nodes must agree on a common wire protocol in
 /// order to communicate. This should be the highest protocol version supported
@@ -27,6 +29,96 @@ pub const WIRE_PROTOCOL_CURRENT_VERSION: WireProtocolVersion = 1;
 /// The supported write protocol versions in descending order.
 pub const WIRE_PROTOCOL_VERSIONS: [WireProtocolVersion; 1] = [WIRE_PROTOCOL_CURRENT_VERSION];
 
+/// A set of optional protocol features. Nodes advertise the capabilities
+/// they support in the handshake, and a feature may only be used on a
+/// connection if both ends advertised it. This allows new features to be
+/// rolled out gradually, without all nodes having to upgrade at once.
+///
+/// Bits that are not known to this version are kept when deserializing, but
+/// they never survive the negotiation since we don't advertise them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    /// Compression of network messages with lz4.
+    pub const COMPRESSION_LZ4: Capabilities = Capabilities(1 << 3);
+    /// Compression of network messages with zstd.
+    pub const COMPRESSION_ZSTD: Capabilities = Capabilities(1 << 0);
+    /// All the capabilities known to this version, along with their names.
+    pub const KNOWN: [(&'static str, Capabilities); 3] = [
+        ("compression-zstd", Self::COMPRESSION_ZSTD),
+        ("compression-lz4", Self::COMPRESSION_LZ4),
+        ("transaction-pull-relay", Self::TRANSACTION_PULL_RELAY),
+    ];
+    /// Announcing transactions by hash and letting peers request them.
+    pub const TRANSACTION_PULL_RELAY: Capabilities = Capabilities(1 << 1);
+
+    /// The empty set of capabilities.
+    pub const fn empty() -> Self { Capabilities(0) }
+
+    /// Create a set of capabilities from its bit representation.
+    pub const fn from_bits(bits: u64) -> Self { Capabilities(bits) }
+
+    /// The bit representation of the set.
+    pub const fn bits(self) -> u64 { self.0 }
+
+    /// Check whether the set contains no capabilities.
+    pub const fn is_empty(self) -> bool { self.0 == 0 }
+
+    /// Check whether all the capabilities in `other` are in the set.
+    pub const fn contains(self, other: Capabilities) -> bool { self.0 & other.0 == other.0 }
+
+    /// The capabilities that are in both sets.
+    pub const fn intersection(self, other: Capabilities) -> Self { Capabilities(self.0 & other.0) }
+
+    /// The capabilities that are in either set.
+    pub const fn union(self, other: Capabilities) -> Self { Capabilities(self.0 | other.0) }
+
+    /// The capabilities in the set that are not in `other`.
+    pub const fn difference(self, other: Capabilities) -> Self { Capabilities(self.0 & !other.0) }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let mut unknown = *self;
+        let mut names = Vec::new();
+        for (name, capability) in Self::KNOWN.iter() {
+            if self.contains(*capability) {
+                names.push((*name).to_owned());
+                unknown = unknown.difference(*capability);
+            }
+        }
+        if !unknown.is_empty() {
+            names.push(format!("{:#x}", unknown.bits()));
+        }
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl FromStr for Capabilities {
+    type Err = anyhow::Error;
+
+    /// Parse the name of a single capability.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::KNOWN.iter().find(|(name, _)| *name == s) {
+            Some((_, capability)) => Ok(*capability),
+            None => bail!("Unknown capability: {}", s),
+        }
+    }
+}
+
+/// The capabilities implemented by this version of the node.
+pub const SUPPORTED_CAPABILITIES: Capabilities = Capabilities::COMPRESSION_ZSTD
+    .union(Capabilities::COMPRESSION_LZ4)
+    .union(Capabilities::TRANSACTION_PULL_RELAY);
+
+/// The SHA256 digest of a transaction packet, which identifies it in the
+/// pull-based transaction relay.
+pub type TransactionDigest = [u8; 32];
+
 /// Identifies a network.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct NetworkId {
@@ -85,6 +177,8 @@ pub struct Handshake {
     pub wire_versions:  Vec<WireProtocolVersion>,
     pub genesis_blocks: Vec<BlockHash>,
     pub proof:          Vec<u8>,
+    /// The optional protocol features the sender supports.
+    pub capabilities:   Capabilities,
 }
 
 /// A network message serving a specified purpose.
@@ -100,6 +194,11 @@ pub enum NetworkRequest {
     JoinNetwork(NetworkId),
     /// Notifies that a node left a specific network.
     LeaveNetwork(NetworkId),
+    /// Announces transactions available from the sender, which the receiver
+    /// can request if it hasn't seen them yet.
+    TransactionAnnouncement(Vec<TransactionDigest>),
+    /// Requests previously announced transactions.
+    TransactionRequest(Vec<TransactionDigest>),
 }
 
 /// A network message sent only in response to a network request.
@@ -6,36 +6,73 @@ use crate::{
         p2p_peer::{P2PPeer, PeerType},
         P2PNodeId,
     },
+    configuration::PROTOCOL_MAX_MESSAGE_SIZE,
     flatbuffers_shim::network,
     network::{
-        Handshake, NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest,
-        NetworkResponse, PacketDestination,
+        Capabilities, Handshake, NetworkId, NetworkMessage, NetworkPacket, NetworkPayload,
+        NetworkRequest, NetworkResponse, PacketDestination, TransactionDigest,
     },
 };
-use anyhow::{bail, Context, Error};
+use anyhow::{bail, ensure, Context, Error};
 use concordium_base::hashes::BlockHash;
-use flatbuffers::FlatBufferBuilder;
+use flatbuffers::{FlatBufferBuilder, VerifierOptions};
 use semver::Version;
 use std::{
     convert::TryFrom,
     io::{self, Read, Write},
     net::{IpAddr, SocketAddr},
-    panic,
 };
 
 /// The HANDSHAKE message version. In order to make the handshake robust, we
 /// need to version the message itself. Higher versions are assumed to append
 /// new fields at the end of the message so it should be still deserializable
 /// even if the new fields are not understood, but a warning will be emitted.
-pub const HANDSHAKE_MESSAGE_VERSION: u8 = 0;
+pub const HANDSHAKE_MESSAGE_VERSION: u8 = 1;
+
+/// The maximum nesting depth of flatbuffers tables in a network message. The
+/// deepest path in the schema is NetworkMessage -> NetworkResponse -> PeerList
+/// -> P2PPeer -> IpAddr.
+const MAX_TABLE_DEPTH: usize = 8;
+
+/// The size of a single transaction digest in a transaction announcement or
+/// request.
+const TRANSACTION_DIGEST_SIZE: usize = std::mem::size_of::<TransactionDigest>();
 
 impl NetworkMessage {
-    // FIXME: remove the unwind once the verifier is available
+    /// Deserialize a network message. The buffer is checked by the
+    /// flatbuffers verifier before any of its fields are accessed, so
+    /// malformed input results in an error rather than a panic.
     pub fn deserialize(buffer: &[u8]) -> anyhow::Result<Self> {
-        match panic::catch_unwind(|| _deserialize(buffer)) {
-            Ok(msg) => msg,
-            Err(_) => bail!("caught a panic: received a mangled buffer"),
+        if buffer.len() < 12 {
+            bail!("the buffer is too small")
+        }
+
+        if !network::network_message_size_prefixed_buffer_has_identifier(buffer) {
+            bail!("unrecognized protocol name")
         }
+
+        let verifier_options = VerifierOptions {
+            max_depth: MAX_TABLE_DEPTH,
+            max_apparent_size: PROTOCOL_MAX_MESSAGE_SIZE as usize,
+            ..VerifierOptions::default()
+        };
+        let root =
+            network::size_prefixed_root_as_network_message_with_opts(&verifier_options, buffer)?;
+
+        let created = root.timestamp();
+
+        let payload = match root.payload_type() {
+            network::NetworkPayload::NetworkPacket => deserialize_packet(&root)?,
+            network::NetworkPayload::NetworkRequest => deserialize_request(&root)?,
+            network::NetworkPayload::NetworkResponse => deserialize_response(&root)?,
+            _ => bail!("invalid network message payload type"),
+        };
+
+        Ok(NetworkMessage {
+            created,
+            received: Some(get_current_stamp()),
+            payload,
+        })
     }
 
     pub fn serialize<T: Write>(&self, target: &mut T) -> anyhow::Result<()> {
@@ -76,33 +113,6 @@ impl NetworkMessage {
 
 // deserialization
 
-fn _deserialize(buffer: &[u8]) -> anyhow::Result<NetworkMessage> {
-    if buffer.len() < 12 {
-        bail!("the buffer is too small")
-    }
-
-    if !network::network_message_size_prefixed_buffer_has_identifier(buffer) {
-        bail!("unrecognized protocol name")
-    }
-
-    let root = network::size_prefixed_root_as_network_message(buffer)?;
-
-    let created = root.timestamp();
-
-    let payload = match root.payload_type() {
-        network::NetworkPayload::NetworkPacket => deserialize_packet(&root)?,
-        network::NetworkPayload::NetworkRequest => deserialize_request(&root)?,
-        network::NetworkPayload::NetworkResponse => deserialize_response(&root)?,
-        _ => bail!("invalid network message payload type"),
-    };
-
-    Ok(NetworkMessage {
-        created,
-        received: Some(get_current_stamp()),
-        payload,
-    })
-}
-
 fn deserialize_packet(root: &network::NetworkMessage) -> anyhow::Result<NetworkPayload> {
     let packet = root
         .payload_as_network_packet()
@@ -154,7 +164,7 @@ fn deserialize_request(root: &network::NetworkMessage) -> anyhow::Result<Network
         }
         network::RequestVariant::Handshake => {
             if let Some(handshake) = request.payload_as_handshake() {
-                if handshake.version() != HANDSHAKE_MESSAGE_VERSION {
+                if handshake.version() > HANDSHAKE_MESSAGE_VERSION {
                     warn!(
                         "Received handshake version ({}) is higher than our version ({}). \
                          Attempting to parse.",
@@ -206,7 +216,8 @@ fn deserialize_request(root: &network::NetworkMessage) -> anyhow::Result<Network
                     node_version,
                     wire_versions,
                     genesis_blocks,
-                    proof: Vec::new(),
+                    proof: handshake.zk().map(|zk| zk.to_vec()).unwrap_or_default(),
+                    capabilities: Capabilities::from_bits(handshake.capabilities()),
                 })))
             } else {
                 bail!("missing handshake payload")
@@ -223,10 +234,45 @@ fn deserialize_request(root: &network::NetworkMessage) -> anyhow::Result<Network
                 bail!("missing network id in a join/leave network request")
             }
         }
+        network::RequestVariant::TransactionAnnouncement
+        | network::RequestVariant::TransactionRequest => {
+            let digests = if let Some(digests) =
+                request.payload_as_transaction_digests().and_then(|payload| payload.digests())
+            {
+                deserialize_transaction_digests(digests.bytes())?
+            } else {
+                bail!("missing transaction digests in a transaction announcement/request")
+            };
+            Ok(NetworkPayload::NetworkRequest(match request.variant() {
+                network::RequestVariant::TransactionAnnouncement => {
+                    NetworkRequest::TransactionAnnouncement(digests)
+                }
+                network::RequestVariant::TransactionRequest => {
+                    NetworkRequest::TransactionRequest(digests)
+                }
+                _ => unreachable!(),
+            }))
+        }
         msg => bail!("Unsupported request variant {:?}", msg),
     }
 }
 
+fn deserialize_transaction_digests(bytes: &[u8]) -> anyhow::Result<Vec<TransactionDigest>> {
+    ensure!(
+        bytes.len() % TRANSACTION_DIGEST_SIZE == 0,
+        "the transaction digests are not a multiple of {} bytes long",
+        TRANSACTION_DIGEST_SIZE
+    );
+    Ok(bytes
+        .chunks_exact(TRANSACTION_DIGEST_SIZE)
+        .map(|chunk| {
+            let mut digest = [0u8; TRANSACTION_DIGEST_SIZE];
+            digest.copy_from_slice(chunk);
+            digest
+        })
+        .collect())
+}
+
 fn deserialize_response(root: &network::NetworkMessage) -> anyhow::Result<NetworkPayload> {
     let response = root
         .payload_as_network_response()
@@ -387,16 +433,22 @@ fn serialize_request(
                 builder.push(*offset);
             }
             let genesis_blocks_offset = Some(builder.end_vector(genesis_blocks.len()));
+            let zk_offset = if handshake.proof.is_empty() {
+                None
+            } else {
+                Some(builder.create_vector(&handshake.proof))
+            };
 
             let offset = network::Handshake::create(builder, &network::HandshakeArgs {
-                version:        0,
+                version:        HANDSHAKE_MESSAGE_VERSION,
                 node_id:        handshake.remote_id.as_raw(),
                 port:           handshake.remote_port,
                 network_ids:    nets_offset,
                 node_version:   Some(node_version_offset),
                 wire_versions:  wire_version_offset,
                 genesis_blocks: genesis_blocks_offset,
-                zk:             None,
+                zk:             zk_offset,
+                capabilities:   handshake.capabilities.bits(),
             });
             (
                 network::RequestVariant::Handshake,
@@ -424,6 +476,22 @@ fn serialize_request(
                 Some(offset.as_union_value()),
             )
         }
+        NetworkRequest::TransactionAnnouncement(digests) => {
+            let offset = serialize_transaction_digests(builder, digests);
+            (
+                network::RequestVariant::TransactionAnnouncement,
+                network::RequestPayload::TransactionDigests,
+                Some(offset.as_union_value()),
+            )
+        }
+        NetworkRequest::TransactionRequest(digests) => {
+            let offset = serialize_transaction_digests(builder, digests);
+            (
+                network::RequestVariant::TransactionRequest,
+                network::RequestPayload::TransactionDigests,
+                Some(offset.as_union_value()),
+            )
+        }
     };
 
     let request_offset = network::NetworkRequest::create(builder, &network::NetworkRequestArgs {
@@ -436,6 +504,17 @@ fn serialize_request(
     Ok(request_offset)
 }
 
+fn serialize_transaction_digests<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    digests: &[TransactionDigest],
+) -> flatbuffers::WIPOffset<network::TransactionDigests<'a>> {
+    let digests = digests.concat();
+    let digests_offset = Some(builder.create_vector(&digests));
+    network::TransactionDigests::create(builder, &network::TransactionDigestsArgs {
+        digests: digests_offset,
+    })
+}
+
 fn serialize_response(
     builder: &mut FlatBufferBuilder,
     response: &NetworkResponse,
@@ -3,9 +3,10 @@ use semver::Version;
 use crate::{
     common::{get_current_stamp, p2p_peer::P2PPeer, P2PNodeId, PeerType},
     network::{
-        Handshake, NetworkId, NetworkMessage, NetworkPayload, NetworkRequest, NetworkResponse,
+        Capabilities, Handshake, NetworkId, NetworkMessage, NetworkPayload, NetworkRequest,
+        NetworkResponse,
     },
-    test_utils::{create_random_packet, dummy_regenesis_blocks},
+    test_utils::{create_random_packet, dummy_regenesis_blocks, fuzz_regressions},
 };
 
 use std::{
@@ -48,6 +49,21 @@ test_s11n!(
         wire_versions:  vec![0, 1, 2],
         genesis_blocks: dummy_regenesis_blocks(),
         proof:          Vec::new(),
+        // unknown bits must survive a roundtrip
+        capabilities:   Capabilities::COMPRESSION_ZSTD.union(Capabilities::from_bits(1 << 63)),
+    }))
+);
+test_s11n!(
+    s11n_req_handshake_with_proof,
+    NetworkPayload::NetworkRequest(NetworkRequest::Handshake(Handshake {
+        remote_id:      P2PNodeId(77),
+        remote_port:    1234,
+        networks:       [100u16].iter().copied().map(NetworkId::from).collect(),
+        node_version:   Version::parse(env!("CARGO_PKG_VERSION")).unwrap(),
+        wire_versions:  vec![0, 1, 2],
+        genesis_blocks: dummy_regenesis_blocks(),
+        proof:          vec![7u8; 72],
+        capabilities:   Capabilities::COMPRESSION_ZSTD,
     }))
 );
 test_s11n!(
@@ -58,6 +74,16 @@ test_s11n!(
     s11n_req_leave_net,
     NetworkPayload::NetworkRequest(NetworkRequest::LeaveNetwork(NetworkId::from(1337),))
 );
+test_s11n!(
+    s11n_req_transaction_announcement,
+    NetworkPayload::NetworkRequest(NetworkRequest::TransactionAnnouncement(vec![
+        [1u8; 32], [2u8; 32]
+    ]))
+);
+test_s11n!(
+    s11n_req_transaction_request,
+    NetworkPayload::NetworkRequest(NetworkRequest::TransactionRequest(vec![[3u8; 32]]))
+);
 
 test_s11n!(s11n_resp_pong, NetworkPayload::NetworkResponse(NetworkResponse::Pong));
 
@@ -98,3 +124,10 @@ quickcheck! {
         true
     }
 }
+
+#[test]
+fn s11n_fuzz_regressions() {
+    for (name, bytes) in fuzz_regressions("network_message") {
+        assert!(NetworkMessage::deserialize(&bytes).is_err(), "{} was accepted", name);
+    }
+}
@@ -0,0 +1,86 @@
+//! The allowlist of peers in a private network.
+
+use crate::common::P2PNodeId;
+use anyhow::Context;
+use std::{collections::HashSet, net::IpAddr};
+
+/// The peers a node in a private network is willing to talk to, identified
+/// either by their IP address or by their node id. Since node ids are only
+/// learned in the handshake, a connection with an address that is not on the
+/// list is only refused early if the list contains no node ids. Node ids only
+/// match after the handshake if the peer authenticated them with its static
+/// key.
+#[derive(Debug, Default)]
+pub struct PeerAllowlist {
+    ips: HashSet<IpAddr>,
+    ids: HashSet<P2PNodeId>,
+}
+
+impl PeerAllowlist {
+    /// Parse a list of entries, each an IP address or a node id. Returns
+    /// `None` if the list is empty, i.e. if the node is not in private network
+    /// mode.
+    pub fn parse(entries: &[String]) -> anyhow::Result<Option<Self>> {
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let mut allowlist = PeerAllowlist::default();
+        for entry in entries {
+            if let Ok(ip) = entry.parse::<IpAddr>() {
+                allowlist.ips.insert(ip);
+            } else {
+                let id = entry.parse::<P2PNodeId>().with_context(|| {
+                    format!("Allowed peer '{}' is neither an IP address nor a node id.", entry)
+                })?;
+                allowlist.ids.insert(id);
+            }
+        }
+        Ok(Some(allowlist))
+    }
+
+    /// Whether a peer with the given address and node id, if already known,
+    /// may be allowed. This is a preliminary check before the handshake; the
+    /// id is the one the peer is expected to have.
+    pub fn is_allowed(&self, ip: IpAddr, id: Option<P2PNodeId>) -> bool {
+        self.ips.contains(&ip) || id.map_or(!self.ids.is_empty(), |id| self.ids.contains(&id))
+    }
+
+    /// Whether a peer that completed the handshake is allowed. Node id entries
+    /// only match the id of the peer if it was authenticated.
+    pub fn is_allowed_authenticated(
+        &self,
+        ip: IpAddr,
+        authenticated_id: Option<P2PNodeId>,
+    ) -> bool {
+        self.ips.contains(&ip) || authenticated_id.map_or(false, |id| self.ids.contains(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_by_ip_and_id() {
+        let allowed_ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let by_ip = PeerAllowlist::parse(&["10.0.0.1".to_owned()]).unwrap().unwrap();
+        assert!(by_ip.is_allowed(allowed_ip, None));
+        assert!(!by_ip.is_allowed(other_ip, None));
+        assert!(!by_ip.is_allowed(other_ip, Some(P2PNodeId(1))));
+
+        let by_id = PeerAllowlist::parse(&["00000000000000ab".to_owned()]).unwrap().unwrap();
+        // the id is not known before the handshake
+        assert!(by_id.is_allowed(other_ip, None));
+        assert!(by_id.is_allowed(other_ip, Some(P2PNodeId(0xab))));
+        assert!(!by_id.is_allowed(other_ip, Some(P2PNodeId(0xac))));
+        // after the handshake, only an authenticated id matches
+        assert!(by_id.is_allowed_authenticated(other_ip, Some(P2PNodeId(0xab))));
+        assert!(!by_id.is_allowed_authenticated(other_ip, None));
+        assert!(by_ip.is_allowed_authenticated(allowed_ip, None));
+
+        assert!(PeerAllowlist::parse(&[]).unwrap().is_none());
+        assert!(PeerAllowlist::parse(&["not-a-peer".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Proofs that a peer is a baker, sent in the `proof` field of the handshake.
+//!
+//! A proof consists of the baker id (big-endian) followed by a signature with
+//! the baker's block signing key on the noise static key of the sender. Since
+//! the static key is authenticated by the noise handshake, a proof cannot be
+//! replayed by somebody else. Whether the signing key belongs to the claimed
+//! baker is checked against the current bakers known to consensus, see
+//! [verify_baker_peers](super::connectivity::verify_baker_peers).
+
+use anyhow::Context;
+use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey, Signature, Verifier};
+use noiseexplorer_xx::consts::DHLEN;
+use std::convert::TryFrom;
+
+/// Domain separation string for the signed message.
+const BAKER_PROOF_DOMAIN: &[u8] = b"CONCORDIUM-P2P-BAKER-PROOF";
+
+/// Length of a baker proof in bytes.
+pub const BAKER_PROOF_LEN: usize = 8 + ed25519_dalek::SIGNATURE_LENGTH;
+
+/// The part of the baker credentials needed to produce a proof.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BakerSigningKeys {
+    baker_id:             u64,
+    signature_sign_key:   String,
+    signature_verify_key: String,
+}
+
+fn signed_message(static_key: &[u8; DHLEN]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(BAKER_PROOF_DOMAIN.len() + DHLEN);
+    message.extend_from_slice(BAKER_PROOF_DOMAIN);
+    message.extend_from_slice(static_key);
+    message
+}
+
+/// The baker id and the block signing key pair from the (decrypted) baker
+/// credentials.
+fn signing_keys(credentials: &[u8]) -> anyhow::Result<(u64, SecretKey, PublicKey)> {
+    let keys: BakerSigningKeys =
+        serde_json::from_slice(credentials).context("Could not parse the baker credentials.")?;
+    let secret = SecretKey::from_bytes(&hex::decode(&keys.signature_sign_key)?)
+        .context("Invalid baker signing key.")?;
+    let public = PublicKey::from(&secret);
+    anyhow::ensure!(
+        public.as_bytes()[..] == hex::decode(&keys.signature_verify_key)?[..],
+        "The baker signing key does not match the verification key."
+    );
+    Ok((keys.baker_id, secret, public))
+}
+
+/// Check that the (decrypted) baker credentials contain a matching block
+/// signing key pair, returning the baker id.
+pub fn check_baker_credentials(credentials: &[u8]) -> anyhow::Result<u64> {
+    signing_keys(credentials).map(|(baker_id, ..)| baker_id)
+}
+
+/// Produce a baker proof for the given static key from the (decrypted) baker
+/// credentials.
+pub fn make_baker_proof(credentials: &[u8], static_key: &[u8; DHLEN]) -> anyhow::Result<Vec<u8>> {
+    let (baker_id, secret, public) = signing_keys(credentials)?;
+    let signature = ExpandedSecretKey::from(&secret).sign(&signed_message(static_key), &public);
+    let mut proof = Vec::with_capacity(BAKER_PROOF_LEN);
+    proof.extend_from_slice(&baker_id.to_be_bytes());
+    proof.extend_from_slice(&signature.to_bytes());
+    Ok(proof)
+}
+
+/// A peer's claim to be a baker, as received in the handshake.
+#[derive(Debug, Clone)]
+pub struct BakerClaim {
+    pub baker_id: u64,
+    signature:    Signature,
+    static_key:   [u8; DHLEN],
+    /// The outcome of the last check against the current bakers, or `None`
+    /// if the claim has not been checked yet.
+    pub verified: Option<bool>,
+}
+
+impl BakerClaim {
+    /// Parse a non-empty proof sent by the peer with the given static key.
+    pub fn parse(proof: &[u8], static_key: [u8; DHLEN]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            proof.len() == BAKER_PROOF_LEN,
+            "A baker proof must be {} bytes.",
+            BAKER_PROOF_LEN
+        );
+        let mut baker_id = [0u8; 8];
+        baker_id.copy_from_slice(&proof[..8]);
+        Ok(BakerClaim {
+            baker_id: u64::from_be_bytes(baker_id),
+            signature: Signature::try_from(&proof[8..])?,
+            static_key,
+            verified: None,
+        })
+    }
+
+    /// Check the claim against the current signature verification key of the
+    /// claimed baker, if any, and record the outcome.
+    pub fn check(&mut self, verify_key: Option<[u8; 32]>) -> bool {
+        let valid =
+            verify_key.and_then(|key| PublicKey::from_bytes(&key).ok()).map_or(false, |key| {
+                key.verify(&signed_message(&self.static_key), &self.signature).is_ok()
+            });
+        self.verified = Some(valid);
+        valid
+    }
+
+    /// The baker id, if the claim was verified by the last check.
+    pub fn verified_baker(&self) -> Option<u64> {
+        if self.verified == Some(true) {
+            Some(self.baker_id)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baker_proof_roundtrip() {
+        let secret = SecretKey::from_bytes(&[3u8; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let credentials = serde_json::json!({
+            "bakerId": 42,
+            "signatureSignKey": hex::encode(secret.as_bytes()),
+            "signatureVerifyKey": hex::encode(public.as_bytes()),
+        });
+        let static_key = [5u8; DHLEN];
+        let proof = make_baker_proof(credentials.to_string().as_bytes(), &static_key).unwrap();
+
+        let mut claim = BakerClaim::parse(&proof, static_key).unwrap();
+        assert_eq!(claim.baker_id, 42);
+        assert!(claim.check(Some(public.to_bytes())));
+        assert_eq!(claim.verified_baker(), Some(42));
+        assert!(!claim.check(None));
+        assert_eq!(claim.verified_baker(), None);
+
+        // The proof is bound to the static key of the sender.
+        let mut replayed = BakerClaim::parse(&proof, [6u8; DHLEN]).unwrap();
+        assert!(!replayed.check(Some(public.to_bytes())));
+    }
+}
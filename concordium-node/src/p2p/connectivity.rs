@@ -4,10 +4,11 @@ use crate::{
     common::{get_current_stamp, p2p_peer::RemotePeerId, P2PNodeId, PeerType, RemotePeer},
     configuration as config,
     connection::{ConnChange, Connection, MessageSendingPriority},
+    consensus_ffi::{consensus::ConsensusContainer, helpers::PacketType},
     lock_or_die, netmsg,
     network::{
-        Handshake, NetworkId, NetworkPacket, NetworkRequest, PacketDestination,
-        WIRE_PROTOCOL_VERSIONS,
+        compression::OutgoingMessage, Capabilities, Handshake, NetworkId, NetworkPacket,
+        NetworkRequest, PacketDestination, WIRE_PROTOCOL_VERSIONS,
     },
     p2p::{
         bans::{BanId, PersistedBanId},
@@ -59,18 +60,21 @@ impl P2PNode {
     }
 
     /// Send a `data` message to all connections adhering to the specified
-    /// filter. Returns the number of sent messages.
+    /// filter. Returns the number of sent messages. Messages above the
+    /// compression threshold are compressed for the connections that
+    /// negotiated compression.
     pub fn send_over_all_connections(
         &self,
         data: &[u8],
         conn_filter: &dyn Fn(&Connection) -> bool,
     ) -> usize {
         let mut sent_messages = 0usize;
-        let data = Arc::from(data);
+        let mut message = OutgoingMessage::new(data, self.config.compression_threshold);
 
         for conn in write_or_die!(self.connections()).values_mut().filter(|conn| conn_filter(conn))
         {
-            conn.async_send(Arc::clone(&data), MessageSendingPriority::Normal);
+            let data = message.for_capabilities(conn.capabilities, &self.stats);
+            conn.async_send(data, MessageSendingPriority::Normal);
             sent_messages += 1;
         }
 
@@ -188,14 +192,14 @@ impl P2PNode {
         let peers_to_skip = match inner_pkt.destination {
             PacketDestination::Direct(..) => vec![],
             PacketDestination::Broadcast(ref dont_relay_to) => {
-                if self.config.relay_broadcast_percentage < 1.0 {
+                let relay_broadcast_percentage = self.config.limits().relay_broadcast_percentage;
+                if relay_broadcast_percentage < 1.0 {
                     use rand::seq::SliceRandom;
                     let mut rng = rand::thread_rng();
                     let mut peers = self.get_node_peer_tokens();
                     peers.retain(|token| !dont_relay_to.contains(token));
-                    let peers_to_take = f64::floor(
-                        f64::from(peers.len() as u32) * self.config.relay_broadcast_percentage,
-                    );
+                    let peers_to_take =
+                        f64::floor(f64::from(peers.len() as u32) * relay_broadcast_percentage);
                     peers
                         .choose_multiple(&mut rng, peers_to_take as usize)
                         .copied()
@@ -212,6 +216,15 @@ impl P2PNode {
             None
         };
         let network_id = inner_pkt.network_id;
+        // broadcast transactions are subject to the pull-based relay
+        let transaction = if target.is_none()
+            && self.pulls_transactions()
+            && inner_pkt.message.first() == Some(&(PacketType::Transaction as u8))
+        {
+            Some(inner_pkt.message.clone())
+        } else {
+            None
+        };
 
         let message = netmsg!(NetworkPacket, inner_pkt);
         let mut serialized = Vec::with_capacity(256);
@@ -222,6 +235,19 @@ impl P2PNode {
             // direct messages
             let filter = |conn: &Connection| conn.remote_peer.local_id == target_token;
             sent += self.send_over_all_connections(&serialized, &filter);
+        } else if let Some(transaction) = transaction {
+            // transactions are sent in full only to the peers that can't request them
+            let pulls = |conn: &Connection| {
+                conn.capabilities.contains(Capabilities::TRANSACTION_PULL_RELAY)
+            };
+            let push_filter = |conn: &Connection| {
+                is_valid_broadcast_target(conn, &peers_to_skip, network_id) && !pulls(conn)
+            };
+            let announce_filter = |conn: &Connection| {
+                is_valid_broadcast_target(conn, &peers_to_skip, network_id) && pulls(conn)
+            };
+            sent += self.send_over_all_connections(&serialized, &push_filter);
+            sent += self.announce_transaction(network_id, &transaction, &announce_filter)?;
         } else {
             // broadcast messages
             let filter =
@@ -309,7 +335,8 @@ impl P2PNode {
                 node_version:   Version::parse(env!("CARGO_PKG_VERSION"))?,
                 wire_versions:  WIRE_PROTOCOL_VERSIONS.to_vec(),
                 genesis_blocks: read_or_die!(self.config.regenesis_arc.blocks).clone(),
-                proof:          vec![],
+                proof:          read_or_die!(self.baker_proof).clone(),
+                capabilities:   self.config.capabilities,
             })
         );
         let mut serialized = Vec::with_capacity(128);
@@ -347,6 +374,10 @@ pub enum AcceptFailureReason {
     Banned,
     #[error("Connection attempt from a soft-banned address.")]
     SoftBanned,
+    #[error("Connection attempt from {addr}, which is not an allowed peer.")]
+    NotAllowed {
+        addr: SocketAddr,
+    },
     #[error("{err}")]
     Other {
         #[from]
@@ -373,6 +404,13 @@ pub fn accept(
         return Err(AcceptFailureReason::Banned);
     }
 
+    // in private network mode, the node id is checked again after the handshake.
+    if !node.is_allowed_peer(addr.ip(), None) {
+        return Err(AcceptFailureReason::NotAllowed {
+            addr,
+        });
+    }
+
     // Lock the candidate list for added safety against duplicate connections
     let mut candidates_lock = lock_or_die!(node.conn_candidates());
 
@@ -457,12 +495,9 @@ pub fn connect(
 
     if respect_max_peers && peer_type == PeerType::Node {
         let current_peer_count = node.get_peer_stats(Some(PeerType::Node)).len() as u16;
-        if current_peer_count >= node.config.max_allowed_nodes {
-            bail!(
-                "Maximum number of peers reached {}/{}",
-                current_peer_count,
-                node.config.max_allowed_nodes
-            );
+        let max_allowed_nodes = node.config.limits().max_allowed_nodes;
+        if current_peer_count >= max_allowed_nodes {
+            bail!("Maximum number of peers reached {}/{}", current_peer_count, max_allowed_nodes);
         }
     }
 
@@ -481,6 +516,11 @@ pub fn connect(
         bail!("Refusing to connect to a soft-banned IP ({})", peer_addr.ip());
     }
 
+    // Or, in private network mode, to peers that are not allowed.
+    if !node.is_allowed_peer(peer_addr.ip(), peer_id) {
+        bail!("Refusing to connect to {}, which is not an allowed peer", peer_addr);
+    }
+
     // Lock the candidate list for added safety against duplicate connections
     let mut candidates_lock = lock_or_die!(node.conn_candidates());
 
@@ -532,6 +572,47 @@ pub fn connect(
     }
 }
 
+/// Check the baker proofs sent by peers against the current bakers. If
+/// `recheck` is not set, only the claims that have not been checked yet are
+/// considered.
+pub fn verify_baker_peers(node: &P2PNode, consensus: &ConsensusContainer, recheck: bool) {
+    let pending = read_or_die!(node.connections())
+        .iter()
+        .filter_map(|(&token, conn)| {
+            conn.baker_claim
+                .as_ref()
+                .filter(|claim| recheck || claim.verified.is_none())
+                .map(|claim| (token, claim.baker_id))
+        })
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return;
+    }
+    // look up the keys before taking the write lock on the connections
+    let keys = pending
+        .into_iter()
+        .map(|(token, baker_id)| (token, consensus.current_baker_signature_key(baker_id)))
+        .collect::<Vec<_>>();
+    let mut connections = write_or_die!(node.connections());
+    for (token, key) in keys {
+        if let Some(conn) = connections.get_mut(&token) {
+            if let Some(claim) = conn.baker_claim.as_mut() {
+                let was_verified = claim.verified == Some(true);
+                if claim.check(key) != was_verified {
+                    if was_verified {
+                        debug!("Peer {} is no longer a verified baker", conn.remote_peer.local_id);
+                    } else {
+                        debug!(
+                            "Peer {} is verified as baker {}",
+                            conn.remote_peer.local_id, claim.baker_id
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Perform a round of connection maintenance, e.g. removing inactive ones.
 /// Return whether we attempted to bootstrap.
 pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
@@ -539,9 +620,10 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
 
     let curr_stamp = get_current_stamp();
     let peer_type = node.peer_type();
+    let limits = node.config.limits();
 
     let is_conn_faulty = |conn: &Connection| -> bool {
-        if let Some(max_latency) = node.config.max_latency {
+        if let Some(max_latency) = limits.max_latency {
             conn.get_latency() >= max_latency
         } else {
             false
@@ -579,9 +661,16 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
     }
 
     // if the number of peers exceeds the desired value, close a random selection of
-    // post-handshake non-given connections to lower it
+    // post-handshake non-given connections to lower it. Verified baker peers may
+    // occupy up to `baker_peer_slots` connections above the limit and are never
+    // dropped here.
     if peer_type == PeerType::Node {
-        let max_allowed_nodes = node.config.max_allowed_nodes;
+        let baker_peers = read_or_die!(node.connections())
+            .values()
+            .filter(|conn| conn.verified_baker().is_some())
+            .count() as u16;
+        let max_allowed_nodes =
+            limits.max_allowed_nodes.saturating_add(baker_peers.min(node.config.baker_peer_slots));
         let peer_count = node.get_peer_stats(Some(PeerType::Node)).len() as u16;
         if peer_count > max_allowed_nodes {
             // drop connections to any non-given peers.
@@ -589,8 +678,8 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
             let to_drop = read_or_die!(node.connections())
                 .iter()
                 .filter_map(|(&token, conn)| {
-                    // only consider non-given connections for removal
-                    if node.is_given_connection(conn) {
+                    // only consider non-given, non-baker connections for removal
+                    if node.is_given_connection(conn) || conn.verified_baker().is_some() {
                         None
                     } else {
                         Some(token)
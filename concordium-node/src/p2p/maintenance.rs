@@ -5,28 +5,41 @@ use chrono::prelude::*;
 use crossbeam_channel::{self, Receiver, Sender};
 use mio::{net::TcpListener, Events, Interest, Poll, Registry, Token};
 use nohash_hasher::BuildNoHashHasher;
-use rand::{prelude::SliceRandom, thread_rng, Rng};
+use rand::{prelude::SliceRandom, thread_rng};
 use rkv::{
     backend::{Lmdb, LmdbEnvironment},
     Manager, Rkv,
 };
 
 #[cfg(feature = "network_dump")]
-use crate::dumper::{create_dump_thread, DumpItem};
+use crate::dumper::{create_dump_thread, DumpFilter, DumpItem};
+#[cfg(any(test, bench, feature = "test_utils"))]
+use crate::fault_injection::FaultInjector;
 use crate::{
-    common::{get_current_stamp, p2p_peer::RemotePeerId, P2PNodeId, P2PPeer, PeerType},
-    configuration::{self as config, Config},
-    connection::{ConnChange, Connection, DeduplicationHashAlgorithm, DeduplicationQueues},
+    common::{
+        get_current_stamp, node_key::NodeKey, p2p_peer::RemotePeerId, P2PNodeId, P2PPeer, PeerType,
+    },
+    configuration::{self as config, Config, NetworkLimits},
+    connection::{
+        ConnChange, Connection, DeduplicationHashAlgorithm, DeduplicationQueues, DEFAULT_PROLOGUE,
+        DEFAULT_PSK,
+    },
     consensus_ffi::{
         catch_up::PeerList,
         consensus::{ConsensusContainer, Regenesis, CALLBACK_QUEUE},
     },
     lock_or_die,
-    network::{Buckets, NetworkId, Networks},
+    network::{Buckets, Capabilities, NetworkId, Networks, SUPPORTED_CAPABILITIES},
     p2p::{
+        allowlist::PeerAllowlist,
+        baker_proof::make_baker_proof,
         bans::BanId,
-        connectivity::{accept, connect, connection_housekeeping, AcceptFailureReason, SELF_TOKEN},
+        connectivity::{
+            accept, connect, connection_housekeeping, verify_baker_peers, AcceptFailureReason,
+            SELF_TOKEN,
+        },
         peers::check_peers,
+        transaction_relay::{RelayedTransactions, RequestedTransactions},
     },
     plugins::consensus::{check_peer_states, update_peer_list},
     read_or_die, spawn_or_die,
@@ -55,7 +68,9 @@ use std::{
 /// Configuration bits applicable to a node.
 pub struct NodeConfig {
     pub no_net: bool,
-    pub desired_nodes_count: u16,
+    /// The limits that can be changed at runtime, see
+    /// [set_limits](Self::set_limits).
+    limits: RwLock<NetworkLimits>,
     pub no_bootstrap_dns: bool,
     /// Do not clear persistent bans on startup.
     pub no_clear_bans: bool,
@@ -67,15 +82,11 @@ pub struct NodeConfig {
     /// are resolved on startup or when they are added and during execution
     /// we only keep them instead of the domain name.
     pub given_addresses: RwLock<HashSet<SocketAddr>>,
-    pub max_allowed_nodes: u16,
-    pub relay_broadcast_percentage: f64,
     pub poll_interval: u64,
-    pub housekeeping_interval: u64,
     pub bootstrapping_interval: u64,
     pub print_peers: bool,
     pub bootstrapper_wait_minimum_peers: u16,
     pub data_dir_path: PathBuf,
-    pub max_latency: Option<u64>,
     pub hard_connection_limit: u16,
     pub conn_requests_batch_limit: u16,
     pub catch_up_batch_limit: i64,
@@ -86,7 +97,6 @@ pub struct NodeConfig {
     pub dedup_size_short: usize,
     pub socket_read_size: usize,
     pub socket_write_size: usize,
-    pub drop_rebroadcast_probability: Option<f64>,
     pub bootstrapper_peer_list_size: usize,
     pub default_network: NetworkId,
     pub socket_so_linger: Option<u16>,
@@ -94,6 +104,39 @@ pub struct NodeConfig {
     pub deduplication_hashing_algorithm: DeduplicationHashAlgorithm,
     pub regenesis_arc: Arc<Regenesis>,
     pub max_normal_keep_alive_ms: u64,
+    /// The optional protocol features advertised to peers.
+    pub capabilities: Capabilities,
+    /// Minimum size of messages to be compressed.
+    pub compression_threshold: usize,
+    /// Whether to reject peers whose node id does not match their static key.
+    pub require_authenticated_node_ids: bool,
+    /// Number of verified baker peers allowed above `max_allowed_nodes`.
+    pub baker_peer_slots: u16,
+    /// The pre-shared key sent in the first noise handshake message.
+    pub noise_psk: Vec<u8>,
+    /// The prologue of the noise handshake.
+    pub noise_prologue: Vec<u8>,
+    /// If set, the node is in private network mode and only talks to the
+    /// peers on the list.
+    pub peer_allowlist: Option<PeerAllowlist>,
+}
+
+impl NodeConfig {
+    /// The current network limits.
+    pub fn limits(&self) -> NetworkLimits { *read_or_die!(self.limits) }
+
+    /// Replace the network limits, provided they pass the same checks as the
+    /// limits given on startup. Each change is logged together with its
+    /// origin.
+    pub fn set_limits(&self, limits: NetworkLimits, origin: &str) -> anyhow::Result<()> {
+        limits.check(self.hard_connection_limit, self.max_normal_keep_alive_ms / 1000)?;
+        let mut current = write_or_die!(self.limits);
+        for change in current.changes(&limits) {
+            info!("Network limit changed by {}: {}", origin, change);
+        }
+        *current = limits;
+        Ok(())
+    }
 }
 
 /// The collection of connections to peer nodes.
@@ -107,20 +150,24 @@ pub struct ConnChanges {
 
 /// The set of objects related to node's connections.
 pub struct ConnectionHandler {
-    pub next_token:           AtomicUsize,
-    pub buckets:              RwLock<Buckets>,
+    pub next_token:             AtomicUsize,
+    pub buckets:                RwLock<Buckets>,
     #[cfg(feature = "network_dump")]
-    pub log_dumper:           RwLock<Option<Sender<DumpItem>>>,
-    pub conn_candidates:      Mutex<Connections>,
-    pub connections:          RwLock<Connections>,
-    pub conn_changes:         ConnChanges,
-    pub soft_bans:            RwLock<HashMap<BanId, Instant>>, // (id, expiry)
-    pub networks:             RwLock<Networks>,
-    pub deduplication_queues: DeduplicationQueues,
-    pub last_bootstrap:       AtomicU64,
-    pub last_peer_update:     AtomicU64,
-    pub total_received:       AtomicU64,
-    pub total_sent:           AtomicU64,
+    pub log_dumper:             RwLock<Option<Sender<DumpItem>>>,
+    #[cfg(any(test, bench, feature = "test_utils"))]
+    pub fault_injector:         RwLock<Option<Arc<FaultInjector>>>,
+    pub conn_candidates:        Mutex<Connections>,
+    pub connections:            RwLock<Connections>,
+    pub conn_changes:           ConnChanges,
+    pub soft_bans:              RwLock<HashMap<BanId, Instant>>, // (id, expiry)
+    pub networks:               RwLock<Networks>,
+    pub deduplication_queues:   DeduplicationQueues,
+    pub relayed_transactions:   Mutex<RelayedTransactions>,
+    pub requested_transactions: Mutex<RequestedTransactions>,
+    pub last_bootstrap:         AtomicU64,
+    pub last_peer_update:       AtomicU64,
+    pub total_received:         AtomicU64,
+    pub total_sent:             AtomicU64,
 }
 
 impl ConnectionHandler {
@@ -144,12 +191,16 @@ impl ConnectionHandler {
             buckets: Default::default(),
             #[cfg(feature = "network_dump")]
             log_dumper: Default::default(),
+            #[cfg(any(test, bench, feature = "test_utils"))]
+            fault_injector: Default::default(),
             conn_candidates: Default::default(),
             connections: Default::default(),
             conn_changes,
             soft_bans: Default::default(),
             networks: RwLock::new(networks),
             deduplication_queues,
+            relayed_transactions: Default::default(),
+            requested_transactions: Default::default(),
             last_bootstrap: Default::default(),
             last_peer_update: Default::default(),
             total_received: Default::default(),
@@ -169,8 +220,11 @@ impl ConnectionHandler {
 /// Facilitates the `network_dump` feature.
 #[cfg(feature = "network_dump")]
 pub struct NetworkDumper {
-    switch: Sender<(std::path::PathBuf, bool)>,
+    switch: Sender<(std::path::PathBuf, bool, DumpFilter)>,
     sender: Sender<crate::dumper::DumpItem>,
+    /// Whether a dump is running; it is set by the dump thread once a dump
+    /// has started, and cleared by it when the dump stops on its own.
+    active: Arc<std::sync::atomic::AtomicBool>,
 }
 
 #[cfg(feature = "network_dump")]
@@ -178,11 +232,21 @@ impl NetworkDumper {
     fn new(ip: IpAddr, id: P2PNodeId, config: &Config) -> Self {
         let (dump_tx, dump_rx) = crossbeam_channel::bounded(config::DUMP_QUEUE_DEPTH);
         let (act_tx, act_rx) = crossbeam_channel::bounded(config::DUMP_SWITCH_QUEUE_DEPTH);
-        create_dump_thread(ip, id, dump_rx, act_rx, config.common.data_dir.clone());
+        let active = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        create_dump_thread(
+            ip,
+            id,
+            dump_rx,
+            act_rx,
+            Arc::clone(&active),
+            config.common.data_dir.clone(),
+            config.common.network_dump_max_file_size,
+        );
 
         Self {
             switch: act_tx,
             sender: dump_tx,
+            active,
         }
     }
 }
@@ -235,6 +299,10 @@ impl BadEvents {
 /// connectivity and contains the metadata, statistics etc.
 pub struct P2PNode {
     pub self_peer:          P2PPeer,
+    /// The long-term static key used in the noise handshakes.
+    pub node_key:           NodeKey,
+    /// The baker proof sent in handshakes, empty if the node is not a baker.
+    pub baker_proof:        RwLock<Vec<u8>>,
     /// Holds the handles to threads spawned by the node.
     pub threads:            RwLock<Vec<JoinHandle<()>>>,
     /// The handle to the poll registry.
@@ -257,10 +325,12 @@ pub struct P2PNode {
 
 impl P2PNode {
     /// Creates a new node and its Poll. If the node id is provided the node
-    /// will be started with that Peer ID. If it is not a fresh one will be
-    /// generated. The return value is a triple of the node, the socket on which
-    /// the node is listening for incoming connections, and the mio poll
-    /// that can be used to notify/poll for incoming connections.
+    /// will be started with that Peer ID. If it is not, the id is derived from
+    /// the node's static key, which is loaded from the data directory or
+    /// generated if there is none. The return value is a triple of the node,
+    /// the socket on which the node is listening for incoming connections,
+    /// and the mio poll that can be used to notify/poll for incoming
+    /// connections.
     pub fn new(
         supplied_id: Option<P2PNodeId>,
         conf: &Config,
@@ -287,7 +357,19 @@ impl P2PNode {
                 .context("Could not compute my own ip. Use `--listen-address` to specify it.")?
         };
 
-        let id = supplied_id.unwrap_or_else(|| rand::thread_rng().gen::<P2PNodeId>());
+        let node_key = NodeKey::load_or_generate(&conf.common.data_dir)?;
+        let id = match supplied_id {
+            Some(id) if id != node_key.node_id() => {
+                warn!(
+                    "The node id {} does not match the node key; peers that require authenticated \
+                     node ids will refuse to connect.",
+                    id
+                );
+                id
+            }
+            Some(id) => id,
+            None => node_key.node_id(),
+        };
 
         info!("My Node ID is {}", id);
         info!("Listening on {}:{}", ip, conf.common.listen_port);
@@ -318,25 +400,20 @@ impl P2PNode {
 
         let given_addresses = RwLock::new(parse_config_nodes(&conf.connection)?);
 
+        let mut limits = NetworkLimits::from_config(conf);
+        if peer_type != PeerType::Node {
+            limits.drop_rebroadcast_probability = None;
+        }
+
         let config = NodeConfig {
             no_net: conf.cli.no_network,
-            desired_nodes_count: conf.connection.desired_nodes,
+            limits: RwLock::new(limits),
             no_bootstrap_dns: conf.connection.no_bootstrap_dns,
             no_clear_bans: conf.connection.no_clear_bans,
             disallow_multiple_peers_on_ip: conf.connection.disallow_multiple_peers_on_ip,
             bootstrap_nodes: conf.connection.bootstrap_nodes.clone(),
             given_addresses,
-            max_allowed_nodes: if let Some(max) = conf.connection.max_allowed_nodes {
-                max
-            } else {
-                f64::floor(
-                    f64::from(conf.connection.desired_nodes)
-                        * (f64::from(conf.connection.max_allowed_nodes_percentage) / 100f64),
-                ) as u16
-            },
-            relay_broadcast_percentage: conf.connection.relay_broadcast_percentage,
             poll_interval: conf.cli.poll_interval,
-            housekeeping_interval: conf.connection.housekeeping_interval,
             bootstrapping_interval: conf.connection.bootstrapping_interval,
             print_peers: true,
             bootstrapper_wait_minimum_peers: match peer_type {
@@ -344,7 +421,6 @@ impl P2PNode {
                 PeerType::Node => 0,
             },
             data_dir_path: conf.common.data_dir.clone(),
-            max_latency: conf.connection.max_latency,
             conn_requests_batch_limit: conf.connection.conn_requests_batch_limit,
             hard_connection_limit: conf.connection.hard_connection_limit,
             catch_up_batch_limit: conf.connection.catch_up_batch_limit,
@@ -359,10 +435,6 @@ impl P2PNode {
             dedup_size_short: conf.connection.dedup_size_short,
             socket_read_size: conf.connection.socket_read_size,
             socket_write_size: conf.connection.socket_write_size,
-            drop_rebroadcast_probability: match peer_type {
-                PeerType::Node => conf.cli.drop_rebroadcast_probability,
-                _ => None,
-            },
             bootstrapper_peer_list_size: conf.bootstrapper.peer_list_size,
             default_network: NetworkId::from(conf.common.network_ids[0]), // always present
             socket_so_linger: conf.connection.socket_so_linger,
@@ -370,6 +442,25 @@ impl P2PNode {
             deduplication_hashing_algorithm: conf.connection.deduplication_hashing_algorithm,
             regenesis_arc,
             max_normal_keep_alive_ms: conf.connection.max_normal_keep_alive * 1000,
+            capabilities: conf
+                .connection
+                .disable_capabilities
+                .iter()
+                .fold(SUPPORTED_CAPABILITIES, |caps, &disabled| caps.difference(disabled)),
+            compression_threshold: conf.connection.compression_threshold,
+            require_authenticated_node_ids: conf.connection.require_authenticated_node_ids,
+            baker_peer_slots: conf.connection.baker_peer_slots,
+            noise_psk: conf
+                .connection
+                .network_psk
+                .as_ref()
+                .map_or_else(|| DEFAULT_PSK.to_vec(), |psk| psk.as_bytes().to_vec()),
+            noise_prologue: conf
+                .connection
+                .network_prologue
+                .as_ref()
+                .map_or_else(|| DEFAULT_PROLOGUE.to_vec(), |prologue| prologue.as_bytes().to_vec()),
+            peer_allowlist: PeerAllowlist::parse(&conf.connection.allowed_peers)?,
         };
 
         let connection_handler = ConnectionHandler::new(conf);
@@ -390,6 +481,8 @@ impl P2PNode {
             network_dumper: NetworkDumper::new(ip, id, conf),
             connection_handler,
             self_peer,
+            node_key,
+            baker_proof: Default::default(),
             stats,
             kvs,
             peers: Default::default(),
@@ -403,6 +496,34 @@ impl P2PNode {
         Ok((node, server, poll))
     }
 
+    /// Use the given baker credentials to prove to peers that the node is a
+    /// baker in subsequent handshakes.
+    pub fn set_baker_credentials(&self, credentials: &[u8]) -> anyhow::Result<()> {
+        let proof = make_baker_proof(credentials, &self.node_key.public_key())?;
+        *write_or_die!(self.baker_proof) = proof;
+        Ok(())
+    }
+
+    /// Whether the node may talk to the peer with the given address and, if
+    /// known, node id. This is only restricted in private network mode.
+    pub fn is_allowed_peer(&self, ip: IpAddr, id: Option<P2PNodeId>) -> bool {
+        self.config.peer_allowlist.as_ref().map_or(true, |allowlist| allowlist.is_allowed(ip, id))
+    }
+
+    /// Whether the node may talk to a peer that completed the handshake from
+    /// the given address, with the given node id if the peer authenticated it.
+    /// This is only restricted in private network mode.
+    pub fn is_allowed_authenticated_peer(
+        &self,
+        ip: IpAddr,
+        authenticated_id: Option<P2PNodeId>,
+    ) -> bool {
+        self.config
+            .peer_allowlist
+            .as_ref()
+            .map_or(true, |allowlist| allowlist.is_allowed_authenticated(ip, authenticated_id))
+    }
+
     /// Get the timestamp of the node's last bootstrap attempt.
     pub fn get_last_bootstrap(&self) -> u64 {
         self.connection_handler.last_bootstrap.load(Ordering::Relaxed)
@@ -477,11 +598,13 @@ impl P2PNode {
         }
     }
 
-    /// Activate the network dump feature.
+    /// Activate the network dump feature, dumping only the messages that
+    /// match the filter until one of its limits is reached.
     #[cfg(feature = "network_dump")]
-    pub fn activate_dump(&self, path: &str, raw: bool) -> anyhow::Result<()> {
+    pub fn activate_dump(&self, path: &str, raw: bool, filter: DumpFilter) -> anyhow::Result<()> {
         let path = std::path::PathBuf::from(path);
-        self.network_dumper.switch.send((path, raw))?;
+        // the dump thread sets the active flag once the dump has started
+        self.network_dumper.switch.send((path, raw, filter))?;
         self.dump_start(self.network_dumper.sender.clone());
         Ok(())
     }
@@ -490,11 +613,15 @@ impl P2PNode {
     #[cfg(feature = "network_dump")]
     pub fn stop_dump(&self) -> anyhow::Result<()> {
         let path = std::path::PathBuf::new();
-        self.network_dumper.switch.send((path, false))?;
         self.dump_stop();
+        self.network_dumper.switch.send((path, false, DumpFilter::default()))?;
         Ok(())
     }
 
+    /// Check whether network data is currently being dumped.
+    #[cfg(feature = "network_dump")]
+    pub fn is_dump_active(&self) -> bool { self.network_dumper.active.load(Ordering::Relaxed) }
+
     /// Start dumping network data to the disk.
     #[cfg(feature = "network_dump")]
     pub fn dump_start(&self, log_dumper: Sender<DumpItem>) {
@@ -503,7 +630,10 @@ impl P2PNode {
 
     /// Stop dumping network data to the disk.
     #[cfg(feature = "network_dump")]
-    pub fn dump_stop(&self) { *write_or_die!(self.connection_handler.log_dumper) = None; }
+    pub fn dump_stop(&self) {
+        self.network_dumper.active.store(false, Ordering::Relaxed);
+        *write_or_die!(self.connection_handler.log_dumper) = None;
+    }
 
     /// Get the node's client version.
     pub fn get_version(&self) -> String { crate::VERSION.to_string() }
@@ -704,6 +834,7 @@ pub fn spawn(
                     last_peer_list_update = new_last_peer_update;
                 }
                 check_peer_states(&node, consensus);
+                verify_baker_peers(&node, consensus, false);
             }
 
             // perform socket reads and writes in parallel across connections
@@ -715,8 +846,12 @@ pub fn spawn(
             // housekeeping.
             if iterations_since_housekeeping >= 10 {
                 if Instant::now().duration_since(log_time)
-                    >= Duration::from_secs(node.config.housekeeping_interval)
+                    >= Duration::from_secs(node.config.limits().housekeeping_interval)
                 {
+                    if let Some(ref consensus) = consensus {
+                        // the bakers may have changed since the claims were checked
+                        verify_baker_peers(&node, consensus, true);
+                    }
                     let attempted_bootstrap = connection_housekeeping(&node);
                     if node.peer_type() != PeerType::Bootstrapper {
                         node.measure_connection_latencies()
@@ -817,8 +952,9 @@ fn process_conn_change(node: &Arc<P2PNode>, conn_change: ConnChange) {
 
             // Try to connect to each peer in turn.
             // If we are already connected to a peer, this will fail.
+            let desired_nodes_count = node.config.limits().desired_nodes_count as usize;
             for peer in peers {
-                if new_peers + curr_peer_count >= node.config.desired_nodes_count as usize {
+                if new_peers + curr_peer_count >= desired_nodes_count {
                     break;
                 }
 
@@ -861,6 +997,10 @@ pub fn attempt_bootstrap(node: &Arc<P2PNode>) {
         match bootstrap_nodes {
             Ok(nodes) => {
                 for addr in nodes {
+                    if !node.is_allowed_peer(addr.ip(), None) {
+                        warn!("Not bootstrapping from {}, which is not an allowed peer", addr);
+                        continue;
+                    }
                     info!("Using bootstrapper {}", addr);
                     node.register_conn_change(ConnChange::NewConn {
                         addr,
@@ -1,9 +1,12 @@
 //! Central node object handling.
 
+pub mod allowlist;
+pub mod baker_proof;
 pub mod bans;
 pub mod connectivity;
 pub mod maintenance;
 pub mod peers;
+pub mod transaction_relay;
 
 pub use self::maintenance::{Connections, P2PNode};
 
@@ -14,7 +17,8 @@ mod tests {
         p2p::bans::PersistedBanId,
         test_utils::*,
     };
-    use std::net::IpAddr;
+    use concordium_base::common::Deserial;
+    use std::{io::Cursor, net::IpAddr};
 
     #[test]
     fn test_ban_functionalities() -> anyhow::Result<()> {
@@ -65,4 +69,15 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_persisted_ban_id_fuzz_regressions() {
+        for (name, bytes) in fuzz_regressions("persisted_ban_id") {
+            assert!(
+                PersistedBanId::deserial(&mut Cursor::new(&bytes)).is_err(),
+                "{} was accepted",
+                name
+            );
+        }
+    }
 }
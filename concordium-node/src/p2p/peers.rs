@@ -118,7 +118,8 @@ impl P2PNode {
 /// work and the strange looking messages "already connected to ..." in the
 /// logs.
 pub fn check_peers(node: &Arc<P2PNode>, peer_stats: &[PeerStats], attempted_bootstrap: bool) {
-    debug!("I currently have {}/{} peers", peer_stats.len(), node.config.max_allowed_nodes);
+    let limits = node.config.limits();
+    debug!("I currently have {}/{} peers", peer_stats.len(), limits.max_allowed_nodes);
 
     if node.config.print_peers {
         node.print_stats(peer_stats);
@@ -127,7 +128,7 @@ pub fn check_peers(node: &Arc<P2PNode>, peer_stats: &[PeerStats], attempted_boot
     if node.self_peer.peer_type == PeerType::Node {
         let node_count = peer_stats.iter().filter(|peer| peer.peer_type == PeerType::Node).count();
 
-        if !node.config.no_net && node_count < node.config.desired_nodes_count as usize {
+        if !node.config.no_net && node_count < limits.desired_nodes_count as usize {
             if peer_stats.is_empty() {
                 if !attempted_bootstrap {
                     if !node.config.no_bootstrap_dns {
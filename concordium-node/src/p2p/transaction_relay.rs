@@ -0,0 +1,217 @@
+//! Pull-based transaction relay.
+//!
+//! Instead of relaying transactions in full, a node announces their digests
+//! to the peers that negotiated the `TRANSACTION_PULL_RELAY` capability. A
+//! peer requests the announced transactions it hasn't seen yet and receives
+//! them as broadcast packets, which it relays further in the same manner.
+//! The digests of the transactions a node has seen are kept in a
+//! deduplication queue, so that each transaction is only fetched once. While
+//! a request is outstanding, announcements of the same transaction by other
+//! peers are ignored; if the transaction doesn't arrive within
+//! [TRANSACTION_REQUEST_TIMEOUT], it is requested from the next peer that
+//! announces it.
+
+use crate::{
+    common::get_current_stamp,
+    configuration::{MAX_RELAYED_TRANSACTIONS, MAX_RELAYED_TRANSACTIONS_SIZE},
+    connection::Connection,
+    lock_or_die, netmsg,
+    network::{Capabilities, NetworkId, NetworkRequest, TransactionDigest},
+    p2p::P2PNode,
+    read_or_die, write_or_die,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The maximum number of digests in a single announcement or request.
+pub const MAX_TRANSACTION_DIGESTS: usize = 1024;
+
+/// How long to wait for a requested transaction before requesting it from
+/// another peer that announces it.
+pub const TRANSACTION_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Compute the digest of a transaction packet, i.e., a packet payload tagged
+/// as a transaction.
+pub fn transaction_digest(packet: &[u8]) -> TransactionDigest { Sha256::digest(packet).into() }
+
+/// The transactions recently announced by the node, kept so that they can be
+/// sent to the peers that request them. The oldest transactions are evicted
+/// once either the number of transactions or their total size exceeds its
+/// limit.
+#[derive(Default)]
+pub struct RelayedTransactions {
+    order:        VecDeque<TransactionDigest>,
+    transactions: HashMap<TransactionDigest, (NetworkId, Arc<[u8]>)>,
+    total_size:   usize,
+}
+
+impl RelayedTransactions {
+    /// Store an announced transaction packet.
+    pub fn insert(&mut self, digest: TransactionDigest, network_id: NetworkId, packet: Arc<[u8]>) {
+        if self.transactions.contains_key(&digest) {
+            return;
+        }
+        self.total_size += packet.len();
+        self.transactions.insert(digest, (network_id, packet));
+        self.order.push_back(digest);
+        while self.order.len() > MAX_RELAYED_TRANSACTIONS
+            || self.total_size > MAX_RELAYED_TRANSACTIONS_SIZE
+        {
+            if let Some((_, packet)) =
+                self.order.pop_front().and_then(|oldest| self.transactions.remove(&oldest))
+            {
+                self.total_size -= packet.len();
+            }
+        }
+    }
+
+    /// Get an announced transaction packet, if it's still available.
+    pub fn get(&self, digest: &TransactionDigest) -> Option<(NetworkId, Arc<[u8]>)> {
+        self.transactions.get(digest).cloned()
+    }
+
+    pub fn len(&self) -> usize { self.order.len() }
+
+    pub fn is_empty(&self) -> bool { self.order.is_empty() }
+}
+
+/// The transactions requested from peers that haven't arrived yet, along with
+/// the time of the request.
+#[derive(Default)]
+pub struct RequestedTransactions {
+    requested: HashMap<TransactionDigest, Instant>,
+}
+
+impl RequestedTransactions {
+    /// Record a request for the transaction, unless one is already
+    /// outstanding. Returns whether the transaction is to be requested.
+    /// Requests that haven't been answered within
+    /// [TRANSACTION_REQUEST_TIMEOUT] are no longer outstanding.
+    pub fn try_request(&mut self, digest: TransactionDigest, now: Instant) -> bool {
+        if self.requested.len() >= MAX_RELAYED_TRANSACTIONS {
+            self.requested.retain(|_, requested| {
+                now.duration_since(*requested) < TRANSACTION_REQUEST_TIMEOUT
+            });
+            if self.requested.len() >= MAX_RELAYED_TRANSACTIONS {
+                return false;
+            }
+        }
+        match self.requested.get(&digest) {
+            Some(requested) if now.duration_since(*requested) < TRANSACTION_REQUEST_TIMEOUT => {
+                false
+            }
+            _ => {
+                self.requested.insert(digest, now);
+                true
+            }
+        }
+    }
+
+    /// Forget the request for a transaction, once it has arrived.
+    pub fn remove(&mut self, digest: &TransactionDigest) { self.requested.remove(digest); }
+
+    pub fn len(&self) -> usize { self.requested.len() }
+
+    pub fn is_empty(&self) -> bool { self.requested.is_empty() }
+}
+
+impl P2PNode {
+    /// Check whether the node relays transactions by announcing them to the
+    /// peers that support it.
+    pub fn pulls_transactions(&self) -> bool {
+        self.config.capabilities.contains(Capabilities::TRANSACTION_PULL_RELAY)
+    }
+
+    /// Record that the transaction with the given digest was seen, which
+    /// also concludes an outstanding request for it. Returns whether it had
+    /// already been recorded.
+    pub(crate) fn mark_transaction_seen(&self, digest: &TransactionDigest) -> anyhow::Result<bool> {
+        lock_or_die!(self.connection_handler.requested_transactions).remove(digest);
+        write_or_die!(self.connection_handler.deduplication_queues.transaction_digests)
+            .check_and_insert(digest)
+    }
+
+    /// Check whether an announced transaction is to be requested, i.e., it
+    /// hasn't been seen, and there is no outstanding request for it. If so,
+    /// the request is recorded.
+    pub(crate) fn should_request_transaction(&self, digest: &TransactionDigest) -> bool {
+        if read_or_die!(self.connection_handler.deduplication_queues.transaction_digests)
+            .contains(digest)
+        {
+            return false;
+        }
+        lock_or_die!(self.connection_handler.requested_transactions)
+            .try_request(*digest, Instant::now())
+    }
+
+    /// Announce a transaction packet to the connections adhering to the
+    /// specified filter and keep it around for them to request it. Returns
+    /// the number of sent announcements.
+    pub(crate) fn announce_transaction(
+        &self,
+        network_id: NetworkId,
+        packet: &[u8],
+        conn_filter: &dyn Fn(&Connection) -> bool,
+    ) -> anyhow::Result<usize> {
+        let digest = transaction_digest(packet);
+        self.mark_transaction_seen(&digest)?;
+        lock_or_die!(self.connection_handler.relayed_transactions).insert(
+            digest,
+            network_id,
+            Arc::from(packet),
+        );
+
+        let announcement =
+            netmsg!(NetworkRequest, NetworkRequest::TransactionAnnouncement(vec![digest]));
+        let mut serialized = Vec::with_capacity(96);
+        announcement.serialize(&mut serialized)?;
+        let sent = self.send_over_all_connections(&serialized, conn_filter);
+        self.stats.tx_announcements_sent_add(sent as u64);
+
+        Ok(sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relayed_transactions_are_bounded() {
+        let mut relayed = RelayedTransactions::default();
+        let network_id = NetworkId::from(100);
+        for i in 0..=MAX_RELAYED_TRANSACTIONS as u32 {
+            let packet: Arc<[u8]> = Arc::from(&i.to_be_bytes()[..]);
+            relayed.insert(transaction_digest(&packet), network_id, packet);
+        }
+        assert_eq!(relayed.len(), MAX_RELAYED_TRANSACTIONS);
+        // the oldest transaction was evicted
+        assert!(relayed.get(&transaction_digest(&0u32.to_be_bytes())).is_none());
+        assert!(relayed.get(&transaction_digest(&1u32.to_be_bytes())).is_some());
+
+        // a single large transaction evicts as many as needed to fit
+        let large: Arc<[u8]> = Arc::from(vec![0u8; MAX_RELAYED_TRANSACTIONS_SIZE]);
+        relayed.insert(transaction_digest(&large), network_id, Arc::clone(&large));
+        assert_eq!(relayed.len(), 1);
+        assert!(relayed.get(&transaction_digest(&large)).is_some());
+    }
+
+    #[test]
+    fn unanswered_requests_expire() {
+        let mut requested = RequestedTransactions::default();
+        let digest = transaction_digest(b"transaction");
+        let now = Instant::now();
+        assert!(requested.try_request(digest, now));
+        // another announcement while the request is outstanding
+        assert!(!requested.try_request(digest, now + TRANSACTION_REQUEST_TIMEOUT / 2));
+        // the request went unanswered, so the transaction is requested again
+        assert!(requested.try_request(digest, now + TRANSACTION_REQUEST_TIMEOUT));
+
+        requested.remove(&digest);
+        assert!(requested.is_empty());
+    }
+}
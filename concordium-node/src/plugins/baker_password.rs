@@ -0,0 +1,158 @@
+//! Obtaining the password to decrypt the baker credentials.
+//!
+//! The password is taken from the first of the following sources that is
+//! configured:
+//! 1. the file descriptor given by `--baker-credentials-password-fd`,
+//! 2. the file given by `--baker-credentials-password-file`,
+//! 3. the environment variable [PASSWORD_ENV],
+//! 4. the systemd credential [PASSWORD_CREDENTIAL], i.e., the file of that name
+//!    in `$CREDENTIALS_DIRECTORY`.
+//!
+//! Otherwise the password is read from the terminal. A password read from a
+//! file descriptor or a file may end in a newline, which is not part of the
+//! password.
+
+use crate::configuration::BakerConfig;
+use anyhow::{bail, Context};
+use std::path::Path;
+
+/// The environment variable that can hold the password. The environment of the
+/// node is not modified, so the variable stays visible to the node's process,
+/// e.g. in `/proc/<pid>/environ` to the same user; prefer the other sources.
+pub const PASSWORD_ENV: &str = "CONCORDIUM_NODE_BAKER_CREDENTIALS_PASSWORD";
+
+/// The name of the systemd credential that can hold the password.
+pub const PASSWORD_CREDENTIAL: &str = "baker-credentials-password";
+
+/// Obtain the password to decrypt the baker credentials from the configured
+/// source.
+pub fn baker_credentials_password(conf: &BakerConfig) -> anyhow::Result<String> {
+    if let Some(fd) = conf.baker_credentials_password_fd {
+        return read_fd(fd).with_context(|| {
+            format!("Could not read the baker credentials password from file descriptor {}.", fd)
+        });
+    }
+    if let Some(path) = &conf.baker_credentials_password_file {
+        return read_private_file(path).with_context(|| {
+            format!("Could not read the baker credentials password from {}.", path.display())
+        });
+    }
+    if let Some(password) = std::env::var_os(PASSWORD_ENV) {
+        return password
+            .into_string()
+            .map_err(|_| anyhow::anyhow!("The password in {} is not valid UTF-8.", PASSWORD_ENV));
+    }
+    if let Some(directory) = std::env::var_os("CREDENTIALS_DIRECTORY") {
+        let path = Path::new(&directory).join(PASSWORD_CREDENTIAL);
+        if path.exists() {
+            return read_private_file(&path).with_context(|| {
+                format!(
+                    "Could not read the baker credentials password from the systemd credential {}.",
+                    path.display()
+                )
+            });
+        }
+    }
+    rpassword::read_password_from_tty(Some("Enter password to decrypt baker credentials: "))
+        .context(
+            "Could not read the baker credentials password from the terminal. If the node runs \
+             unattended, supply the password through a file, a file descriptor, the environment \
+             or a systemd credential.",
+        )
+}
+
+/// Remove a single trailing newline.
+fn strip_newline(mut password: String) -> String {
+    if password.ends_with('\n') {
+        password.pop();
+        if password.ends_with('\r') {
+            password.pop();
+        }
+    }
+    password
+}
+
+/// Read the password from a file descriptor. Standard input (0) can be used
+/// explicitly and is left open. Any other descriptor is handed to the node
+/// only to pass the password, so it is closed once the password is read.
+#[cfg(unix)]
+fn read_fd(fd: i32) -> anyhow::Result<String> {
+    use std::{io::Read, mem::ManuallyDrop, os::unix::io::FromRawFd};
+    anyhow::ensure!(fd >= 0, "Invalid file descriptor.");
+    anyhow::ensure!(
+        fd != libc::STDOUT_FILENO && fd != libc::STDERR_FILENO,
+        "The password cannot be read from standard output or standard error."
+    );
+    // Safety: `fcntl` with `F_GETFD` only queries the flags of the descriptor.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(std::io::Error::last_os_error()).context("Not an open file descriptor.");
+    }
+    // Safety: the descriptor is open. The file is not dropped implicitly, so the
+    // descriptor is only closed below, once the password was read successfully.
+    let mut file = ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+    let mut password = String::new();
+    file.read_to_string(&mut password)?;
+    if fd != libc::STDIN_FILENO {
+        drop(ManuallyDrop::into_inner(file));
+    }
+    Ok(strip_newline(password))
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> anyhow::Result<String> {
+    bail!("Reading the password from a file descriptor is only supported on Unix.")
+}
+
+/// Read a file that must only be accessible by its owner.
+fn read_private_file(path: &Path) -> anyhow::Result<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            bail!(
+                "The file is accessible by other users than its owner (mode {:o}). Restrict its \
+                 permissions, e.g., with `chmod 600`.",
+                mode & 0o777
+            );
+        }
+    }
+    Ok(strip_newline(std::fs::read_to_string(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_file_permissions_and_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        std::fs::write(&path, "secret\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(read_private_file(&path).is_err());
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        assert_eq!(read_private_file(&path).unwrap(), "secret");
+        assert_eq!(strip_newline("secret\r\n".to_owned()), "secret");
+        assert_eq!(strip_newline("secret\n\n".to_owned()), "secret\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn password_fd_is_validated() {
+        use std::{io::Write, os::unix::io::FromRawFd};
+        assert!(read_fd(libc::STDOUT_FILENO).is_err());
+        assert!(read_fd(libc::STDERR_FILENO).is_err());
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let mut writer = unsafe { std::fs::File::from_raw_fd(fds[1]) };
+        writer.write_all(b"secret\n").unwrap();
+        drop(writer);
+        assert_eq!(read_fd(fds[0]).unwrap(), "secret");
+    }
+}
@@ -19,6 +19,7 @@ use crate::{
         },
         messaging::{ConsensusMessage, DistributionMode, MessageType},
     },
+    lock_or_die,
     p2p::{
         connectivity::{send_broadcast_message, send_direct_message},
         P2PNode,
@@ -33,8 +34,13 @@ use std::{
     io::{Cursor, Read},
     path::Path,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
+/// How often the epoch of the best block is checked while a switch to other
+/// baker credentials is pending.
+const BAKER_CREDENTIALS_EPOCH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Initializes the consensus layer with the given setup.
 pub fn start_consensus_layer(
     conf: &configuration::BakerConfig,
@@ -93,47 +99,158 @@ pub fn get_baker_data(
     app_prefs: &configuration::AppPreferences,
     conf: &configuration::BakerConfig,
 ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let genesis_data = read_genesis_data(app_prefs, conf)?;
+    let private_data = read_baker_credentials(conf)?;
+    Ok((genesis_data, private_data))
+}
+
+/// Reads the genesis data from the configured file.
+pub fn read_genesis_data(
+    app_prefs: &configuration::AppPreferences,
+    conf: &configuration::BakerConfig,
+) -> anyhow::Result<Vec<u8>> {
     let mut genesis_loc = app_prefs.get_data_dir().to_path_buf();
     // if the genesis_data_file is absolute this replaces the entire path
     // otherwise the path is appended to the data directory
     genesis_loc.push(&conf.genesis_data_file);
 
-    let genesis_data = match std::fs::File::open(&genesis_loc) {
+    match std::fs::File::open(&genesis_loc) {
         Ok(mut file) => {
             let mut read_data = vec![];
             match file.read_to_end(&mut read_data) {
-                Ok(_) => read_data,
+                Ok(_) => Ok(read_data),
                 Err(e) => bail!("Cannot not read genesis file ({})!", e),
             }
         }
         Err(e) => bail!("Cannot open the genesis file ({})", e),
+    }
+}
+
+/// Reads the baker credentials from the configured file, if any, decrypting
+/// them if so configured. The password is obtained as described in
+/// [baker_password](super::baker_password), which may query for it.
+pub fn read_baker_credentials(
+    conf: &configuration::BakerConfig,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let path = if let Some(path) = &conf.baker_credentials_file {
+        path
+    } else {
+        return Ok(None);
+    };
+    let read_data = match std::fs::read(&path) {
+        Ok(read_data) => read_data,
+        Err(e) => bail!("Cannot open the baker credentials file ({})!", e),
     };
+    if conf.decrypt_baker_credentials {
+        decrypt_baker_credentials(&read_data, || {
+            super::baker_password::baker_credentials_password(conf)
+        })
+        .map(Some)
+    } else {
+        Ok(Some(read_data))
+    }
+}
 
-    let private_data = if let Some(path) = &conf.baker_credentials_file {
-        let read_data = match std::fs::read(&path) {
-            Ok(read_data) => read_data,
-            Err(e) => bail!("Cannot open the baker credentials file ({})!", e),
-        };
-        if conf.decrypt_baker_credentials {
-            let et = serde_json::from_slice(&read_data)?;
-            let pass = rpassword::read_password_from_tty(Some(
-                "Enter password to decrypt baker credentials: ",
-            ))?;
-            match concordium_base::common::encryption::decrypt(&pass.into(), &et) {
-                Ok(d) => Some(d),
-                Err(_) => bail!(
-                    "Could not decrypt baker credentials. Most likely the password you provided \
-                     is incorrect."
-                ),
+/// Decrypts encrypted baker credentials. The password is only obtained once
+/// the data is known to be encrypted credentials.
+pub fn decrypt_baker_credentials(
+    data: &[u8],
+    password: impl FnOnce() -> anyhow::Result<String>,
+) -> anyhow::Result<Vec<u8>> {
+    let et = serde_json::from_slice(data)?;
+    let pass = password()?;
+    match concordium_base::common::encryption::decrypt(&pass.into(), &et) {
+        Ok(d) => Ok(d),
+        Err(_) => bail!(
+            "Could not decrypt baker credentials. Most likely the password provided is incorrect."
+        ),
+    }
+}
+
+/// Use the given (decrypted) baker credentials from now on, without restarting
+/// consensus. Consensus uses them for baking, finalization and the baker
+/// status, and the node uses them to prove to peers that it is a baker. A
+/// switch that is pending, see [schedule_baker_credentials], is cancelled.
+pub fn switch_baker_credentials(
+    node: &P2PNode,
+    consensus: &ConsensusContainer,
+    credentials: &[u8],
+) -> anyhow::Result<()> {
+    if let Some(pending) = lock_or_die!(consensus.pending_baker_credentials).take() {
+        pending.abort();
+    }
+    apply_baker_credentials(node, consensus, credentials)
+}
+
+/// Switch to the given (decrypted) baker credentials, as with
+/// [switch_baker_credentials], once the best block is in the given epoch of the
+/// given genesis index or later. Epochs restart at every protocol update, so
+/// the switch also happens if the best block reaches a later genesis index
+/// first. This replaces a switch that is still pending.
+pub fn schedule_baker_credentials(
+    node: Arc<P2PNode>,
+    consensus: ConsensusContainer,
+    credentials: Vec<u8>,
+    genesis_index: u32,
+    epoch: u64,
+) {
+    info!(
+        "Switching to other baker credentials in epoch {} of genesis index {}.",
+        epoch, genesis_index
+    );
+    let task = {
+        let consensus = consensus.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(BAKER_CREDENTIALS_EPOCH_CHECK_INTERVAL);
+            loop {
+                // The query is a blocking call into consensus, so it does not run on
+                // the threads of the async runtime.
+                let current = {
+                    let consensus = consensus.clone();
+                    tokio::task::spawn_blocking(move || consensus.best_block_epoch()).await
+                };
+                match current {
+                    Ok(current) if current >= (genesis_index, epoch) => break,
+                    Ok(_) => {
+                        interval.tick().await;
+                    }
+                    Err(e) => {
+                        error!("Could not check the epoch of the best block: {}", e);
+                        return;
+                    }
+                };
             }
-        } else {
-            Some(read_data)
-        }
-    } else {
-        None
+            let switched = tokio::task::spawn_blocking(move || {
+                apply_baker_credentials(&node, &consensus, &credentials)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+            if let Err(e) = switched {
+                error!(
+                    "Could not switch to the baker credentials scheduled for epoch {} of genesis \
+                     index {}: {:#}",
+                    epoch, genesis_index, e
+                );
+            }
+        })
     };
+    if let Some(previous) = lock_or_die!(consensus.pending_baker_credentials).replace(task) {
+        previous.abort();
+    }
+}
 
-    Ok((genesis_data, private_data))
+fn apply_baker_credentials(
+    node: &P2PNode,
+    consensus: &ConsensusContainer,
+    credentials: &[u8],
+) -> anyhow::Result<()> {
+    consensus.set_baker_credentials(credentials)?;
+    if let Err(e) = node.set_baker_credentials(credentials) {
+        warn!("Handshakes will not include a baker proof: {:#}", e);
+    }
+    info!("Switched to other baker credentials.");
+    Ok(())
 }
 
 /// Handles packets coming from other peers.
@@ -143,6 +260,7 @@ pub fn handle_pkt_out(
     peer_id: RemotePeerId, // id of the peer that sent the message.
     msg: Vec<u8>,
     is_broadcast: bool,
+    from_baker: bool, // whether the peer is a verified baker.
 ) -> anyhow::Result<()> {
     ensure!(!msg.is_empty(), "Packet payload can't be empty");
     let consensus_type = u8::deserial(&mut Cursor::new(&msg[..1]))?;
@@ -185,6 +303,25 @@ pub fn handle_pkt_out(
             node.stats.inbound_low_priority_consensus_inc();
         }
     } else {
+        // messages from verified bakers skip ahead of the high priority queue, unless
+        // their own queue is full.
+        let request = if from_baker {
+            match CALLBACK_QUEUE.send_in_baker_priority_message(request) {
+                Ok(()) => {
+                    node.stats.inbound_high_priority_consensus_inc();
+                    return Ok(());
+                }
+                Err(e) => match e.downcast::<TrySendError<QueueMsg<ConsensusMessage>>>()? {
+                    TrySendError::Full(QueueMsg::Relay(request)) => request,
+                    TrySendError::Full(QueueMsg::Stop) => return Ok(()),
+                    TrySendError::Disconnected(_) => {
+                        panic!("Baker priority consensus queue has been shutdown!")
+                    }
+                },
+            }
+        } else {
+            request
+        };
         // high priority message
         if let Err(e) = CALLBACK_QUEUE.send_in_high_priority_message(request) {
             match e.downcast::<TrySendError<QueueMsg<ConsensusMessage>>>()? {
@@ -242,7 +379,7 @@ pub fn handle_consensus_inbound_msg(
 ) -> anyhow::Result<()> {
     // If the drop_rebroadcast_probability parameter is set, do not
     // rebroadcast the packet to the network with the given chance.
-    let drop_message = match node.config.drop_rebroadcast_probability {
+    let drop_message = match node.config.limits().drop_rebroadcast_probability {
         Some(probability) => {
             use rand::distributions::{Bernoulli, Distribution};
             if Bernoulli::new(probability)?.sample(&mut rand::thread_rng()) {
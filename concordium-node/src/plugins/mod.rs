@@ -1,3 +1,4 @@
 //! Client plugins.
 
+pub mod baker_password;
 pub mod consensus;
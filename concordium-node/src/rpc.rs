@@ -978,6 +978,7 @@ impl P2p for RpcServerImpl {
                     &file_path
                 },
                 req.get_ref().raw,
+                crate::dumper::DumpFilter::default(),
             )
             .is_ok();
         Ok(Response::new(BoolResponse {
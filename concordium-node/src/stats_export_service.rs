@@ -15,7 +15,7 @@ use hyper::Body;
 use prometheus::{
     self,
     core::{AtomicI64, AtomicU64, GenericGauge},
-    Encoder, IntCounter, IntGauge, Opts, Registry, TextEncoder,
+    Counter, Encoder, IntCounter, IntGauge, Opts, Registry, TextEncoder,
 };
 use std::{net::SocketAddr, sync::RwLock, thread, time};
 
@@ -63,6 +63,19 @@ pub struct StatsExportService {
     bytes_sent: GenericGauge<AtomicU64>,
     avg_bps_in: GenericGauge<AtomicU64>,
     avg_bps_out: GenericGauge<AtomicU64>,
+    compression_bytes_saved_sent: IntCounter,
+    compression_bytes_saved_received: IntCounter,
+    compression_time: Counter,
+    tx_announcements_sent: IntCounter,
+    tx_announcements_received: IntCounter,
+    tx_requests_sent: IntCounter,
+    tx_requests_served: IntCounter,
+    tx_requests_missed: IntCounter,
+    grpc_blocks_dropped: IntCounter,
+    grpc_finalized_blocks_dropped: IntCounter,
+    grpc_query_cache_hits: IntCounter,
+    grpc_query_cache_misses: IntCounter,
+    grpc_query_cache_size: IntGauge,
 }
 
 impl StatsExportService {
@@ -201,6 +214,90 @@ impl StatsExportService {
         let avg_bps_out = GenericGauge::with_opts(avg_bps_out_opts)?;
         registry.register(Box::new(avg_bps_out.clone()))?;
 
+        let cbss_opts = Opts::new(
+            "compression_bytes_saved_sent",
+            "bytes saved by compressing outgoing network messages",
+        );
+        let cbss = IntCounter::with_opts(cbss_opts)?;
+        registry.register(Box::new(cbss.clone()))?;
+
+        let cbsr_opts = Opts::new(
+            "compression_bytes_saved_received",
+            "bytes saved by peers compressing incoming network messages",
+        );
+        let cbsr = IntCounter::with_opts(cbsr_opts)?;
+        registry.register(Box::new(cbsr.clone()))?;
+
+        let ct_opts = Opts::new(
+            "compression_seconds",
+            "time spent compressing and decompressing network messages",
+        );
+        let ct = Counter::with_opts(ct_opts)?;
+        registry.register(Box::new(ct.clone()))?;
+
+        let tas_opts = Opts::new(
+            "transaction_announcements_sent",
+            "transaction digests announced to peers using the pull-based relay",
+        );
+        let tas = IntCounter::with_opts(tas_opts)?;
+        registry.register(Box::new(tas.clone()))?;
+
+        let tar_opts = Opts::new(
+            "transaction_announcements_received",
+            "transaction digests announced by peers using the pull-based relay",
+        );
+        let tar = IntCounter::with_opts(tar_opts)?;
+        registry.register(Box::new(tar.clone()))?;
+
+        let trs_opts =
+            Opts::new("transaction_requests_sent", "announced transactions requested from peers");
+        let trs = IntCounter::with_opts(trs_opts)?;
+        registry.register(Box::new(trs.clone()))?;
+
+        let trsv_opts =
+            Opts::new("transaction_requests_served", "transactions sent to peers upon request");
+        let trsv = IntCounter::with_opts(trsv_opts)?;
+        registry.register(Box::new(trsv.clone()))?;
+
+        let trm_opts = Opts::new(
+            "transaction_requests_missed",
+            "transactions requested by peers that were no longer available",
+        );
+        let trm = IntCounter::with_opts(trm_opts)?;
+        registry.register(Box::new(trm.clone()))?;
+
+        let gbd_opts = Opts::new(
+            "grpc_blocks_stream_dropped",
+            "arrived blocks not delivered to lagging GRPC clients",
+        );
+        let gbd = IntCounter::with_opts(gbd_opts)?;
+        registry.register(Box::new(gbd.clone()))?;
+
+        let gfbd_opts = Opts::new(
+            "grpc_finalized_blocks_stream_dropped",
+            "finalized blocks not delivered to lagging GRPC clients",
+        );
+        let gfbd = IntCounter::with_opts(gfbd_opts)?;
+        registry.register(Box::new(gfbd.clone()))?;
+
+        let gqch_opts = Opts::new(
+            "grpc_query_cache_hits",
+            "GRPC queries about finalized blocks answered from the cache",
+        );
+        let gqch = IntCounter::with_opts(gqch_opts)?;
+        registry.register(Box::new(gqch.clone()))?;
+
+        let gqcm_opts = Opts::new(
+            "grpc_query_cache_misses",
+            "cacheable GRPC queries that were not answered from the cache",
+        );
+        let gqcm = IntCounter::with_opts(gqcm_opts)?;
+        registry.register(Box::new(gqcm.clone()))?;
+
+        let gqcs_opts = Opts::new("grpc_query_cache_size", "size of the GRPC query cache in bytes");
+        let gqcs = IntGauge::with_opts(gqcs_opts)?;
+        registry.register(Box::new(gqcs.clone()))?;
+
         Ok(StatsExportService {
             registry,
             pkts_received_counter: prc,
@@ -220,6 +317,19 @@ impl StatsExportService {
             bytes_sent: bsc,
             avg_bps_in,
             avg_bps_out,
+            compression_bytes_saved_sent: cbss,
+            compression_bytes_saved_received: cbsr,
+            compression_time: ct,
+            tx_announcements_sent: tas,
+            tx_announcements_received: tar,
+            tx_requests_sent: trs,
+            tx_requests_served: trsv,
+            tx_requests_missed: trm,
+            grpc_blocks_dropped: gbd,
+            grpc_finalized_blocks_dropped: gfbd,
+            grpc_query_cache_hits: gqch,
+            grpc_query_cache_misses: gqcm,
+            grpc_query_cache_size: gqcs,
         })
     }
 
@@ -314,6 +424,96 @@ impl StatsExportService {
     /// Sets the value of average outbound throughput.
     pub fn set_avg_bps_out(&self, value: u64) { self.avg_bps_out.set(value); }
 
+    /// Gets the number of bytes saved by compressing outgoing messages.
+    pub fn get_compression_bytes_saved_sent(&self) -> u64 {
+        self.compression_bytes_saved_sent.get()
+    }
+
+    /// Increases the number of bytes saved by compressing outgoing messages.
+    pub fn compression_bytes_saved_sent_add(&self, value: u64) {
+        self.compression_bytes_saved_sent.inc_by(value);
+    }
+
+    /// Gets the number of bytes saved by peers compressing incoming messages.
+    pub fn get_compression_bytes_saved_received(&self) -> u64 {
+        self.compression_bytes_saved_received.get()
+    }
+
+    /// Increases the number of bytes saved by peers compressing incoming
+    /// messages.
+    pub fn compression_bytes_saved_received_add(&self, value: u64) {
+        self.compression_bytes_saved_received.inc_by(value);
+    }
+
+    /// Increases the time (in seconds) spent on compression and
+    /// decompression.
+    pub fn compression_time_add(&self, seconds: f64) { self.compression_time.inc_by(seconds); }
+
+    /// Gets the number of transaction digests announced to peers.
+    pub fn get_tx_announcements_sent(&self) -> u64 { self.tx_announcements_sent.get() }
+
+    /// Increases the number of transaction digests announced to peers.
+    pub fn tx_announcements_sent_add(&self, value: u64) {
+        self.tx_announcements_sent.inc_by(value);
+    }
+
+    /// Increases the number of transaction digests announced by peers.
+    pub fn tx_announcements_received_add(&self, value: u64) {
+        self.tx_announcements_received.inc_by(value);
+    }
+
+    /// Gets the number of transactions requested from peers.
+    pub fn get_tx_requests_sent(&self) -> u64 { self.tx_requests_sent.get() }
+
+    /// Increases the number of transactions requested from peers.
+    pub fn tx_requests_sent_add(&self, value: u64) { self.tx_requests_sent.inc_by(value); }
+
+    /// Gets the number of transactions sent to peers upon request.
+    pub fn get_tx_requests_served(&self) -> u64 { self.tx_requests_served.get() }
+
+    /// Increases the number of transactions sent to peers upon request.
+    pub fn tx_requests_served_inc(&self) { self.tx_requests_served.inc(); }
+
+    /// Increases the number of requested transactions that were no longer
+    /// available.
+    pub fn tx_requests_missed_inc(&self) { self.tx_requests_missed.inc(); }
+
+    /// Gets the number of arrived blocks not delivered to lagging GRPC
+    /// clients.
+    pub fn get_grpc_blocks_dropped(&self) -> u64 { self.grpc_blocks_dropped.get() }
+
+    /// Increases the number of arrived blocks not delivered to lagging GRPC
+    /// clients.
+    pub fn grpc_blocks_dropped_add(&self, value: u64) { self.grpc_blocks_dropped.inc_by(value); }
+
+    /// Gets the number of finalized blocks not delivered to lagging GRPC
+    /// clients.
+    pub fn get_grpc_finalized_blocks_dropped(&self) -> u64 {
+        self.grpc_finalized_blocks_dropped.get()
+    }
+
+    /// Increases the number of finalized blocks not delivered to lagging GRPC
+    /// clients.
+    pub fn grpc_finalized_blocks_dropped_add(&self, value: u64) {
+        self.grpc_finalized_blocks_dropped.inc_by(value);
+    }
+
+    /// Gets the number of GRPC queries answered from the cache.
+    pub fn get_grpc_query_cache_hits(&self) -> u64 { self.grpc_query_cache_hits.get() }
+
+    /// Increases the number of GRPC queries answered from the cache.
+    pub fn grpc_query_cache_hits_inc(&self) { self.grpc_query_cache_hits.inc(); }
+
+    /// Gets the number of cacheable GRPC queries not answered from the cache.
+    pub fn get_grpc_query_cache_misses(&self) -> u64 { self.grpc_query_cache_misses.get() }
+
+    /// Increases the number of cacheable GRPC queries not answered from the
+    /// cache.
+    pub fn grpc_query_cache_misses_inc(&self) { self.grpc_query_cache_misses.inc(); }
+
+    /// Sets the size of the GRPC query cache in bytes.
+    pub fn set_grpc_query_cache_size(&self, value: i64) { self.grpc_query_cache_size.set(value); }
+
     fn metrics(state: State) -> (State, String) {
         let state_data = PrometheusStateData::borrow_from(&state);
         let encoder = TextEncoder::new();
@@ -19,6 +19,7 @@ use concordium_base::{common::Serial, hashes::BlockHash};
 use std::{
     io::Write,
     net::TcpListener,
+    path::Path,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -102,9 +103,18 @@ pub fn make_node_and_sync(
     networks: Vec<u16>,
     node_type: PeerType,
     regenesis_blocks: Vec<BlockHash>,
+) -> anyhow::Result<(Arc<P2PNode>, DeletePermission)> {
+    make_node_and_sync_with_config(get_test_config(port, networks), node_type, regenesis_blocks)
+}
+
+/// Like `make_node_and_sync`, but starting from the given config object, which
+/// should be obtained via `get_test_config`.
+pub fn make_node_and_sync_with_config(
+    mut config: Config,
+    node_type: PeerType,
+    regenesis_blocks: Vec<BlockHash>,
 ) -> anyhow::Result<(Arc<P2PNode>, DeletePermission)> {
     // locally-run tests and benches can be polled with a much greater frequency
-    let mut config = get_test_config(port, networks);
     config.cli.no_network = true;
     config.cli.poll_interval = 1;
     config.connection.housekeeping_interval = 10;
@@ -158,3 +168,21 @@ pub fn create_random_packet(size: usize) -> NetworkMessage {
         message:     generate_fake_block(size).unwrap(),
     })
 }
+
+/// Reads the inputs checked in under `fuzz/regressions/<target>`. These are
+/// inputs that crashed the given fuzz target at some point and must now be
+/// handled gracefully.
+pub fn fuzz_regressions(target: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions").join(target);
+    let mut inputs = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Cannot read {}: {}", dir.display(), e))
+        .map(|entry| {
+            let path = entry.expect("Cannot read a regression input.").path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let bytes = std::fs::read(&path).expect("Cannot read a regression input.");
+            (name, bytes)
+        })
+        .collect::<Vec<_>>();
+    inputs.sort();
+    inputs
+}